@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use hgrep::chunk::{File, LineMatch};
-use hgrep::printer::{Printer, PrinterOptions, TermColorSupport, TextWrapMode};
+use hgrep::printer::{BorderStyle, Printer, PrinterOptions, TermColorSupport, TextWrapMode};
 use hgrep::ripgrep;
 use hgrep::syntect::{LockableWrite, SyntectAssets, SyntectPrinter};
 use hgrep_bench::node_modules_path;
@@ -34,10 +34,11 @@ impl<'a> LockableWrite<'a> for Sink {
 }
 
 fn get_opts() -> PrinterOptions<'static> {
-    let mut opts = PrinterOptions::default();
-    opts.color_support = TermColorSupport::True;
-    opts.term_width = 80;
-    opts
+    PrinterOptions {
+        color_support: TermColorSupport::True,
+        term_width: 80,
+        ..Default::default()
+    }
 }
 
 fn create_files_for_contents(contents: String, path: &Path, per_lines: usize) -> Vec<File> {
@@ -102,6 +103,59 @@ fn print_files(c: &mut Criterion) {
     });
 }
 
+fn print_long_token(c: &mut Criterion) {
+    #[inline]
+    fn run(file: File, assets: SyntectAssets, opts: PrinterOptions<'_>) {
+        let sink = Sink(Mutex::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(assets, sink, opts);
+        printer.print(file).unwrap();
+        let buf = mem::take(printer.writer_mut()).0.into_inner().unwrap();
+        assert!(!buf.is_empty());
+    }
+
+    let assets = SyntectAssets::load(None).unwrap();
+
+    // One huge token like a base64 blob embedded in a string literal
+    let blob = "QmFzZTY0IGJsb2I".repeat(100_000);
+    let contents = format!("const BLOB: &str = \"{}\";\n", blob);
+    let file = File::new(
+        Path::new("blob.rs").into(),
+        vec![LineMatch::lnum(1)],
+        vec![(1, 1)],
+        contents.into_bytes(),
+    );
+
+    c.bench_function("syntect::long-token-wrap", |b| {
+        b.iter(|| run(file.clone(), assets.clone(), get_opts()))
+    });
+
+    // The text past the right edge is still written without border. See `Drawer::draw_line`
+    c.bench_function("syntect::long-token-no-wrap", |b| {
+        b.iter(|| {
+            let mut opts = get_opts();
+            opts.text_wrap = TextWrapMode::Never;
+            run(file.clone(), assets.clone(), opts)
+        })
+    });
+
+    c.bench_function("syntect::long-token-clip", |b| {
+        b.iter(|| {
+            let mut opts = get_opts();
+            opts.text_wrap = TextWrapMode::Never;
+            opts.border = BorderStyle::Rounded;
+            run(file.clone(), assets.clone(), opts)
+        })
+    });
+
+    c.bench_function("syntect::long-token-truncate", |b| {
+        b.iter(|| {
+            let mut opts = get_opts();
+            opts.auto_truncate_after = Some(2);
+            run(file.clone(), assets.clone(), opts)
+        })
+    });
+}
+
 fn with_ripgrep(c: &mut Criterion) {
     #[inline]
     fn run_ripgrep(pat: &str, dir: &Path, opts: PrinterOptions<'_>) -> bool {
@@ -144,5 +198,11 @@ fn with_ripgrep(c: &mut Criterion) {
     });
 }
 
-criterion_group!(syntect, print_files, with_ripgrep, load_assets);
+criterion_group!(
+    syntect,
+    print_files,
+    print_long_token,
+    with_ripgrep,
+    load_assets
+);
 criterion_main!(syntect);
@@ -1,6 +1,7 @@
 use crate::chunk::File;
-use crate::printer::{Printer, PrinterOptions, TermColorSupport, TextWrapMode};
-use anyhow::{Error, Result};
+use crate::dirs::bat_cache_dir;
+use crate::error::{HgrepError, Result};
+use crate::printer::{FirstOnly, Printer, PrinterOptions, TermColorSupport, TextWrapMode};
 use bat::assets::HighlightingAssets;
 use bat::config::{Config, VisibleLines};
 use bat::controller::Controller;
@@ -8,49 +9,8 @@ use bat::input::Input;
 use bat::line_range::{HighlightedLineRanges, LineRange, LineRanges};
 use bat::style::{StyleComponent, StyleComponents};
 use bat::WrappingMode;
-use std::env;
-use std::fmt;
-use std::path::PathBuf;
 use std::sync::Mutex;
 
-#[derive(Debug)]
-pub struct BatPrintError {
-    path: PathBuf,
-    cause: Option<String>,
-}
-
-impl std::error::Error for BatPrintError {}
-
-impl fmt::Display for BatPrintError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Could not print file {:?}", &self.path)?;
-        if let Some(cause) = &self.cause {
-            write!(f, ". Caused by: {}", cause)?;
-        }
-        Ok(())
-    }
-}
-
-// Brought from bat/src/bin/bat/directories.rs dde770aa210ab9eeb5469e152cec6fcaab374d84
-fn get_cache_dir() -> Option<PathBuf> {
-    // on all OS prefer BAT_CACHE_PATH if set
-    let cache_dir_op = env::var_os("BAT_CACHE_PATH").map(PathBuf::from);
-    if cache_dir_op.is_some() {
-        return cache_dir_op;
-    }
-
-    #[cfg(target_os = "macos")]
-    let cache_dir_op = env::var_os("XDG_CACHE_HOME")
-        .map(PathBuf::from)
-        .filter(|p| p.is_absolute())
-        .or_else(|| dirs_next::home_dir().map(|d| d.join(".cache")));
-
-    #[cfg(not(target_os = "macos"))]
-    let cache_dir_op = dirs_next::cache_dir();
-
-    cache_dir_op.map(|d| d.join("bat"))
-}
-
 pub struct BatPrinter<'main> {
     opts: PrinterOptions<'main>,
     config: Config<'main>,
@@ -81,28 +41,40 @@ impl<'main> BatPrinter<'main> {
 
         let mut config = Config {
             colored_output: true,
-            term_width: opts.term_width as usize,
+            term_width: opts.output_width.unwrap_or(opts.term_width) as usize,
             style_components: StyleComponents::new(styles),
             tab_width: opts.tab_width,
             true_color: opts.color_support == TermColorSupport::True,
             wrapping_mode,
+            language: opts.language,
             ..Default::default()
         };
 
-        if let Some(theme) = &opts.theme {
+        if let Some(theme) = opts.theme_name() {
             config.theme = theme.to_string();
         } else if opts.color_support == TermColorSupport::Ansi16 {
             config.theme = "ansi".to_string();
         }
 
         let assets = if opts.custom_assets {
-            get_cache_dir()
+            bat_cache_dir()
                 .and_then(|path| HighlightingAssets::from_cache(&path).ok())
                 .unwrap_or_else(HighlightingAssets::from_binary)
         } else {
             HighlightingAssets::from_binary()
         };
 
+        // Fall back only when the theme is specified explicitly. Otherwise bat's default theme is used
+        if opts.theme_name().is_some() && !assets.themes().any(|t| t == config.theme) {
+            if let Some(theme) = opts
+                .theme_fallbacks
+                .iter()
+                .find(|f| assets.themes().any(|t| t == **f))
+            {
+                config.theme = theme.to_string();
+            }
+        }
+
         Self {
             opts,
             assets,
@@ -127,9 +99,15 @@ impl<'main> BatPrinter<'main> {
         Ok(())
     }
 
-    pub fn print(&self, file: File) -> Result<()> {
-        if file.chunks.is_empty() || file.line_matches.is_empty() {
-            return Ok(()); // Ensure to print some match
+    pub fn print(&self, mut file: File) -> Result<()> {
+        if file.chunks.is_empty() {
+            return Ok(()); // Ensure to print some lines
+        }
+        if self.opts.first_only == FirstOnly::Match {
+            file.retain_first_match();
+        }
+        if self.opts.trim_chunk_blanks {
+            file.trim_blank_context();
         }
 
         // XXX: We don't use `bat::PrettyPrinter`.
@@ -150,12 +128,13 @@ impl<'main> BatPrinter<'main> {
         //    for more details.
 
         let mut config = self.config.clone();
+        config.language = file.language.as_deref().or(config.language);
 
         let ranges = file
             .chunks
             .iter()
             .map(|(s, e)| LineRange::new(*s as usize, *e as usize));
-        let ranges = if self.opts.first_only {
+        let ranges = if self.opts.first_only != FirstOnly::None {
             ranges.take(1).collect()
         } else {
             ranges.collect()
@@ -177,7 +156,10 @@ impl<'main> BatPrinter<'main> {
         config.highlighted_lines = HighlightedLineRanges(LineRanges::from(ranges));
 
         if !self.opts.grid {
-            print!("\n\n"); // Empty lines as files separator
+            use crate::io::IgnoreBrokenPipe;
+            use std::io::{self, Write};
+            // Empty lines as files separator. Note that `print!` panics on BrokenPipe
+            io::stdout().write_all(b"\n\n").ignore_broken_pipe()?;
         }
 
         let controller = Controller::new(&config, &self.assets);
@@ -186,21 +168,21 @@ impl<'main> BatPrinter<'main> {
         // XXX: bat's Error type cannot be converted to anyhow::Error since it does not implement Sync
         match controller.run(vec![input]) {
             Ok(true) => Ok(()),
-            Ok(false) => Err(Error::new(BatPrintError {
+            Ok(false) => Err(HgrepError::Print {
                 path: file.path,
                 cause: None,
-            })),
-            Err(err) => Err(Error::new(BatPrintError {
+            }),
+            Err(err) => Err(HgrepError::Print {
                 path: file.path,
                 cause: Some(format!("{}", err)),
-            })),
+            }),
         }
     }
 }
 
 impl<'main> Printer for Mutex<BatPrinter<'main>> {
-    fn print(&self, file: File) -> Result<()> {
-        self.lock().unwrap().print(file)
+    fn print(&self, file: File) -> anyhow::Result<()> {
+        Ok(self.lock().unwrap().print(file)?)
     }
 }
 
@@ -208,6 +190,7 @@ impl<'main> Printer for Mutex<BatPrinter<'main>> {
 mod tests {
     use super::*;
     use crate::chunk::LineMatch;
+    use std::path::PathBuf;
 
     fn sample_file() -> File {
         let path = PathBuf::from("test.rs");
@@ -246,4 +229,12 @@ mod tests {
         let f = File::new(PathBuf::from("x.txt"), vec![], vec![], vec![]);
         p.print(f).unwrap();
     }
+
+    #[test]
+    fn test_print_chunk_without_matched_lines() {
+        let p = BatPrinter::new(PrinterOptions::default());
+        let mut f = sample_file();
+        f.line_matches = vec![].into_boxed_slice();
+        p.print(f).unwrap();
+    }
 }
@@ -14,6 +14,11 @@ pub struct LineMatch {
     pub line_number: u64,
     // Byte offsets of start/end positions within the line. Inherit from GrepMatch
     pub ranges: Vec<(usize, usize)>,
+    // New contents of the line for previewing search-and-replace. `None` means the line is not replaced
+    pub replacement: Option<Vec<u8>>,
+    // Index of the search pattern which matched each range in `ranges`. Empty means all ranges are matched by the first
+    // pattern
+    pub patterns: Vec<usize>,
 }
 
 impl LineMatch {
@@ -21,14 +26,28 @@ impl LineMatch {
         Self {
             line_number,
             ranges,
+            replacement: None,
+            patterns: vec![],
         }
     }
 
     pub fn lnum(line_number: u64) -> Self {
-        Self {
-            line_number,
-            ranges: vec![],
-        }
+        Self::new(line_number, vec![])
+    }
+
+    /// Set the contents of the line after replacement. A printer which supports replace previews shows the line before
+    /// and after the replacement. A trailing newline in `replacement` is not necessary.
+    pub fn with_replacement(mut self, replacement: impl Into<Vec<u8>>) -> Self {
+        self.replacement = Some(replacement.into());
+        self
+    }
+
+    /// Set the index of the search pattern for each range of the line. A printer which supports multiple patterns
+    /// paints the ranges with the colors of their patterns. Unlike ranges of one pattern, ranges of different patterns
+    /// may overlap. The range later in `ranges` is painted over the earlier ones on the overlapped part.
+    pub fn with_patterns(mut self, patterns: Vec<usize>) -> Self {
+        self.patterns = patterns;
+        self
     }
 }
 
@@ -39,6 +58,11 @@ pub struct File {
     pub line_matches: Box<[LineMatch]>,
     pub chunks: Box<[(u64, u64)]>,
     pub contents: Box<[u8]>,
+    // Name of the language to highlight the contents. `None` means the language is detected from the path and contents
+    pub language: Option<String>,
+    // Occurrences of the search pattern in the context lines sorted by line numbers. Printers highlight them like matched
+    // regions, but the lines are not matched lines. Empty unless the searcher looks for them in context lines
+    context_regions: Box<[LineMatch]>,
 }
 
 impl File {
@@ -53,6 +77,145 @@ impl File {
             line_matches: lm.into_boxed_slice(),
             chunks: chunks.into_boxed_slice(),
             contents: contents.into_boxed_slice(),
+            language: None,
+            context_regions: Box::new([]),
+        }
+    }
+
+    /// Create a file from in-memory contents which may not exist on file system (e.g. fetched from network).
+    /// `name_hint` is shown in the header instead of a file path. `language` is the name or the file extension of the
+    /// language to highlight the contents such as `"Rust"` or `"rs"`. When it is `None` or not found, the language is
+    /// detected from `name_hint` and the contents as usual.
+    pub fn from_bytes(
+        name_hint: impl Into<PathBuf>,
+        language: Option<&str>,
+        contents: impl Into<Vec<u8>>,
+        lm: Vec<LineMatch>,
+        chunks: Vec<(u64, u64)>,
+    ) -> Self {
+        let mut file = Self::new(name_hint.into(), lm, chunks, contents.into());
+        file.language = language.map(str::to_string);
+        file
+    }
+
+    /// Set occurrences of the search pattern in the context lines. They must be sorted by line numbers.
+    pub fn with_context_regions(mut self, regions: Vec<LineMatch>) -> Self {
+        self.context_regions = regions.into_boxed_slice();
+        self
+    }
+
+    /// Occurrences of the search pattern in the context lines sorted by line numbers.
+    pub fn context_regions(&self) -> &[LineMatch] {
+        &self.context_regions
+    }
+
+    /// Create a file to print the given line ranges of the file at `path`, like `sed -n 'START,ENDp'`. No line is
+    /// marked as matched. Overlapping or adjacent ranges are merged into one chunk.
+    pub fn with_ranges(path: PathBuf, mut ranges: Vec<(u64, u64)>) -> Result<Self> {
+        let contents = fs::read(&path)?;
+        ranges.sort_unstable();
+        let mut chunks: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            let start = cmp::max(start, 1);
+            if end < start {
+                continue;
+            }
+            match chunks.last_mut() {
+                Some((_, e)) if start <= e.saturating_add(1) => *e = cmp::max(*e, end),
+                _ => chunks.push((start, end)),
+            }
+        }
+        Ok(Self::new(path, vec![], chunks, contents))
+    }
+
+    /// Create a file to print the line containing the byte at `offset` of the file at `path` with `context` lines before
+    /// and after it. The line is marked as matched. This is for tools which report byte offsets instead of line numbers.
+    /// A newline character belongs to the line it ends, and an offset at or beyond the end of the file points to the last
+    /// line. Nothing is printed for an empty file.
+    pub fn around_offset(path: PathBuf, offset: usize, context: u64) -> Result<Self> {
+        let contents = fs::read(&path)?;
+        Ok(Self::around_offset_in(path, contents, offset, context))
+    }
+
+    fn around_offset_in(path: PathBuf, contents: Vec<u8>, offset: usize, context: u64) -> Self {
+        if contents.is_empty() {
+            return Self::new(path, vec![], vec![], contents);
+        }
+        let last = contents.len() - 1; // Offset of the last byte. When it is a newline, it ends the last line
+        let lnum = memchr_iter(b'\n', &contents[..cmp::min(offset, last)]).count() as u64 + 1;
+        let num_lines = memchr_iter(b'\n', &contents[..last]).count() as u64 + 1;
+        let start = cmp::max(lnum.saturating_sub(context), 1);
+        let end = cmp::min(lnum.saturating_add(context), num_lines);
+        Self::new(
+            path,
+            vec![LineMatch::lnum(lnum)],
+            vec![(start, end)],
+            contents,
+        )
+    }
+
+    /// Keep only the first matched line and the chunk which contains it. The whole chunk is kept as the context of the
+    /// match and other matched lines in the chunk are no longer marked as matched. Nothing changes when no line matched.
+    pub fn retain_first_match(&mut self) {
+        let Some(first) = self.line_matches.first() else {
+            return;
+        };
+        let lnum = first.line_number;
+        if let Some(chunk) = self.chunks.iter().find(|(s, e)| *s <= lnum && lnum <= *e) {
+            self.chunks = vec![*chunk].into_boxed_slice();
+        }
+        self.line_matches = self.line_matches[..1].to_vec().into_boxed_slice();
+    }
+
+    /// Remove blank context lines at the start and the end of each chunk. Lines which contain only whitespaces are
+    /// blank. Matched lines are never removed, and a chunk whose lines are all blank is kept as-is. Line numbers of the
+    /// kept lines don't change. Lines after the end of the contents are also trimmed.
+    pub fn trim_blank_context(&mut self) {
+        let Some(last) = self.chunks.iter().map(|(_, e)| *e).max() else {
+            return;
+        };
+        let blanks: Vec<bool> = self
+            .lines()
+            .take(last as usize)
+            .map(|Line(l, _)| l.iter().all(u8::is_ascii_whitespace))
+            .collect();
+        let matches = &self.line_matches;
+        let is_blank = |lnum: u64| {
+            lnum >= 1
+                && blanks.get(lnum as usize - 1).copied().unwrap_or(true)
+                && matches
+                    .binary_search_by_key(&lnum, |m| m.line_number)
+                    .is_err()
+        };
+        for (start, end) in self.chunks.iter_mut() {
+            let (mut s, mut e) = (*start, *end);
+            while s <= e && is_blank(s) {
+                s += 1;
+            }
+            if s > e {
+                continue; // All lines are blank
+            }
+            while is_blank(e) {
+                e -= 1;
+            }
+            *start = s;
+            *end = e;
+        }
+    }
+
+    /// Iterate all lines of the file. Each line includes its trailing newline.
+    pub fn lines(&self) -> LinesInclusive<'_> {
+        LinesInclusive::new(&self.contents)
+    }
+
+    /// Iterate the lines in the chunks of the file with their matches. This is useful to implement a custom printer.
+    /// Chunks are assumed to be sorted. Even if some chunks share lines, each line is yielded only once.
+    pub fn chunk_lines(&self) -> ChunkLines<'_> {
+        ChunkLines {
+            lines: self.lines(),
+            chunks: &self.chunks,
+            chunk_index: 0,
+            line_matches: &self.line_matches,
         }
     }
 
@@ -75,24 +238,67 @@ fn print_sqrt<S: AsRef<str>>(input: S) {
     }
 }
 
+/// Lines of leading and trailing context which make a chunk of one matched line roughly fill a screen of `height` rows.
+/// The rows of the header and the footer of the file are excluded. This is useful to make previews fill the window.
+pub fn context_for_height(height: u16) -> u64 {
+    const FRAME_ROWS: u16 = 4; // Top line, file path, line under the path and footer
+    (height.saturating_sub(FRAME_ROWS + 1) / 2) as u64
+}
+
+/// Default upper bound of the context scaled by [`ContextPercent`].
+pub const DEFAULT_CONTEXT_PERCENT_MAX: u64 = 30;
+
+/// Context surrounding each match scaled to `percent` % of the number of lines in each file. The scaled context is at
+/// most `max` lines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ContextPercent {
+    pub percent: u64,
+    pub max: u64,
+}
+
+impl ContextPercent {
+    pub fn new(percent: u64) -> Self {
+        Self {
+            percent,
+            max: DEFAULT_CONTEXT_PERCENT_MAX,
+        }
+    }
+}
+
 pub struct Files<I: Iterator> {
     iter: Peekable<I>,
     min_context: u64,
     max_context: u64,
+    context_percent: Option<ContextPercent>,
     saw_error: bool,
     cwd: Option<PathBuf>,
 }
 
 impl<I: Iterator> Files<I> {
     pub fn new(iter: I, min_context: u64, max_context: u64) -> Self {
+        Self::with_cwd(iter, min_context, max_context, env::current_dir().ok())
+    }
+
+    // Absolute paths of matches are shown relative to `cwd`. Passing it explicitly makes the result independent of the
+    // current directory of the process
+    pub fn with_cwd(iter: I, min_context: u64, max_context: u64, cwd: Option<PathBuf>) -> Self {
         Self {
             iter: iter.peekable(),
             min_context,
             max_context,
+            context_percent: None,
             saw_error: false,
-            cwd: env::current_dir().ok(),
+            cwd,
         }
     }
+
+    /// Scale the maximum context surrounding each match to the number of lines in each file so that short files don't
+    /// show redundant context and large files show more. The context is clamped between the minimum context given on
+    /// creation and [`ContextPercent::max`]. `None` always uses the maximum context given on creation.
+    pub fn context_percent(mut self, percent: Option<ContextPercent>) -> Self {
+        self.context_percent = percent;
+        self
+    }
 }
 
 pub struct Line<'a>(pub &'a [u8], pub u64);
@@ -141,17 +347,103 @@ impl<'a> Iterator for Lines<'a> {
     }
 }
 
+/// Iterator of the lines in a buffer with their line numbers starting from 1. Unlike `str::lines`, each line keeps its
+/// trailing newline. "\r\n" is also kept as-is and the last line has no newline when the buffer does not end with
+/// "\n". Highlighters which parse a line with its newline (e.g. syntect) can take the lines directly.
+pub struct LinesInclusive<'a> {
+    lnum: usize,
+    prev: usize,
+    buf: &'a [u8],
+    iter: Memchr<'a>,
+}
+impl<'a> LinesInclusive<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            lnum: 1,
+            prev: 0,
+            buf,
+            iter: memchr_iter(b'\n', buf),
+        }
+    }
+}
+impl<'a> Iterator for LinesInclusive<'a> {
+    type Item = Line<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(idx) = self.iter.next() {
+            let lnum = self.lnum;
+            let end = idx + 1;
+            let line = &self.buf[self.prev..end];
+            self.prev = end;
+            self.lnum += 1;
+            Some(Line(line, lnum as u64))
+        } else if self.prev == self.buf.len() {
+            None
+        } else {
+            let line = &self.buf[self.prev..];
+            self.prev = self.buf.len();
+            Some(Line(line, self.lnum as u64))
+        }
+    }
+}
+
+/// Line in a chunk of a file yielded by [`File::chunk_lines`]. `line_match` is set when the line is matched.
+pub struct ChunkLine<'a> {
+    /// Contents of the line including its trailing newline
+    pub bytes: &'a [u8],
+    pub line_number: u64,
+    /// Index of the chunk in `File::chunks`. When it is different from the previous line's one, a new chunk starts
+    pub chunk_index: usize,
+    pub line_match: Option<&'a LineMatch>,
+}
+
+/// Iterator of the lines in the chunks of a file. See [`File::chunk_lines`].
+pub struct ChunkLines<'a> {
+    lines: LinesInclusive<'a>,
+    chunks: &'a [(u64, u64)],
+    chunk_index: usize,
+    line_matches: &'a [LineMatch],
+}
+impl<'a> Iterator for ChunkLines<'a> {
+    type Item = ChunkLine<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Line(bytes, lnum) = self.lines.next()?;
+            // Skip chunks which end before the line. A chunk inside the previous chunk is also skipped here
+            while self.chunks.get(self.chunk_index)?.1 < lnum {
+                self.chunk_index += 1;
+            }
+            if lnum < self.chunks[self.chunk_index].0 {
+                continue;
+            }
+            while let Some((m, ms)) = self.line_matches.split_first() {
+                if m.line_number >= lnum {
+                    break;
+                }
+                self.line_matches = ms;
+            }
+            let line_match = self.line_matches.first().filter(|m| m.line_number == lnum);
+            return Some(ChunkLine {
+                bytes,
+                line_number: lnum,
+                chunk_index: self.chunk_index,
+                line_match,
+            });
+        }
+    }
+}
+
 impl<I: Iterator<Item = Result<GrepMatch>>> Files<I> {
     fn calculate_chunk_range<'contents>(
         &self,
         match_start: u64,
         match_end: u64,
+        max_context: u64,
         lines: impl Iterator<Item = Line<'contents>>,
     ) -> (u64, u64) {
-        let before_start = cmp::max(match_start.saturating_sub(self.max_context), 1);
+        let before_start = cmp::max(match_start.saturating_sub(max_context), 1);
         let before_end = cmp::max(match_start.saturating_sub(self.min_context), 1);
         let after_start = match_end + self.min_context;
-        let after_end = match_end + self.max_context;
+        let after_end = match_end + max_context;
 
         let mut range_start = before_start;
         let mut range_end = after_end;
@@ -184,6 +476,21 @@ impl<I: Iterator<Item = Result<GrepMatch>>> Files<I> {
         (range_start, range_end)
     }
 
+    // Maximum context of the file. See `Files::context_percent`
+    fn max_context_of(&self, contents: &[u8]) -> u64 {
+        let Some(ContextPercent { percent, max }) = self.context_percent else {
+            return self.max_context;
+        };
+        let mut lines = memchr_iter(b'\n', contents).count() as u64;
+        if !contents.is_empty() && !contents.ends_with(b"\n") {
+            lines += 1; // The last line without newline
+        }
+        cmp::max(
+            cmp::min(lines.saturating_mul(percent) / 100, max),
+            self.min_context,
+        )
+    }
+
     fn relative_path(&self, path: PathBuf) -> PathBuf {
         if !path.is_relative() {
             if let Some(cwd) = &self.cwd {
@@ -221,12 +528,10 @@ impl<I: Iterator<Item = Result<GrepMatch>>> Iterator for Files<I> {
             Ok(vec) => vec,
             Err(err) => return self.error_item(err.into()),
         };
+        let max_context = self.max_context_of(&contents);
         // Assumes that matched lines are sorted by source location
         let mut lines = Lines::new(&contents);
-        let mut lmats = vec![LineMatch {
-            line_number,
-            ranges,
-        }];
+        let mut lmats = vec![LineMatch::new(line_number, ranges)];
         let mut chunks = Vec::new();
 
         'chunks: loop {
@@ -250,7 +555,7 @@ impl<I: Iterator<Item = Result<GrepMatch>>> Iterator for Files<I> {
                         self.iter.next();
                         continue;
                     }
-                    Some(Ok(m)) if m.line_number - line_number >= self.max_context * 2 => {
+                    Some(Ok(m)) if m.line_number - line_number >= max_context * 2 => {
                         State::EndOfChunk
                     }
                     Some(Ok(_)) => State::NextMatch,
@@ -258,9 +563,14 @@ impl<I: Iterator<Item = Result<GrepMatch>>> Iterator for Files<I> {
 
                 // Actions for each states
                 match peeked {
-                    State::EndOfFile | State::EndOfChunk => chunks.push(
-                        self.calculate_chunk_range(first_match_line, line_number, &mut lines),
-                    ),
+                    State::EndOfFile | State::EndOfChunk => {
+                        chunks.push(self.calculate_chunk_range(
+                            first_match_line,
+                            line_number,
+                            max_context,
+                            &mut lines,
+                        ))
+                    }
                     State::Error => {
                         let err = self.iter.next().unwrap().unwrap_err();
                         return self.error_item(err);
@@ -303,6 +613,7 @@ mod tests {
     use crate::test;
     use anyhow::Error;
     use std::fmt;
+    use std::iter;
     use std::path::Path;
 
     fn test_success_case(inputs: &[&str]) {
@@ -388,12 +699,38 @@ mod tests {
             chunks: vec![(5, 11)].into_boxed_slice(),
             contents: fs::read(&path).unwrap().into_boxed_slice(),
             path,
+            language: None,
+            context_regions: Box::new([]),
         };
 
         assert_eq!(got.len(), 1);
         assert_eq!(got[0], expected);
     }
 
+    #[test]
+    fn test_context_percent() {
+        let dir = Path::new("testdata").join("chunk");
+        let percent = |percent, max| Some(ContextPercent { percent, max });
+        let tests = [
+            (None, (5, 11)),
+            (percent(20, 30), (5, 11)), // 3 lines of 15 lines
+            (percent(0, 30), (7, 9)),   // Clamped to min context
+            (percent(60, 30), (1, 15)), // Larger than max context given on creation
+            (percent(60, 5), (3, 13)),  // Clamped to max of the percentage
+            (percent(u64::MAX, 5), (3, 13)),
+            (Some(ContextPercent::new(100)), (1, 15)),
+        ];
+        for (percent, chunk) in tests {
+            let matches = test::read_matches(&dir, "single_max");
+            let got: Vec<_> = Files::new(matches.into_iter(), 1, 3)
+                .context_percent(percent)
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(got.len(), 1, "percent={:?}", percent);
+            assert_eq!(&got[0].chunks[..], &[chunk], "percent={:?}", percent);
+        }
+    }
+
     #[test]
     fn test_zero_context() {
         let dir = Path::new("testdata").join("chunk");
@@ -408,12 +745,182 @@ mod tests {
             chunks: vec![(8, 8)].into_boxed_slice(),
             contents: fs::read(&path).unwrap().into_boxed_slice(),
             path,
+            language: None,
+            context_regions: Box::new([]),
         };
 
         assert_eq!(got.len(), 1);
         assert_eq!(got[0], expected);
     }
 
+    #[test]
+    fn test_file_with_ranges() {
+        let path = Path::new("testdata").join("chunk").join("single_max.in");
+        let ranges = vec![(10, 12), (0, 2), (3, 4), (8, 11), (7, 6)];
+        let file = File::with_ranges(path.clone(), ranges).unwrap();
+        let expected = File {
+            line_matches: vec![].into_boxed_slice(),
+            chunks: vec![(1, 4), (8, 12)].into_boxed_slice(),
+            contents: fs::read(&path).unwrap().into_boxed_slice(),
+            path,
+            language: None,
+            context_regions: Box::new([]),
+        };
+        assert_eq!(file, expected);
+
+        let ranges = vec![(1, u64::MAX), (5, 6)];
+        let file = File::with_ranges(expected.path, ranges).unwrap();
+        assert_eq!(&file.chunks[..], &[(1, u64::MAX)]);
+
+        let path = Path::new("testdata").join("this-file-does-not-exist.txt");
+        File::with_ranges(path, vec![(1, 2)]).unwrap_err();
+    }
+
+    #[test]
+    fn test_file_around_offset() {
+        let contents = "a\nbc\n\nd\ne\n";
+        let tests = [
+            (0, 2, 1, (1, 3)),
+            (1, 2, 1, (1, 3)), // Newline at the end of the first line
+            (2, 2, 2, (1, 4)), // Start of the second line
+            (5, 0, 3, (3, 3)), // Empty line
+            (6, 1, 4, (3, 5)),
+            (9, 1, 5, (4, 5)),  // Newline at the end of the file
+            (10, 1, 5, (4, 5)), // End of the file
+            (100, 2, 5, (3, 5)),
+            (3, 100, 2, (1, 5)),
+        ];
+        for (offset, context, lnum, chunk) in tests {
+            let path = PathBuf::from("test.txt");
+            let file = File::around_offset_in(path, contents.into(), offset, context);
+            assert_eq!(
+                &*file.line_matches,
+                &[LineMatch::lnum(lnum)],
+                "offset={}",
+                offset
+            );
+            assert_eq!(&*file.chunks, &[chunk], "offset={}", offset);
+        }
+
+        // The last line without newline and an empty file
+        let file = File::around_offset_in(PathBuf::from("a"), b"a\nb".to_vec(), 3, 0);
+        assert_eq!(&*file.chunks, &[(2, 2)]);
+        let file = File::around_offset_in(PathBuf::from("a"), vec![], 0, 3);
+        assert!(file.chunks.is_empty() && file.line_matches.is_empty());
+
+        let path = Path::new("testdata").join("chunk").join("single_max.in");
+        let contents = fs::read(&path).unwrap();
+        let offset = memchr_iter(b'\n', &contents).nth(9).unwrap() + 1; // Start of line 11
+        let file = File::around_offset(path, offset, 2).unwrap();
+        assert_eq!(&*file.line_matches, &[LineMatch::lnum(11)]);
+        assert_eq!(&*file.chunks, &[(9, 13)]);
+    }
+
+    #[test]
+    fn test_retain_first_match() {
+        let mut file = File::new(
+            PathBuf::from("test.rs"),
+            vec![
+                LineMatch::new(3, vec![(0, 1)]),
+                LineMatch::new(5, vec![(1, 2)]),
+                LineMatch::new(12, vec![(2, 3)]),
+            ],
+            vec![(1, 8), (10, 15)],
+            vec![],
+        );
+        file.retain_first_match();
+        assert_eq!(&*file.line_matches, &[LineMatch::new(3, vec![(0, 1)])]);
+        assert_eq!(&*file.chunks, &[(1, 8)]);
+
+        let mut file = File::new(
+            PathBuf::from("test.rs"),
+            vec![],
+            vec![(1, 3), (5, 6)],
+            vec![],
+        );
+        file.retain_first_match();
+        assert_eq!(&*file.chunks, &[(1, 3), (5, 6)]);
+    }
+
+    #[test]
+    fn test_context_for_height() {
+        for (height, want) in [
+            (0, 0),
+            (5, 0),
+            (6, 0),
+            (7, 1),
+            (24, 9),
+            (25, 10),
+            (u16::MAX, 32765),
+        ] {
+            assert_eq!(context_for_height(height), want, "height={}", height);
+        }
+
+        // The chunk of one matched line fills the rows except for the header and the footer
+        let dir = Path::new("testdata").join("chunk");
+        let matches = test::read_matches(&dir, "single_max");
+        let context = context_for_height(11);
+        let got: Vec<_> = Files::new(matches.into_iter(), context, context)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(&*got[0].chunks, &[(5, 11)]);
+    }
+
+    #[test]
+    fn test_trim_blank_context() {
+        let contents = b"\n  \nfoo\n\t\n\nbar\n\n \r\n\nbaz\n\n";
+        let mut file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(3), LineMatch::lnum(5), LineMatch::lnum(10)],
+            vec![(1, 6), (7, 9), (9, 12)],
+            contents.to_vec(),
+        );
+        file.trim_blank_context();
+        // Matched blank line 5 is kept. The second chunk has only blank lines. Line 12 is after the end of the contents
+        assert_eq!(&*file.chunks, &[(3, 6), (7, 9), (10, 10)]);
+
+        // Nothing happens without chunks
+        let mut file = File::new(PathBuf::from("test.rs"), vec![], vec![], contents.to_vec());
+        file.trim_blank_context();
+        assert!(file.chunks.is_empty());
+    }
+
+    #[test]
+    fn test_lines_inclusive() {
+        let lines: Vec<_> = LinesInclusive::new(b"a\nb\r\n\nc")
+            .map(|Line(l, n)| (l, n))
+            .collect();
+        let want: Vec<(&[u8], u64)> = vec![(b"a\n", 1), (b"b\r\n", 2), (b"\n", 3), (b"c", 4)];
+        assert_eq!(lines, want);
+        assert_eq!(LinesInclusive::new(b"").count(), 0);
+        assert_eq!(LinesInclusive::new(b"a\n").count(), 1);
+    }
+
+    #[test]
+    fn test_chunk_lines() {
+        let file = File::new(
+            PathBuf::from("test.txt"),
+            vec![LineMatch::lnum(2), LineMatch::new(5, vec![(0, 1)])],
+            vec![(1, 3), (3, 3), (5, 6)],
+            b"a\nb\nc\nd\ne\nf\ng\n".to_vec(),
+        );
+        let lines: Vec<_> = file
+            .chunk_lines()
+            .map(|l| {
+                let matched = l.line_match.map(|m| m.ranges.clone());
+                (l.bytes, l.line_number, l.chunk_index, matched)
+            })
+            .collect();
+        let want: Vec<(&[u8], _, _, _)> = vec![
+            (b"a\n", 1, 0, None),
+            (b"b\n", 2, 0, Some(vec![])),
+            (b"c\n", 3, 0, None),
+            (b"e\n", 5, 2, Some(vec![(0, 1)])),
+            (b"f\n", 6, 2, None),
+        ];
+        assert_eq!(lines, want);
+    }
+
     #[test]
     fn test_same_line_occurs_repeatedly() {
         // Same line may be reported multiple times when reading output from `rg --vimgrep` (regression test for #17)
@@ -441,6 +948,24 @@ mod tests {
         assert_eq!(chunks, want);
     }
 
+    #[test]
+    fn test_relative_path_with_cwd() {
+        let cwd = env::current_dir().unwrap();
+        let mat = || {
+            Result::Ok(GrepMatch {
+                path: cwd.join("Cargo.toml"),
+                line_number: 1,
+                ranges: vec![],
+            })
+        };
+
+        let mut files = Files::with_cwd(iter::once(mat()), 0, 0, Some(cwd.clone()));
+        assert_eq!(files.next().unwrap().unwrap().path, Path::new("Cargo.toml"));
+
+        let mut files = Files::with_cwd(iter::once(mat()), 0, 0, None);
+        assert_eq!(files.next().unwrap().unwrap().path, cwd.join("Cargo.toml"));
+    }
+
     #[test]
     fn test_error_while_matching() {
         #[derive(Debug)]
@@ -0,0 +1,43 @@
+use std::env;
+use std::path::PathBuf;
+
+// Brought from bat/src/bin/bat/directories.rs dde770aa210ab9eeb5469e152cec6fcaab374d84
+pub fn bat_cache_dir() -> Option<PathBuf> {
+    // on all OS prefer BAT_CACHE_PATH if set
+    let cache_dir_op = env::var_os("BAT_CACHE_PATH").map(PathBuf::from);
+    if cache_dir_op.is_some() {
+        return cache_dir_op;
+    }
+
+    #[cfg(target_os = "macos")]
+    let cache_dir_op = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs_next::home_dir().map(|d| d.join(".cache")));
+
+    #[cfg(not(target_os = "macos"))]
+    let cache_dir_op = dirs_next::cache_dir();
+
+    cache_dir_op.map(|d| d.join("bat"))
+}
+
+// Brought from bat/src/bin/bat/directories.rs dde770aa210ab9eeb5469e152cec6fcaab374d84
+#[cfg(feature = "syntect-printer")]
+pub fn bat_config_dir() -> Option<PathBuf> {
+    // on all OS prefer BAT_CONFIG_DIR if set
+    let config_dir_op = env::var_os("BAT_CONFIG_DIR").map(PathBuf::from);
+    if config_dir_op.is_some() {
+        return config_dir_op;
+    }
+
+    #[cfg(target_os = "macos")]
+    let config_dir_op = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs_next::home_dir().map(|d| d.join(".config")));
+
+    #[cfg(not(target_os = "macos"))]
+    let config_dir_op = dirs_next::config_dir();
+
+    config_dir_op.map(|d| d.join("bat"))
+}
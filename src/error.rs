@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// Error returned from printers. Library users can match on the kind of error. It can be converted into `anyhow::Error`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HgrepError {
+    UnknownTheme(String),
+    Io(io::Error),
+    SyntaxLoad(Box<dyn Error + Send + Sync>),
+    ThemeLoad(Box<dyn Error + Send + Sync>),
+    InvalidSyntaxMapping(String),
+    UnknownLanguage(String),
+    Print {
+        path: PathBuf,
+        cause: Option<String>,
+    },
+}
+
+impl Error for HgrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::SyntaxLoad(err) | Self::ThemeLoad(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HgrepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownTheme(name) => {
+                write!(f, "Unknown theme '{}'. See --list-themes output", name)
+            }
+            Self::Io(err) => write!(f, "{}", err),
+            Self::SyntaxLoad(err) => write!(f, "Could not load syntax definitions: {}", err),
+            Self::ThemeLoad(err) => write!(f, "Could not load themes: {}", err),
+            Self::InvalidSyntaxMapping(msg) => write!(f, "Invalid syntax mapping: {}", msg),
+            Self::UnknownLanguage(name) => write!(f, "Unknown language '{}'", name),
+            Self::Print { path, cause } => {
+                write!(f, "Could not print file {:?}", path)?;
+                if let Some(cause) = cause {
+                    write!(f, ". Caused by: {}", cause)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<io::Error> for HgrepError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, HgrepError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_message() {
+        let tests = [
+            (
+                HgrepError::UnknownTheme("foo".to_string()),
+                "Unknown theme 'foo'. See --list-themes output",
+            ),
+            (HgrepError::Io(io::Error::other("oops")), "oops"),
+            (
+                HgrepError::UnknownLanguage("foo".to_string()),
+                "Unknown language 'foo'",
+            ),
+            (
+                HgrepError::Print {
+                    path: PathBuf::from("foo.rs"),
+                    cause: Some("oops".to_string()),
+                },
+                "Could not print file \"foo.rs\". Caused by: oops",
+            ),
+        ];
+        for (err, want) in tests {
+            assert_eq!(format!("{}", err), want);
+        }
+    }
+
+    #[test]
+    fn test_convert_to_anyhow() {
+        let err: anyhow::Error = HgrepError::UnknownTheme("foo".to_string()).into();
+        assert!(matches!(
+            err.downcast_ref::<HgrepError>(),
+            Some(HgrepError::UnknownTheme(name)) if name == "foo",
+        ));
+    }
+}
@@ -2,16 +2,25 @@
 compile_error!("Either feature \"bat-printer\" or \"syntect-printer\" must be enabled");
 
 pub mod chunk;
+pub mod error;
 pub mod grep;
+pub mod io;
+pub mod locations;
 pub mod printer;
 
-mod io;
+mod dirs;
 
 #[cfg(feature = "bat-printer")]
 pub mod bat;
+#[cfg(feature = "syntect-printer")]
+pub mod pager;
 #[cfg(feature = "ripgrep")]
 pub mod ripgrep;
 #[cfg(feature = "syntect-printer")]
+pub mod rtf;
+#[cfg(feature = "syntect-printer")]
+pub mod svg;
+#[cfg(feature = "syntect-printer")]
 pub mod syntect;
 
 #[cfg(test)]
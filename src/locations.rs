@@ -0,0 +1,281 @@
+use crate::chunk::File;
+use crate::printer::{file_url, json_string, Printer};
+use anyhow::Result;
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Default format of locations. It is compatible with Vim's 'errorformat' `%f:%l:%c:%m` so that the output can be
+/// loaded to the quickfix list with `:cexpr` or `:cfile`.
+pub const DEFAULT_LOCATION_FORMAT: &str = "{path}:{line}:{column}:{text}";
+
+/// Output format of each location.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LocationFormat<'a> {
+    /// `{path}`, `{line}`, `{column}`, `{text}` and `{url}` in the string are replaced with the file path, the line
+    /// number, the column of the start of the matched region, the contents of the matched line and the `file://` URL
+    /// of the location respectively. `{url}` is empty when the file has no URL (e.g. an archive member).
+    Template(&'a str),
+    /// One JSON object per line like `{"path":"src/lib.rs","line":3,"column":5,"text":"..."}`. When URLs are enabled
+    /// by [`LocationsPrinter::urls`], the object also has a `"url"` key which is `null` when the file has no URL.
+    Json,
+}
+
+/// Printer which prints one location per matched region instead of highlighted code. It is the machine-readable
+/// counterpart of the other printers for jumping to matches from editors.
+///
+/// Line numbers and columns are 1-based and columns are counted in bytes like `rg --vimgrep`. A matched line without
+/// regions is printed once at column 1. See [`LocationFormat`] for the formats of locations.
+pub struct LocationsPrinter<'main, W: Write> {
+    out: Mutex<W>, // Protected with mutex because it should print file by file
+    format: LocationFormat<'main>,
+    urls: bool,
+    // Captured once on creation to resolve relative paths in URLs. Tests overwrite this
+    cwd: Option<PathBuf>,
+}
+
+impl<'main, W: Write> LocationsPrinter<'main, W> {
+    pub fn new(out: W, format: LocationFormat<'main>) -> Self {
+        Self {
+            out: Mutex::new(out),
+            format,
+            urls: false,
+            cwd: env::current_dir().ok(),
+        }
+    }
+
+    /// Include the `file://` URL of each location in JSON output. The path in the URL is absolute and percent-encoded,
+    /// and the URL has an anchor like `#L3C5` for the line and the column so that consumers can link to the location.
+    pub fn urls(&mut self, enabled: bool) {
+        self.urls = enabled;
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+
+    fn write_location(&self, buf: &mut String, loc: &Location<'_>) {
+        match self.format {
+            LocationFormat::Template(format) => loc.write_template(buf, format),
+            LocationFormat::Json => loc.write_json(buf, self.urls),
+        }
+        buf.push('\n');
+    }
+}
+
+struct Location<'a> {
+    path: &'a str,
+    line: u64,
+    column: usize,
+    text: &'a str,
+    base_url: Option<&'a str>,
+}
+
+impl<'a> Location<'a> {
+    fn url(&self) -> Option<String> {
+        let base = self.base_url?;
+        Some(format!("{}#L{}C{}", base, self.line, self.column))
+    }
+
+    fn write_template(&self, buf: &mut String, mut format: &str) {
+        while let Some(start) = format.find('{') {
+            buf.push_str(&format[..start]);
+            format = &format[start..];
+            let Some(end) = format.find('}') else {
+                break;
+            };
+            match &format[1..end] {
+                "path" => buf.push_str(self.path),
+                "line" => buf.push_str(&self.line.to_string()),
+                "column" => buf.push_str(&self.column.to_string()),
+                "text" => buf.push_str(self.text),
+                "url" => buf.push_str(self.url().as_deref().unwrap_or("")),
+                _ => buf.push_str(&format[..=end]), // Unknown placeholder is printed as-is
+            }
+            format = &format[end + 1..];
+        }
+        buf.push_str(format);
+    }
+
+    fn write_json(&self, buf: &mut String, url: bool) {
+        buf.push_str(&format!(
+            r#"{{"path":{},"line":{},"column":{},"text":{}"#,
+            json_string(self.path),
+            self.line,
+            self.column,
+            json_string(self.text),
+        ));
+        if url {
+            match self.url() {
+                Some(url) => buf.push_str(&format!(r#","url":{}"#, json_string(&url))),
+                None => buf.push_str(r#","url":null"#),
+            }
+        }
+        buf.push('}');
+    }
+}
+
+impl<'main, W: Write> Printer for LocationsPrinter<'main, W> {
+    fn print(&self, file: File) -> Result<()> {
+        use crate::io::IgnoreBrokenPipe;
+
+        let path = file.path.to_string_lossy();
+        let base_url = file_url(&file.path, self.cwd.as_deref());
+        let mut buf = String::new();
+        for line in file.chunk_lines() {
+            let Some(line_match) = line.line_match else {
+                continue;
+            };
+            let text = String::from_utf8_lossy(line.bytes);
+            let text = text.trim_end_matches(['\n', '\r']);
+            let columns = line_match.ranges.iter().map(|(start, _)| start + 1);
+            let columns = line_match
+                .ranges
+                .is_empty()
+                .then_some(1)
+                .into_iter()
+                .chain(columns);
+            for column in columns {
+                let loc = Location {
+                    path: &path,
+                    line: line.line_number,
+                    column,
+                    text,
+                    base_url: base_url.as_deref(),
+                };
+                self.write_location(&mut buf, &loc);
+            }
+        }
+
+        let mut out = self.out.lock().unwrap();
+        out.write_all(buf.as_bytes())
+            .and_then(|_| out.flush())
+            .ignore_broken_pipe()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::LineMatch;
+    use std::path::PathBuf;
+
+    fn print(format: &str, file: File) -> String {
+        let mut printer = LocationsPrinter::new(vec![], LocationFormat::Template(format));
+        printer.cwd = Some(PathBuf::from("/work"));
+        printer.print(file).unwrap();
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_multiple_matches_in_line() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![
+                LineMatch::new(2, vec![(4, 7), (11, 14)]),
+                LineMatch::new(3, vec![(0, 2)]),
+            ],
+            vec![(1, 4)],
+            b"fn main() {\n    foo(); foo();\r\nfoo\n}\n".to_vec(),
+        );
+        let printed = print(DEFAULT_LOCATION_FORMAT, file);
+        assert_eq!(
+            printed,
+            "\
+test.rs:2:5:    foo(); foo();
+test.rs:2:12:    foo(); foo();
+test.rs:3:1:foo
+",
+        );
+    }
+
+    #[test]
+    fn test_line_without_regions() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 2)],
+            b"fn main() {\n}\n".to_vec(),
+        );
+        let printed = print(DEFAULT_LOCATION_FORMAT, file);
+        assert_eq!(printed, "test.rs:1:1:fn main() {\n");
+    }
+
+    #[test]
+    fn test_json_format() {
+        let file = File::new(
+            PathBuf::from("src/a b.rs"),
+            vec![LineMatch::new(2, vec![(4, 7), (11, 14)])],
+            vec![(1, 2)],
+            b"fn main() {\n    \"foo\"; foo\t\n".to_vec(),
+        );
+        let mut printer = LocationsPrinter::new(vec![], LocationFormat::Json);
+        printer.print(file.clone()).unwrap();
+        let printed = String::from_utf8(printer.into_inner()).unwrap();
+        assert_eq!(
+            printed,
+            r#"{"path":"src/a b.rs","line":2,"column":5,"text":"    \"foo\"; foo\t"}
+{"path":"src/a b.rs","line":2,"column":12,"text":"    \"foo\"; foo\t"}
+"#,
+        );
+
+        printer = LocationsPrinter::new(vec![], LocationFormat::Json);
+        printer.urls(true);
+        printer.cwd = Some(PathBuf::from("/work"));
+        printer.print(file).unwrap();
+        let printed = String::from_utf8(printer.into_inner()).unwrap();
+        #[cfg(not(windows))]
+        assert!(
+            printed.contains(r#","url":"file:///work/src/a%20b.rs#L2C12"}"#),
+            "{:?}",
+            printed,
+        );
+
+        // Files which don't exist on file system have no URL
+        let file = File::new(
+            PathBuf::from("foo.tar!src/lib.rs"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 1)],
+            b"fn main() {}\n".to_vec(),
+        );
+        let mut printer = LocationsPrinter::new(vec![], LocationFormat::Json);
+        printer.urls(true);
+        printer.print(file).unwrap();
+        let printed = String::from_utf8(printer.into_inner()).unwrap();
+        assert!(
+            printed.ends_with(
+                r#","url":null}
+"#
+            ),
+            "{:?}",
+            printed
+        );
+    }
+
+    #[test]
+    fn test_custom_format() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::new(1, vec![(3, 7)])],
+            vec![(1, 1)],
+            b"fn {line}() {}\n".to_vec(),
+        );
+        let tests = [
+            (
+                "{path}({line},{column}): {text}",
+                "test.rs(1,4): fn {line}() {}\n",
+            ),
+            ("{line}:{column} {unknown}", "1:4 {unknown}\n"),
+            ("{path} {", "test.rs {\n"),
+            ("no placeholder", "no placeholder\n"),
+        ];
+        for (format, want) in tests {
+            let printed = print(format, file.clone());
+            assert_eq!(printed, want, "format={:?}", format);
+        }
+        #[cfg(not(windows))]
+        assert_eq!(print("{url}", file), "file:///work/test.rs#L1C4\n");
+    }
+}
@@ -1,10 +1,16 @@
 use anyhow::{Context, Result};
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
+use hgrep::chunk::{ContextPercent, File};
 use hgrep::grep::BufReadExt;
-use hgrep::printer::{PrinterOptions, TextWrapMode};
+use hgrep::locations::{LocationFormat, LocationsPrinter, DEFAULT_LOCATION_FORMAT};
+use hgrep::printer::Printer;
+use hgrep::printer::{
+    FirstOnly, PrinterOptions, SortOrder, TermColorSupport, TextWrapMode, ThemeMode,
+};
 use std::cmp;
 use std::env;
 use std::io;
+use std::path::PathBuf;
 use std::process;
 
 #[global_allocator]
@@ -16,9 +22,16 @@ use hgrep::ripgrep;
 #[cfg(feature = "bat-printer")]
 use hgrep::bat::BatPrinter;
 
+#[cfg(feature = "syntect-printer")]
+use hgrep::rtf::RtfPrinter;
+#[cfg(feature = "syntect-printer")]
+use hgrep::svg::SvgPrinter;
 #[cfg(feature = "syntect-printer")]
 use hgrep::syntect::SyntectPrinter;
 
+#[cfg(feature = "syntect-printer")]
+use hgrep::pager::{Output, PagingMode};
+
 fn command() -> Command {
     #[cfg(feature = "syntect-printer")]
     const DEFAULT_PRINTER: &str = "syntect";
@@ -52,15 +65,45 @@ fn command() -> Command {
                 .default_value("6")
                 .help("Maximum lines of leading and trailing context surrounding each match"),
         )
+        .arg(
+            Arg::new("context-percent")
+                .long("context-percent")
+                .num_args(1)
+                .value_name("NUM")
+                .help("Scale the context surrounding each match to NUM (0 to 100) percent of lines in each file. The context is clamped between --min-context and --context-percent-max"),
+        )
+        .arg(
+            Arg::new("context-percent-max")
+                .long("context-percent-max")
+                .num_args(1)
+                .value_name("NUM")
+                .default_value("30")
+                .help("Maximum lines of leading and trailing context scaled by --context-percent"),
+        )
+        .arg(
+            Arg::new("fill-height")
+                .long("fill-height")
+                .action(ArgAction::SetTrue)
+                .help("Scale the context surrounding each match so that a chunk of one matched line roughly fills the terminal height. This overrides --min-context and --max-context"),
+        )
+        .arg(
+            Arg::new("term-height")
+                .long("term-height")
+                .num_args(1)
+                .value_name("NUM")
+                .help("Height (number of lines) of terminal window used by --fill-height. By default, the height is detected from the terminal"),
+        )
         .arg(
             Arg::new("no-grid")
                 .short('G')
                 .long("no-grid")
+                .action(ArgAction::SetTrue)
                 .help("Remove borderlines for more compact output"),
         )
         .arg(
             Arg::new("grid")
                 .long("grid")
+                .action(ArgAction::SetTrue)
                 .help("Add borderlines to output. This flag is an opposite of --no-grid"),
         )
         .arg(
@@ -69,7 +112,7 @@ fn command() -> Command {
                 .num_args(1)
                 .value_name("NUM")
                 .default_value("4")
-                .help("Number of spaces for tab character. Set 0 to pass tabs through directly"),
+                .help("Number of spaces for tab character. Set 0 to pass tabs through directly as hard tabs which advance to the next tab stop of the terminal"),
         )
         .arg(
             Arg::new("theme")
@@ -78,9 +121,42 @@ fn command() -> Command {
                 .value_name("THEME")
                 .help("Theme for syntax highlighting. Use --list-themes flag to print the theme list"),
         )
+        .arg(
+            Arg::new("theme-light")
+                .long("theme-light")
+                .num_args(1)
+                .value_name("THEME")
+                .help("Theme used when the terminal background is light. This is preferred over --theme"),
+        )
+        .arg(
+            Arg::new("theme-dark")
+                .long("theme-dark")
+                .num_args(1)
+                .value_name("THEME")
+                .help("Theme used when the terminal background is dark. This is preferred over --theme"),
+        )
+        .arg(
+            Arg::new("theme-mode")
+                .long("theme-mode")
+                .num_args(1)
+                .value_name("MODE")
+                .default_value("auto")
+                .value_parser(["auto", "light", "dark"])
+                .ignore_case(true)
+                .help("Choose a theme from --theme-light and --theme-dark. 'auto' detects the terminal background from $COLORFGBG and assumes dark background when it is not available"),
+        )
+        .arg(
+            Arg::new("theme-fallback")
+                .long("theme-fallback")
+                .num_args(1)
+                .value_name("THEME")
+                .action(ArgAction::Append)
+                .help("Theme used when the theme specified by other options is not available. This option is repeatable and the themes are tried in order"),
+        )
         .arg(
             Arg::new("list-themes")
                 .long("list-themes")
+                .action(ArgAction::SetTrue)
                 .help("List all available theme names and their samples. Samples show the output where 'let' is searched. The names can be used at --theme option"),
         )
         .arg(
@@ -106,11 +182,124 @@ fn command() -> Command {
                 .value_parser(["char", "never"])
                 .ignore_case(true)
                 .help("Text-wrapping mode. 'char' enables character-wise text-wrapping. 'never' disables text-wrapping")
+        ).arg(
+            Arg::new("color-level")
+                .long("color-level")
+                .num_args(1)
+                .value_name("LEVEL")
+                .default_value("auto")
+                .value_parser(["auto", "truecolor", "256", "16"])
+                .ignore_case(true)
+                .help("Number of colors used for output. 'auto' detects it from $COLORTERM and terminfo. Other values are preferred over the detection for terminals which advertise colors they don't render well")
         ).arg(
             Arg::new("first-only")
                 .short('f')
                 .long("first-only")
-                .help("Show only the first code snippet per file")
+                .num_args(0..=1)
+                .require_equals(true)
+                .value_name("WHAT")
+                .default_missing_value("chunk")
+                .value_parser(["chunk", "match"])
+                .ignore_case(true)
+                .help("Show only the first code snippet per file. With 'match', show only the first matched line with its context per file and other matched lines in the context are not highlighted")
+        )
+        .arg(
+            Arg::new("trim-chunk-blanks")
+                .long("trim-chunk-blanks")
+                .action(ArgAction::SetTrue)
+                .help("Skip blank context lines at the start and the end of each chunk. Matched lines are always shown")
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .num_args(1)
+                .value_name("ORDER")
+                .default_value("none")
+                .value_parser(["none", "path", "path-reverse"])
+                .ignore_case(true)
+                .help("Order of files in output. 'none' prints files in the order they are found. 'path' sorts files by their paths and 'path-reverse' sorts them in reverse order. Sorted output is deterministic, but nothing is printed until the search finishes")
+        )
+        .arg(
+            Arg::new("language")
+                .long("language")
+                .num_args(1)
+                .value_name("LANG")
+                .help("Highlight all files with the language regardless of their file names like `bat -l`. LANG is the name of the language such as 'Rust' or its file extension such as 'rs'"),
+        )
+        .arg(
+            Arg::new("text")
+                .short('a')
+                .long("text")
+                .action(ArgAction::SetTrue)
+                .help("Search and print binary files as if they were text. By default, syntect printer does not render a file containing NUL bytes. This flag is only available for syntect printer")
+        )
+        .arg(
+            Arg::new("range")
+                .long("range")
+                .num_args(1)
+                .value_name("PATH:START-END")
+                .action(ArgAction::Append)
+                .help("Print lines from START to END of the file at PATH instead of searching. END can be omitted to print a single line. This option is repeatable"),
+        )
+        .arg(
+            Arg::new("locations")
+                .long("locations")
+                .action(ArgAction::SetTrue)
+                .help("Print one location per matched region like 'path:line:column:text' instead of code snippets. The output can be loaded to editors' quickfix lists"),
+        )
+        .arg(
+            Arg::new("locations-format")
+                .long("locations-format")
+                .num_args(1)
+                .value_name("FORMAT")
+                .default_value(DEFAULT_LOCATION_FORMAT)
+                .help("Format of each location printed by --locations. {path}, {line}, {column}, {text} and {url} are replaced with the file path, the line number, the byte column of the match (1-based), the matched line and the file:// URL of the location. The default is compatible with Vim's 'errorformat' %f:%l:%c:%m. 'json' prints one JSON object per line"),
+        )
+        .arg(
+            Arg::new("locations-urls")
+                .long("locations-urls")
+                .action(ArgAction::SetTrue)
+                .help("Include a file:// URL with the line and column anchor (e.g. #L3C5) of each location in the JSON output of --locations"),
+        )
+        .arg(
+            Arg::new("svg")
+                .long("svg")
+                .action(ArgAction::SetTrue)
+                .help("Print each file as an SVG image instead of text for screenshots. Search one file to get a valid image. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("svg-window")
+                .long("svg-window")
+                .action(ArgAction::SetTrue)
+                .help("Draw a window title bar with the file path in the images printed by --svg"),
+        )
+        .arg(
+            Arg::new("svg-font-family")
+                .long("svg-font-family")
+                .num_args(1)
+                .value_name("FAMILY")
+                .help("Font family of the text in the images printed by --svg in the syntax of CSS like \"'Fira Code', monospace\". Monospace fonts should be used"),
+        )
+        .arg(
+            Arg::new("svg-font-size")
+                .long("svg-font-size")
+                .num_args(1)
+                .value_name("PIXELS")
+                .help("Font size of the text in the images printed by --svg. The default value is 14"),
+        )
+        .arg(
+            Arg::new("svg-line-height")
+                .long("svg-line-height")
+                .num_args(1)
+                .value_name("RATIO")
+                .help("Height of each row relative to the font size in the images printed by --svg. The default value is 1.4"),
+        )
+        .arg(
+            Arg::new("rtf")
+                .long("rtf")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("svg")
+                .help("Print each file as an RTF document instead of text for pasting into word processors. Search one file to get a valid document. This flag is only for syntect printer"),
         )
         .arg(
             Arg::new("generate-completion-script")
@@ -126,6 +315,7 @@ fn command() -> Command {
     let cmd = cmd.arg(
         Arg::new("custom-assets")
             .long("custom-assets")
+            .action(ArgAction::SetTrue)
             .help("Load bat's custom assets. Note that this flag may not work with some version of `bat` command. This flag is only for bat printer"),
     );
 
@@ -134,12 +324,337 @@ fn command() -> Command {
         .arg(
             Arg::new("background")
                 .long("background")
+                .action(ArgAction::SetTrue)
                 .help("Paint background colors. This flag is only for syntect printer"),
         )
         .arg(
-            Arg::new("ascii-lines").long("ascii-lines").help(
-                "Use ASCII characters for drawing border lines instead of Unicode characters",
-            ),
+            Arg::new("ascii-lines")
+                .long("ascii-lines")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Use ASCII characters for drawing border lines instead of Unicode characters",
+                ),
+        )
+        .arg(
+            Arg::new("map-syntax")
+                .long("map-syntax")
+                .num_args(1)
+                .value_name("GLOB:SYNTAX")
+                .action(ArgAction::Append)
+                .help("Use the syntax for files whose names or paths match the glob pattern (e.g. '*.build:Python'). This option is repeatable and only for syntect printer"),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .num_args(1)
+                .value_name("PATH")
+                .action(ArgAction::Append)
+                .help("Explain which syntax and theme are used for the file at PATH and why instead of searching. This option is repeatable and only for syntect printer"),
+        )
+        .arg(
+            Arg::new("explain-format")
+                .long("explain-format")
+                .num_args(1)
+                .value_name("FORMAT")
+                .default_value("text")
+                .value_parser(["text", "json"])
+                .ignore_case(true)
+                .help("Output format of --explain. 'json' prints one JSON object per line"),
+        )
+        .arg(
+            Arg::new("align-tabs")
+                .long("align-tabs")
+                .action(ArgAction::SetTrue)
+                .help("Expand a tab character to the next tab stop (multiple of --tab width) like text editors instead of the fixed number of spaces. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("separator-gap")
+                .long("separator-gap")
+                .action(ArgAction::SetTrue)
+                .help("Show the number of lines skipped between code snippets in the separator line. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("hyperlinks")
+                .long("hyperlinks")
+                .action(ArgAction::SetTrue)
+                .help("Make line numbers clickable links to the lines with OSC 8 escape sequences. Links are enabled only when stdout is a terminal. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("indent-guides")
+                .long("indent-guides")
+                .action(ArgAction::SetTrue)
+                .help("Draw vertical guides at each indentation level in leading whitespaces of lines. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("match-sparkline")
+                .long("match-sparkline")
+                .action(ArgAction::SetTrue)
+                .help("Show a tiny bar in the header of each file. Its length grows with the number of matched lines. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("overflow-tooltip")
+                .long("overflow-tooltip")
+                .action(ArgAction::SetTrue)
+                .help("With --wrap never, print the whole matched line which overflows the terminal again on the following faint rows. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("bracket-colors")
+                .long("bracket-colors")
+                .action(ArgAction::SetTrue)
+                .help("Color brackets (), [] and {} by their nesting depth in each code snippet. Brackets in strings or comments are not colored. <> are not colored since they are often used as operators. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("header-path-width")
+                .long("header-path-width")
+                .num_args(1)
+                .value_name("NUM")
+                .help("Pad the file path in each header to NUM characters so that the notes after the path are aligned across files. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("invalid-utf8")
+                .long("invalid-utf8")
+                .num_args(1)
+                .value_name("CHAR")
+                .help("How to draw bytes which are invalid as UTF-8. A single character replaces each invalid sequence and 'escape' draws each invalid byte as \\xNN in a dim color [default: U+FFFD]. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("transparent")
+                .long("transparent")
+                .action(ArgAction::SetTrue)
+                .help("Never paint background colors so that a background image or transparency of the terminal is visible. Matched regions are underlined instead. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("header-align")
+                .long("header-align")
+                .num_args(1)
+                .value_name("ALIGN")
+                .default_value("left")
+                .value_parser(["left", "right"])
+                .ignore_case(true)
+                .help("Alignment of the file path in each header. 'right' aligns the path and the notes after it to the right edge of the terminal. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("auto-truncate-after")
+                .long("auto-truncate-after")
+                .num_args(1)
+                .value_name("NUM")
+                .help("Truncate the rest of a line and show the number of truncated bytes when text-wrapping the line needs more than NUM wrapped rows. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("continuation-line-numbers")
+                .long("continuation-line-numbers")
+                .action(ArgAction::SetTrue)
+                .help("Draw the dimmed line number on each row wrapped from a line instead of a blank gutter. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("match-indicator")
+                .long("match-indicator")
+                .action(ArgAction::SetTrue)
+                .help("Draw a marker in a narrow column before the line numbers of matched lines. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("no-highlight")
+                .long("no-highlight")
+                .action(ArgAction::SetTrue)
+                .help("Draw code without syntax highlighting. Gutters, grids and matched regions are still drawn. This is faster than highlighting. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("border")
+                .long("border")
+                .num_args(1)
+                .value_name("STYLE")
+                .default_value("none")
+                .value_parser(["none", "rounded"])
+                .ignore_case(true)
+                .help("Border drawn around each file. 'rounded' encloses each file in a box with rounded corners, which is useful for screenshots. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("reserve-separator-gutter")
+                .long("reserve-separator-gutter")
+                .action(ArgAction::SetTrue)
+                .help("Always reserve the width of \"...\" separators in the gutter even when a file has only one chunk. This aligns gutters across many small files. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("file-index")
+                .long("file-index")
+                .action(ArgAction::SetTrue)
+                .help("Print an index which lists each file and its number of matched lines before printing the files. Nothing is printed until the search finishes. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("match-emphasis")
+                .long("match-emphasis")
+                .num_args(1)
+                .value_name("LINES")
+                .default_value("matched")
+                .value_parser(["matched", "chunk", "none"])
+                .ignore_case(true)
+                .help("Lines painted with the background color of matched lines. 'matched' paints only matched lines, 'chunk' paints all lines in chunks and 'none' paints no line. Matched regions are highlighted in any case. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("highlight-budget")
+                .long("highlight-budget")
+                .num_args(1)
+                .value_name("MILLIS")
+                .help("Stop highlighting a file and draw the rest of the file as plain text when highlighting it takes longer than MILLIS milliseconds. This is useful to bound the time of previews on huge or pathological files. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("line-count")
+                .long("line-count")
+                .action(ArgAction::SetTrue)
+                .help("Show the total number of lines of each file in its header. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("header-lines")
+                .long("header-lines")
+                .num_args(1)
+                .value_name("KIND")
+                .default_value("none")
+                .value_parser(["none", "matches", "chunks"])
+                .ignore_case(true)
+                .help("List line numbers in the header of each file like \"(lines 12,40-42)\". 'matches' lists matched lines and 'chunks' lists the line ranges of chunks. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("edge-separators")
+                .long("edge-separators")
+                .action(ArgAction::SetTrue)
+                .help("Draw separators also before the first chunk and after the last chunk of each file when lines at the start or the end of the file are not shown. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("match-color")
+                .long("match-color")
+                .num_args(1)
+                .value_name("COLOR")
+                .help("Background color of matched regions in the form of '#RRGGBB' or '#RGB' instead of the color of the theme. The color is ignored when the terminal supports only 16 colors. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("focus-match")
+                .long("focus-match")
+                .action(ArgAction::SetTrue)
+                .help("Dim the text of matched lines except for the matched regions. This makes matches stand out in very long lines. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("show-eol")
+                .long("show-eol")
+                .num_args(1)
+                .value_name("CHAR")
+                .help("Draw the character in dim style at the end of each line like '¶' or '$' to reveal trailing whitespaces. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("numbering")
+                .long("numbering")
+                .num_args(1)
+                .value_name("MODE")
+                .default_value("absolute")
+                .value_parser(["absolute", "chunk"])
+                .ignore_case(true)
+                .help("How line numbers are counted. 'absolute' shows line numbers in files and 'chunk' counts line numbers from 1 at the start of each chunk. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("no-trailing-newline")
+                .long("no-trailing-newline")
+                .action(ArgAction::SetTrue)
+                .help("Do not terminate the last row of the output with a newline. This is useful to embed the output in another text. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("min-text-width")
+                .long("min-text-width")
+                .num_args(1)
+                .value_name("NUM")
+                .help("Draw line numbers on their own rows above lines when the text next to the gutter would be narrower than NUM columns. The default value is 10. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("collapse-repeats")
+                .long("collapse-repeats")
+                .action(ArgAction::SetTrue)
+                .help("Collapse consecutive identical lines in each chunk into one row with the count like \"(×3)\". The gutter shows the range of the collapsed line numbers. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("no-bat-assets")
+                .long("no-bat-assets")
+                .action(ArgAction::SetTrue)
+                .help("Do not look for the themes and syntaxes installed for bat in bat's config and cache directories. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("gutter-only")
+                .long("gutter-only")
+                .action(ArgAction::SetTrue)
+                .help("Print only the gutter with line numbers for each line without the code so that it can be pasted next to code copied from somewhere else. Each row corresponds to one line since separators are not printed. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("hanging-indent")
+                .long("hanging-indent")
+                .action(ArgAction::SetTrue)
+                .help("Indent the continuation rows of wrapped lines to the first non-whitespace column of the lines like soft-wrap of editors. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("min-lnum-width")
+                .long("min-lnum-width")
+                .num_args(1)
+                .value_name("NUM")
+                .help("Minimum number of columns of line numbers in gutter. This keeps gutters of files aligned regardless of their line numbers. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("no-highlight-matches")
+                .long("no-highlight-matches")
+                .action(ArgAction::SetTrue)
+                .help("Paint neither matched lines nor matched regions. Line numbers of matched lines are still highlighted. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("tabular")
+                .long("tabular")
+                .action(ArgAction::SetTrue)
+                .help("Align the fields of delimiter-separated lines like TSV or CSV into columns. The width of each column is the widest field in the chunk. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("delimiter")
+                .long("delimiter")
+                .num_args(1)
+                .value_name("CHAR")
+                .help("Delimiter of fields for --tabular. '\\t' or 'tab' means a tab character. By default, ',' is used for .csv files and a tab is used for other files"),
+        )
+        .arg(
+            Arg::new("plain-gutter")
+                .long("plain-gutter")
+                .action(ArgAction::SetTrue)
+                .help("Paint line numbers of matched lines and the gutter in the same color as line numbers of context lines. Code is still highlighted. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("form-feed")
+                .long("form-feed")
+                .action(ArgAction::SetTrue)
+                .help("Print a form feed character between files so that each file starts on a new page when the output is sent to a printer (e.g. `lp`). This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("disable-font-style")
+                .long("disable-font-style")
+                .num_args(1)
+                .value_name("STYLE")
+                .action(ArgAction::Append)
+                .value_parser(["bold", "italic", "underline"])
+                .ignore_case(true)
+                .help("Never emit the font style of themes for terminals rendering it poorly. This option is repeatable. This option is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("literal-tabs-in-strings")
+                .long("literal-tabs-in-strings")
+                .action(ArgAction::SetTrue)
+                .help("Expand tab characters only in code and keep tabs in string literals or comments as-is. Whether a tab is in a string or a comment is decided by the scopes of the syntax definition ('string' and 'comment'). This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("strict-utf8")
+                .long("strict-utf8")
+                .action(ArgAction::SetTrue)
+                .help("Skip rendering a file which is not valid UTF-8 and show the reason in its header instead of replacing invalid bytes with U+FFFD. This flag is only for syntect printer"),
+        )
+        .arg(
+            Arg::new("paging")
+                .long("paging")
+                .num_args(1)
+                .value_name("WHEN")
+                .default_value("never")
+                .value_parser(["always", "auto", "never"])
+                .ignore_case(true)
+                .help("Print the output to a pager command. The command is $PAGER or `less -R` when it is not set. 'auto' uses the pager only when stdout is a terminal. This option is only for syntect printer"),
         );
 
     #[cfg(feature = "ripgrep")]
@@ -156,24 +671,34 @@ fn command() -> Command {
             .arg(
                 Arg::new("no-ignore")
                     .long("no-ignore")
+                    .action(ArgAction::SetTrue)
                     .help("Don't respect ignore files (.gitignore, .ignore, etc.)"),
             )
             .arg(
                 Arg::new("ignore-case")
                     .short('i')
                     .long("ignore-case")
+                    .action(ArgAction::SetTrue)
                     .help("When this flag is provided, the given pattern will be searched case insensitively"),
             )
             .arg(
                 Arg::new("smart-case")
                     .short('S')
                     .long("smart-case")
+                    .action(ArgAction::SetTrue)
                     .help("Search case insensitively if the pattern is all lowercase. Search case sensitively otherwise"),
             )
+            .arg(
+                Arg::new("search-info")
+                    .long("search-info")
+                    .action(ArgAction::SetTrue)
+                    .help("Show a note about the search such as case-insensitive matching in the header of each file. This flag is only for syntect printer"),
+            )
             .arg(
                 Arg::new("hidden")
                     .short('.')
                     .long("hidden")
+                    .action(ArgAction::SetTrue)
                     .help("Search hidden files and directories. By default, hidden files and directories are skipped"),
             )
             .arg(
@@ -189,45 +714,53 @@ fn command() -> Command {
             .arg(
                 Arg::new("glob-case-insensitive")
                     .long("glob-case-insensitive")
+                    .action(ArgAction::SetTrue)
                     .help("Process glob patterns given with the -g/--glob flag case insensitively"),
             )
             .arg(
                 Arg::new("fixed-strings")
                     .short('F')
                     .long("fixed-strings")
+                    .action(ArgAction::SetTrue)
                     .help("Treat the pattern as a literal string instead of a regular expression"),
             )
             .arg(
                 Arg::new("word-regexp")
                     .short('w')
                     .long("word-regexp")
+                    .action(ArgAction::SetTrue)
                     .help("Only show matches surrounded by word boundaries"),
             )
             .arg(
                 Arg::new("follow-symlink")
                     .short('L')
                     .long("follow")
+                    .action(ArgAction::SetTrue)
                     .help("When this flag is enabled, hgrep will follow symbolic links while traversing directories"),
             )
             .arg(
                 Arg::new("multiline")
                     .short('U')
                     .long("multiline")
+                    .action(ArgAction::SetTrue)
                     .help("Enable matching across multiple lines"),
             )
             .arg(
                 Arg::new("multiline-dotall")
                     .long("multiline-dotall")
+                    .action(ArgAction::SetTrue)
                     .help("Enable \"dot all\" in your regex pattern, which causes '.' to match newlines when multiline searching is enabled"),
             )
             .arg(
                 Arg::new("crlf")
                     .long("crlf")
+                    .action(ArgAction::SetTrue)
                     .help(r"When enabled, hgrep will treat CRLF ('\r\n') as a line terminator instead of just '\n'. This flag is useful on Windows"),
             )
             .arg(
                 Arg::new("mmap")
                     .long("mmap")
+                    .action(ArgAction::SetTrue)
                     .help("Search using memory maps when possible. mmap is disabled by default unlike ripgrep"),
             )
             .arg(
@@ -249,12 +782,14 @@ fn command() -> Command {
                 Arg::new("line-regexp")
                     .short('x')
                     .long("line-regexp")
+                    .action(ArgAction::SetTrue)
                     .help("Only show matches surrounded by line boundaries. This is equivalent to putting ^...$ around the search pattern"),
             )
             .arg(
                 Arg::new("pcre2")
                     .short('P')
                     .long("pcre2")
+                    .action(ArgAction::SetTrue)
                     .help("When this flag is present, hgrep will use the PCRE2 regex engine instead of its default regex engine"),
             )
             .arg(
@@ -263,7 +798,7 @@ fn command() -> Command {
                     .long("type")
                     .num_args(1)
                     .value_name("TYPE")
-                    .action(clap::ArgAction::Append)
+                    .action(ArgAction::Append)
                     .help("Only search files matching TYPE. This option is repeatable. --type-list can print the list of types"),
             )
             .arg(
@@ -272,12 +807,13 @@ fn command() -> Command {
                     .long("type-not")
                     .num_args(1)
                     .value_name("TYPE")
-                    .action(clap::ArgAction::Append)
+                    .action(ArgAction::Append)
                     .help("Do not search files matching TYPE. Inverse of --type. This option is repeatable. --type-list can print the list of types"),
             )
             .arg(
                 Arg::new("type-list")
                     .long("type-list")
+                    .action(ArgAction::SetTrue)
                     .help("Show all supported file types and their corresponding globs"),
             )
             .arg(
@@ -291,16 +827,25 @@ fn command() -> Command {
                 Arg::new("invert-match")
                     .short('v')
                     .long("invert-match")
+                    .action(ArgAction::SetTrue)
                     .help("Invert matching. Show lines that do not match the given pattern"),
             )
+            .arg(
+                Arg::new("highlight-all")
+                    .long("highlight-all")
+                    .action(ArgAction::SetTrue)
+                    .help("Highlight occurrences of the pattern also in context lines. This is useful with --invert-match or --max-count where some lines containing the pattern are not matched lines. This flag is only for syntect printer"),
+            )
             .arg(
                 Arg::new("one-file-system")
                     .long("one-file-system")
+                    .action(ArgAction::SetTrue)
                     .help("When enabled, the search will not cross file system boundaries relative to where it started from"),
             )
             .arg(
                 Arg::new("no-unicode")
                     .long("no-unicode")
+                    .action(ArgAction::SetTrue)
                     .help("Disable unicode-aware regular expression matching"),
             )
             .arg(
@@ -354,38 +899,73 @@ fn generate_completion_script(shell: &str) {
     }
 }
 
+fn sort_order(matches: &clap::ArgMatches) -> SortOrder {
+    match matches.get_one::<String>("sort").unwrap().as_str() {
+        s if s.eq_ignore_ascii_case("none") => SortOrder::None,
+        s if s.eq_ignore_ascii_case("path") => SortOrder::Path,
+        s if s.eq_ignore_ascii_case("path-reverse") => SortOrder::PathReverse,
+        _ => unreachable!(), // Option value was validated by clap
+    }
+}
+
+// Read all files from stdin before printing them at once
+fn print_all_stdin<P: Printer>(
+    printer: P,
+    min_context: u64,
+    max_context: u64,
+    context_percent: Option<ContextPercent>,
+    sort: SortOrder,
+) -> Result<bool> {
+    let files = io::BufReader::new(io::stdin())
+        .grep_lines()
+        .chunks_per_file(min_context, max_context)
+        .context_percent(context_percent)
+        .collect::<Result<Vec<_>>>()?;
+    let found = !files.is_empty();
+    printer.print_all(files, sort)?;
+    Ok(found)
+}
+
 #[cfg(feature = "ripgrep")]
 fn build_ripgrep_config(
     min_context: u64,
     max_context: u64,
+    context_percent: Option<ContextPercent>,
     matches: &clap::ArgMatches,
 ) -> Result<ripgrep::Config<'_>> {
     let mut config = ripgrep::Config::default();
     config
         .min_context(min_context)
         .max_context(max_context)
-        .no_ignore(matches.contains_id("no-ignore"))
-        .hidden(matches.contains_id("hidden"))
-        .case_insensitive(matches.contains_id("ignore-case"))
-        .smart_case(matches.contains_id("smart-case"))
-        .glob_case_insensitive(matches.contains_id("glob-case-insensitive"))
-        .pcre2(matches.contains_id("pcre2")) // must be before fixed_string
-        .fixed_strings(matches.contains_id("fixed-strings"))
-        .word_regexp(matches.contains_id("word-regexp"))
-        .follow_symlink(matches.contains_id("follow-symlink"))
-        .multiline(matches.contains_id("multiline"))
-        .crlf(matches.contains_id("crlf"))
-        .multiline_dotall(matches.contains_id("multiline-dotall"))
-        .mmap(matches.contains_id("mmap"))
-        .line_regexp(matches.contains_id("line-regexp"))
-        .invert_match(matches.contains_id("invert-match"))
-        .one_file_system(matches.contains_id("one-file-system"))
-        .no_unicode(matches.contains_id("no-unicode"));
+        .no_ignore(matches.get_flag("no-ignore"))
+        .hidden(matches.get_flag("hidden"))
+        .case_insensitive(matches.get_flag("ignore-case"))
+        .smart_case(matches.get_flag("smart-case"))
+        .glob_case_insensitive(matches.get_flag("glob-case-insensitive"))
+        .pcre2(matches.get_flag("pcre2")) // must be before fixed_string
+        .fixed_strings(matches.get_flag("fixed-strings"))
+        .word_regexp(matches.get_flag("word-regexp"))
+        .follow_symlink(matches.get_flag("follow-symlink"))
+        .multiline(matches.get_flag("multiline"))
+        .crlf(matches.get_flag("crlf"))
+        .multiline_dotall(matches.get_flag("multiline-dotall"))
+        .mmap(matches.get_flag("mmap"))
+        .line_regexp(matches.get_flag("line-regexp"))
+        .invert_match(matches.get_flag("invert-match"))
+        .highlight_all(matches.get_flag("highlight-all"))
+        .one_file_system(matches.get_flag("one-file-system"))
+        .no_unicode(matches.get_flag("no-unicode"))
+        .text(matches.get_flag("text"))
+        .sort(sort_order(matches));
 
     if let Some(globs) = matches.get_many::<String>("glob") {
         config.globs(globs.map(String::as_str));
     }
 
+    if let Some(percent) = context_percent {
+        config.context_percent(percent);
+    }
+
     if let Some(num) = matches.get_one::<String>("max-count") {
         let num = num
             .parse()
@@ -431,6 +1011,141 @@ fn build_ripgrep_config(
     Ok(config)
 }
 
+#[cfg(feature = "syntect-printer")]
+fn parse_invalid_utf8(arg: &str) -> Result<hgrep::printer::InvalidUtf8> {
+    use hgrep::printer::InvalidUtf8;
+    if arg.eq_ignore_ascii_case("escape") {
+        return Ok(InvalidUtf8::Escape);
+    }
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(InvalidUtf8::Replace(c)),
+        _ => anyhow::bail!(
+            "--invalid-utf8 option value must be a single character or 'escape' but got {:?}",
+            arg
+        ),
+    }
+}
+
+#[cfg(feature = "syntect-printer")]
+fn parse_delimiter(arg: &str) -> Result<char> {
+    if arg == "\\t" || arg.eq_ignore_ascii_case("tab") {
+        return Ok('\t');
+    }
+    let mut chars = arg.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => anyhow::bail!(
+            "--delimiter option value must be a single character or 'tab' but got {:?}",
+            arg
+        ),
+    }
+}
+
+// Options of the images printed by --svg
+#[cfg(feature = "syntect-printer")]
+struct SvgStyle {
+    window: bool,
+    font_family: Option<String>,
+    font_size: Option<f64>,
+    line_height: Option<f64>,
+}
+
+#[cfg(feature = "syntect-printer")]
+impl SvgStyle {
+    fn printer(self, opts: PrinterOptions<'_>) -> Result<SvgPrinter<'_, io::Stdout>> {
+        let mut printer = SvgPrinter::new(io::stdout(), opts)?;
+        warn_unknown_theme_env(printer.unknown_default_theme());
+        printer.window_chrome(self.window);
+        if let Some(family) = self.font_family {
+            printer.font_family(family);
+        }
+        if let Some(size) = self.font_size {
+            printer.font_size(size);
+        }
+        if let Some(ratio) = self.line_height {
+            printer.line_height(ratio);
+        }
+        Ok(printer)
+    }
+}
+
+#[cfg(feature = "syntect-printer")]
+fn parse_hex_color(arg: &str) -> Result<(u8, u8, u8)> {
+    let Some(hex) = arg.strip_prefix('#') else {
+        anyhow::bail!(
+            "Color {:?} must start with '#'. The format is #RRGGBB or #RGB",
+            arg
+        );
+    };
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        anyhow::bail!("Color {:?} contains non-hexadecimal digit", arg);
+    }
+    let digit =
+        |i: usize, len: usize| u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).unwrap();
+    match hex.len() {
+        6 => Ok((digit(0, 2), digit(1, 2), digit(2, 2))),
+        3 => Ok((digit(0, 1) * 0x11, digit(1, 1) * 0x11, digit(2, 1) * 0x11)), // #abc is equivalent to #aabbcc
+        n => anyhow::bail!(
+            "Color {:?} must have 6 or 3 hexadecimal digits but has {} digits",
+            arg,
+            n
+        ),
+    }
+}
+
+fn parse_range(arg: &str) -> Result<(PathBuf, (u64, u64))> {
+    let (path, range) = match arg.rsplit_once(':') {
+        Some((p, r)) if !p.is_empty() => (p, r),
+        _ => anyhow::bail!(
+            "Path is missing in --range option value {:?}. The format is PATH:START-END",
+            arg
+        ),
+    };
+    let parse_lnum = |s: &str| -> Result<u64> {
+        s.parse().with_context(|| {
+            format!(
+                "could not parse {:?} as line number in --range option value {:?}",
+                s, arg
+            )
+        })
+    };
+    let (start, end) = match range.split_once('-') {
+        Some((s, e)) => (parse_lnum(s)?, parse_lnum(e)?),
+        None => {
+            let n = parse_lnum(range)?;
+            (n, n)
+        }
+    };
+    if start == 0 || end < start {
+        anyhow::bail!(
+            "Invalid line range {}-{} in --range option value {:?}",
+            start,
+            end,
+            arg
+        );
+    }
+    Ok((PathBuf::from(path), (start, end)))
+}
+
+fn files_for_ranges<'a>(args: impl Iterator<Item = &'a String>) -> Result<Vec<File>> {
+    let mut ranges: Vec<(PathBuf, Vec<(u64, u64)>)> = vec![];
+    for arg in args {
+        let (path, range) = parse_range(arg)?;
+        match ranges.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, rs)) => rs.push(range),
+            None => ranges.push((path, vec![range])),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(path, rs)| {
+            let msg = format!("could not read file {:?} for --range option", &path);
+            File::with_ranges(path, rs).context(msg)
+        })
+        .collect()
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum PrinterKind {
     #[cfg(feature = "bat-printer")]
@@ -470,6 +1185,89 @@ fn app() -> Result<bool> {
         .parse()
         .context("could not parse \"max-context\" option value as unsigned integer")?;
     let max_context = cmp::max(min_context, max_context);
+    let (min_context, max_context) = if matches.get_flag("fill-height") {
+        let height = if let Some(height) = matches.get_one::<String>("term-height") {
+            height
+                .parse()
+                .context("could not parse \"term-height\" option value as unsigned integer")?
+        } else {
+            use terminal_size::{terminal_size, Height};
+            let Some((_, Height(height))) = terminal_size() else {
+                anyhow::bail!("Could not detect the terminal height for --fill-height. Specify it with --term-height");
+            };
+            height
+        };
+        let context = hgrep::chunk::context_for_height(height);
+        (context, context)
+    } else {
+        (min_context, max_context)
+    };
+
+    let context_percent = if let Some(percent) = matches.get_one::<String>("context-percent") {
+        let percent: u64 = percent
+            .parse()
+            .context("could not parse \"context-percent\" option value as unsigned integer")?;
+        if percent > 100 {
+            anyhow::bail!(
+                "--context-percent option value must be 100 or less: {}",
+                percent
+            );
+        }
+        let max = matches
+            .get_one::<String>("context-percent-max")
+            .unwrap()
+            .parse()
+            .context("could not parse \"context-percent-max\" option value as unsigned integer")?;
+        Some(ContextPercent { percent, max })
+    } else {
+        None
+    };
+
+    let locations_format = matches.get_flag("locations").then(|| {
+        match matches
+            .get_one::<String>("locations-format")
+            .unwrap()
+            .as_str()
+        {
+            f if f.eq_ignore_ascii_case("json") => LocationFormat::Json,
+            f => LocationFormat::Template(f),
+        }
+    });
+    let locations_urls = matches.get_flag("locations-urls");
+    #[cfg(feature = "syntect-printer")]
+    let svg_style = if matches.get_flag("svg") {
+        let parse_size = |name: &str| -> Result<Option<f64>> {
+            let Some(value) = matches.get_one::<String>(name) else {
+                return Ok(None);
+            };
+            match value.parse::<f64>() {
+                Ok(size) if size.is_finite() && size > 0.0 => Ok(Some(size)),
+                _ => anyhow::bail!(
+                    "\"{}\" option value must be a positive number but got {:?}",
+                    name,
+                    value
+                ),
+            }
+        };
+        Some(SvgStyle {
+            window: matches.get_flag("svg-window"),
+            font_family: matches.get_one::<String>("svg-font-family").cloned(),
+            font_size: parse_size("svg-font-size")?,
+            line_height: parse_size("svg-line-height")?,
+        })
+    } else {
+        None
+    };
+    #[cfg(feature = "bat-printer")]
+    if matches.get_flag("svg") && printer_kind == PrinterKind::Bat {
+        anyhow::bail!("--svg flag is only available for syntect printer");
+    }
+    #[cfg(feature = "syntect-printer")]
+    let rtf = matches.get_flag("rtf");
+    #[cfg(feature = "bat-printer")]
+    if matches.get_flag("rtf") && printer_kind == PrinterKind::Bat {
+        anyhow::bail!("--rtf flag is only available for syntect printer");
+    }
 
     let mut printer_opts = PrinterOptions::default();
     if let Some(width) = matches.get_one::<String>("tab") {
@@ -486,11 +1284,36 @@ fn app() -> Result<bool> {
             printer_opts.theme = Some(var);
         }
     }
+    #[cfg(feature = "syntect-printer")]
+    let theme_env = if printer_kind == PrinterKind::Syntect {
+        theme_from_env()
+    } else {
+        None
+    };
+    #[cfg(feature = "syntect-printer")]
+    {
+        printer_opts.default_theme = theme_env.as_deref();
+    }
     if let Some(theme) = matches.get_one::<String>("theme") {
         printer_opts.theme = Some(theme);
     }
+    if let Some(theme) = matches.get_one::<String>("theme-light") {
+        printer_opts.theme_light = Some(theme);
+    }
+    if let Some(theme) = matches.get_one::<String>("theme-dark") {
+        printer_opts.theme_dark = Some(theme);
+    }
+    printer_opts.theme_mode = match matches.get_one::<String>("theme-mode").unwrap().as_str() {
+        m if m.eq_ignore_ascii_case("light") => Some(ThemeMode::Light),
+        m if m.eq_ignore_ascii_case("dark") => Some(ThemeMode::Dark),
+        m if m.eq_ignore_ascii_case("auto") => None,
+        _ => unreachable!(), // Option value was validated by clap
+    };
+    if let Some(themes) = matches.get_many::<String>("theme-fallback") {
+        printer_opts.theme_fallbacks = themes.map(String::as_str).collect();
+    }
 
-    let is_grid = matches.contains_id("grid");
+    let is_grid = matches.get_flag("grid");
     #[cfg(feature = "bat-printer")]
     if printer_kind == PrinterKind::Bat {
         if let Ok("plain" | "header" | "numbers") =
@@ -501,7 +1324,7 @@ fn app() -> Result<bool> {
             }
         }
     }
-    if matches.contains_id("no-grid") && !is_grid {
+    if matches.get_flag("no-grid") && !is_grid {
         printer_opts.grid = false;
     }
 
@@ -525,13 +1348,45 @@ fn app() -> Result<bool> {
         }
     }
 
-    if matches.contains_id("first-only") {
-        printer_opts.first_only = true;
+    match matches.get_one::<String>("color-level").unwrap().as_str() {
+        l if l.eq_ignore_ascii_case("auto") => {} // Detected by `PrinterOptions::default()`
+        l if l.eq_ignore_ascii_case("truecolor") => {
+            printer_opts.color_support = TermColorSupport::True
+        }
+        "256" => printer_opts.color_support = TermColorSupport::Ansi256,
+        "16" => printer_opts.color_support = TermColorSupport::Ansi16,
+        _ => unreachable!(), // Option value was validated by clap
+    }
+
+    if let Some(what) = matches.get_one::<String>("first-only") {
+        if what.eq_ignore_ascii_case("match") {
+            printer_opts.first_only = FirstOnly::Match;
+        } else if what.eq_ignore_ascii_case("chunk") {
+            printer_opts.first_only = FirstOnly::Chunk;
+        } else {
+            unreachable!(); // Option value was validated by clap
+        }
+    }
+
+    if matches.get_flag("trim-chunk-blanks") {
+        printer_opts.trim_chunk_blanks = true;
+    }
+
+    if matches.get_flag("text") {
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--text flag is only available for syntect printer since bat printer does not support printing binary files");
+        }
+        printer_opts.binary_as_text = true;
+    }
+
+    if let Some(lang) = matches.get_one::<String>("language") {
+        printer_opts.language = Some(lang);
     }
 
     #[cfg(feature = "syntect-printer")]
     {
-        if matches.contains_id("background") {
+        if matches.get_flag("background") {
             printer_opts.background_color = true;
             #[cfg(feature = "bat-printer")]
             if printer_kind == PrinterKind::Bat {
@@ -539,7 +1394,7 @@ fn app() -> Result<bool> {
             }
         }
 
-        if matches.contains_id("ascii-lines") {
+        if matches.get_flag("ascii-lines") {
             printer_opts.ascii_lines = true;
             #[cfg(feature = "bat-printer")]
             if printer_kind == PrinterKind::Bat {
@@ -548,8 +1403,488 @@ fn app() -> Result<bool> {
         }
     }
 
+    #[cfg(feature = "syntect-printer")]
+    if let Some(mappings) = matches.get_many::<String>("map-syntax") {
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--map-syntax option is only available for syntect printer");
+        }
+        for mapping in mappings {
+            let Some((glob, syntax)) = mapping.rsplit_once(':') else {
+                anyhow::bail!(
+                    "Mapping at --map-syntax must be in the form of 'GLOB:SYNTAX' but got {:?}",
+                    mapping
+                );
+            };
+            printer_opts.syntax_mappings.push((glob, syntax));
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("align-tabs") {
+        printer_opts.align_tabs = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--align-tabs flag is only available for syntect printer. Note that bat printer always aligns tabs");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("separator-gap") {
+        printer_opts.separator_gap = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--separator-gap flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("hyperlinks") {
+        printer_opts.hyperlinks = terminal_size::terminal_size().is_some();
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--hyperlinks flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("indent-guides") {
+        printer_opts.indent_guides = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--indent-guides flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("match-sparkline") {
+        printer_opts.match_sparkline = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--match-sparkline flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("overflow-tooltip") {
+        printer_opts.overflow_tooltip = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--overflow-tooltip flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("bracket-colors") {
+        printer_opts.bracket_colors = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--bracket-colors flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(width) = matches.get_one::<String>("header-path-width") {
+        let width = width
+            .parse()
+            .context("could not parse \"header-path-width\" option value as unsigned integer")?;
+        printer_opts.header_path_width = Some(width);
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--header-path-width option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(value) = matches.get_one::<String>("invalid-utf8") {
+        printer_opts.invalid_utf8 = parse_invalid_utf8(value)?;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--invalid-utf8 option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("transparent") {
+        printer_opts.transparent = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--transparent flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    match matches.get_one::<String>("header-align").unwrap().as_str() {
+        a if a.eq_ignore_ascii_case("left") => {}
+        a if a.eq_ignore_ascii_case("right") => {
+            printer_opts.header_align = hgrep::printer::HeaderAlign::Right;
+            #[cfg(feature = "bat-printer")]
+            if printer_kind == PrinterKind::Bat {
+                anyhow::bail!("--header-align option is only available for syntect printer");
+            }
+        }
+        _ => unreachable!(), // Option value was validated by clap
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(rows) = matches.get_one::<String>("auto-truncate-after") {
+        let rows = rows
+            .parse()
+            .context("could not parse \"auto-truncate-after\" option value as unsigned integer")?;
+        printer_opts.auto_truncate_after = Some(rows);
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--auto-truncate-after option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("continuation-line-numbers") {
+        printer_opts.continuation_line_numbers = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--continuation-line-numbers flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("match-indicator") {
+        printer_opts.match_indicator = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--match-indicator flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("no-highlight") {
+        printer_opts.highlight = false;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--no-highlight flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    match matches.get_one::<String>("border").unwrap().as_str() {
+        b if b.eq_ignore_ascii_case("none") => {}
+        b if b.eq_ignore_ascii_case("rounded") => {
+            printer_opts.border = hgrep::printer::BorderStyle::Rounded;
+            #[cfg(feature = "bat-printer")]
+            if printer_kind == PrinterKind::Bat {
+                anyhow::bail!("--border option is only available for syntect printer");
+            }
+        }
+        _ => unreachable!(), // Option value was validated by clap
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("reserve-separator-gutter") {
+        printer_opts.reserve_separator_gutter = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--reserve-separator-gutter flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("file-index") {
+        printer_opts.file_index = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--file-index flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    {
+        use hgrep::printer::MatchEmphasis;
+        let emphasis = match matches
+            .get_one::<String>("match-emphasis")
+            .unwrap()
+            .as_str()
+        {
+            e if e.eq_ignore_ascii_case("matched") => MatchEmphasis::MatchedOnly,
+            e if e.eq_ignore_ascii_case("chunk") => MatchEmphasis::WholeChunk,
+            e if e.eq_ignore_ascii_case("none") => MatchEmphasis::None,
+            _ => unreachable!(), // Option value was validated by clap
+        };
+        if emphasis != MatchEmphasis::MatchedOnly {
+            printer_opts.match_emphasis = emphasis;
+            #[cfg(feature = "bat-printer")]
+            if printer_kind == PrinterKind::Bat {
+                anyhow::bail!("--match-emphasis option is only available for syntect printer");
+            }
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(millis) = matches.get_one::<String>("highlight-budget") {
+        let millis = millis
+            .parse()
+            .context("could not parse \"highlight-budget\" option value as unsigned integer")?;
+        printer_opts.highlight_budget = Some(std::time::Duration::from_millis(millis));
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--highlight-budget option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("line-count") {
+        printer_opts.show_line_count = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--line-count flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    {
+        use hgrep::printer::HeaderLines;
+        let header_lines = match matches.get_one::<String>("header-lines").unwrap().as_str() {
+            k if k.eq_ignore_ascii_case("none") => HeaderLines::None,
+            k if k.eq_ignore_ascii_case("matches") => HeaderLines::Matches,
+            k if k.eq_ignore_ascii_case("chunks") => HeaderLines::Chunks,
+            _ => unreachable!(), // Option value was validated by clap
+        };
+        if header_lines != HeaderLines::None {
+            printer_opts.header_lines = header_lines;
+            #[cfg(feature = "bat-printer")]
+            if printer_kind == PrinterKind::Bat {
+                anyhow::bail!("--header-lines option is only available for syntect printer");
+            }
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("edge-separators") {
+        printer_opts.edge_separators = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--edge-separators flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(color) = matches.get_one::<String>("match-color") {
+        printer_opts.match_color = Some(parse_hex_color(color)?);
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--match-color option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("focus-match") {
+        printer_opts.focus_match = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--focus-match flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(marker) = matches.get_one::<String>("show-eol") {
+        let mut chars = marker.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => printer_opts.show_eol = Some(c),
+            _ => anyhow::bail!(
+                "--show-eol option value must be one character but got {:?}",
+                marker
+            ),
+        }
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--show-eol option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    {
+        use hgrep::printer::Numbering;
+        let numbering = match matches.get_one::<String>("numbering").unwrap().as_str() {
+            n if n.eq_ignore_ascii_case("absolute") => Numbering::Absolute,
+            n if n.eq_ignore_ascii_case("chunk") => Numbering::ChunkLocal,
+            _ => unreachable!(), // Option value was validated by clap
+        };
+        if numbering != Numbering::Absolute {
+            printer_opts.numbering = numbering;
+            #[cfg(feature = "bat-printer")]
+            if printer_kind == PrinterKind::Bat {
+                anyhow::bail!("--numbering option is only available for syntect printer");
+            }
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("no-trailing-newline") {
+        printer_opts.trailing_newline = false;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--no-trailing-newline flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(width) = matches.get_one::<String>("min-text-width") {
+        let width = width
+            .parse()
+            .context("could not parse \"min-text-width\" option value as unsigned integer")?;
+        printer_opts.min_text_width = Some(width);
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--min-text-width option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("collapse-repeats") {
+        printer_opts.collapse_repeats = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--collapse-repeats flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    {
+        printer_opts.bat_assets = !matches.get_flag("no-bat-assets");
+        #[cfg(feature = "bat-printer")]
+        if matches.get_flag("no-bat-assets") && printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--no-bat-assets flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("gutter-only") {
+        printer_opts.gutter_only = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--gutter-only flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("hanging-indent") {
+        printer_opts.hanging_indent = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--hanging-indent flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(all(feature = "ripgrep", feature = "bat-printer"))]
+    if matches.get_flag("highlight-all") && printer_kind == PrinterKind::Bat {
+        anyhow::bail!("--highlight-all flag is only available for syntect printer");
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(width) = matches.get_one::<String>("min-lnum-width") {
+        let width = width
+            .parse()
+            .context("could not parse \"min-lnum-width\" option value as unsigned integer")?;
+        printer_opts.min_lnum_width = Some(width);
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--min-lnum-width option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("no-highlight-matches") {
+        printer_opts.highlight_matches = false;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--no-highlight-matches flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("tabular") {
+        printer_opts.tabular = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--tabular flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(delimiter) = matches.get_one::<String>("delimiter") {
+        printer_opts.delimiter = Some(parse_delimiter(delimiter)?);
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--delimiter option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("plain-gutter") {
+        printer_opts.plain_gutter = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--plain-gutter flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("form-feed") {
+        printer_opts.form_feed = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--form-feed flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(styles) = matches.get_many::<String>("disable-font-style") {
+        use hgrep::printer::FontStyleKind;
+        printer_opts.disabled_font_styles = styles
+            .map(|s| match s.as_str() {
+                s if s.eq_ignore_ascii_case("bold") => FontStyleKind::Bold,
+                s if s.eq_ignore_ascii_case("italic") => FontStyleKind::Italic,
+                s if s.eq_ignore_ascii_case("underline") => FontStyleKind::Underline,
+                _ => unreachable!(), // Option value was validated by clap
+            })
+            .collect();
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--disable-font-style option is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("literal-tabs-in-strings") {
+        printer_opts.literal_tabs_in_strings = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--literal-tabs-in-strings flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if matches.get_flag("strict-utf8") {
+        printer_opts.strict_utf8 = true;
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--strict-utf8 flag is only available for syntect printer");
+        }
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    let paging = match matches.get_one::<String>("paging").unwrap().as_str() {
+        m if m.eq_ignore_ascii_case("always") => PagingMode::Always,
+        m if m.eq_ignore_ascii_case("auto") => PagingMode::Auto,
+        m if m.eq_ignore_ascii_case("never") => PagingMode::Never,
+        _ => unreachable!(), // Option value was validated by clap
+    };
+    #[cfg(all(feature = "syntect-printer", feature = "bat-printer"))]
+    if paging != PagingMode::Never && printer_kind == PrinterKind::Bat {
+        anyhow::bail!("--paging option is only available for syntect printer");
+    }
+
     #[cfg(feature = "bat-printer")]
-    if matches.contains_id("custom-assets") {
+    if matches.get_flag("custom-assets") {
         printer_opts.custom_assets = true;
         #[cfg(feature = "syntect-printer")]
         if printer_kind == PrinterKind::Syntect {
@@ -557,7 +1892,7 @@ fn app() -> Result<bool> {
         }
     }
 
-    if matches.contains_id("list-themes") {
+    if matches.get_flag("list-themes") {
         #[cfg(feature = "syntect-printer")]
         if printer_kind == PrinterKind::Syntect {
             hgrep::syntect::list_themes(io::stdout().lock(), &printer_opts)?;
@@ -573,25 +1908,142 @@ fn app() -> Result<bool> {
         unreachable!();
     }
 
+    #[cfg(feature = "syntect-printer")]
+    if let Some(paths) = matches.get_many::<String>("explain") {
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            anyhow::bail!("--explain option is only available for syntect printer");
+        }
+        let json = matches
+            .get_one::<String>("explain-format")
+            .unwrap()
+            .eq_ignore_ascii_case("json");
+        use hgrep::io::IgnoreBrokenPipe;
+        use std::io::Write;
+
+        let printer = SyntectPrinter::with_stdout(printer_opts)?;
+        warn_unknown_theme_env(printer.unknown_default_theme());
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for (i, path) in paths.enumerate() {
+            let contents = std::fs::read(path)
+                .with_context(|| format!("could not read file {:?} for --explain", path))?;
+            let explained = printer.explain(path.as_ref(), &contents);
+            let written = if json {
+                writeln!(out, "{}", explained.to_json())
+            } else if i > 0 {
+                writeln!(out).and_then(|_| writeln!(out, "{}", explained))
+            } else {
+                writeln!(out, "{}", explained)
+            };
+            written.ignore_broken_pipe()?;
+        }
+        out.flush().ignore_broken_pipe()?;
+        return Ok(true);
+    }
+
+    if let Some(ranges) = matches.get_many::<String>("range") {
+        let files = files_for_ranges(ranges)?;
+
+        if let Some(format) = locations_format {
+            let mut printer = LocationsPrinter::new(io::stdout(), format);
+            printer.urls(locations_urls);
+            for file in files {
+                printer.print(file)?;
+            }
+            return Ok(true);
+        }
+
+        #[cfg(feature = "syntect-printer")]
+        if let Some(style) = svg_style {
+            let printer = style.printer(printer_opts)?;
+            for file in files {
+                printer.print(file)?;
+            }
+            return Ok(true);
+        }
+
+        #[cfg(feature = "syntect-printer")]
+        if rtf {
+            let printer = RtfPrinter::new(io::stdout(), printer_opts)?;
+            warn_unknown_theme_env(printer.unknown_default_theme());
+            for file in files {
+                printer.print(file)?;
+            }
+            return Ok(true);
+        }
+
+        #[cfg(feature = "syntect-printer")]
+        if printer_kind == PrinterKind::Syntect {
+            let printer = SyntectPrinter::new(Output::new(paging)?, printer_opts)?;
+            warn_unknown_theme_env(printer.unknown_default_theme());
+            for file in files {
+                printer.print(file)?;
+            }
+            return Ok(true);
+        }
+
+        #[cfg(feature = "bat-printer")]
+        if printer_kind == PrinterKind::Bat {
+            let printer = BatPrinter::new(printer_opts);
+            for file in files {
+                printer.print(file)?;
+            }
+            return Ok(true);
+        }
+
+        unreachable!();
+    }
+
     #[cfg(feature = "ripgrep")]
-    if matches.contains_id("type-list") {
-        let config = build_ripgrep_config(min_context, max_context, &matches)?;
+    if matches.get_flag("type-list") {
+        let config = build_ripgrep_config(min_context, max_context, context_percent, &matches)?;
         config.print_types(io::stdout().lock())?;
         return Ok(true);
     }
 
     #[cfg(feature = "ripgrep")]
     if let Some(pattern) = matches.get_one::<String>("PATTERN") {
-        use std::path::PathBuf;
+        use hgrep::printer::SearchInfo;
 
         let paths = matches
             .get_many::<PathBuf>("PATH")
             .map(|p| p.map(PathBuf::as_path));
-        let config = build_ripgrep_config(min_context, max_context, &matches)?;
+        let mut config = build_ripgrep_config(min_context, max_context, context_percent, &matches)?;
+        config.batch(printer_opts.file_index);
+
+        if matches.get_flag("search-info") {
+            #[cfg(feature = "bat-printer")]
+            if printer_kind == PrinterKind::Bat {
+                anyhow::bail!("--search-info flag is only available for syntect printer since bat does not support this feature");
+            }
+            let case_insensitive = config.is_case_insensitive(pattern);
+            printer_opts.search_info = Some(SearchInfo { case_insensitive });
+        }
+
+        if let Some(format) = locations_format {
+            let mut printer = LocationsPrinter::new(io::stdout(), format);
+            printer.urls(locations_urls);
+            return ripgrep::grep(printer, pattern, paths, config);
+        }
+
+        #[cfg(feature = "syntect-printer")]
+        if let Some(style) = svg_style {
+            let printer = style.printer(printer_opts)?;
+            return ripgrep::grep(printer, pattern, paths, config);
+        }
+
+        #[cfg(feature = "syntect-printer")]
+        if rtf {
+            let printer = RtfPrinter::new(io::stdout(), printer_opts)?;
+            warn_unknown_theme_env(printer.unknown_default_theme());
+            return ripgrep::grep(printer, pattern, paths, config);
+        }
 
         #[cfg(feature = "syntect-printer")]
         if printer_kind == PrinterKind::Syntect {
-            let printer = SyntectPrinter::with_stdout(printer_opts)?;
+            let printer = SyntectPrinter::new(Output::new(paging)?, printer_opts)?;
+            warn_unknown_theme_env(printer.unknown_default_theme());
             return ripgrep::grep(printer, pattern, paths, config);
         }
 
@@ -604,14 +2056,79 @@ fn app() -> Result<bool> {
         unreachable!();
     }
 
+    let sort = sort_order(&matches);
+    let batch = sort != SortOrder::None || printer_opts.file_index;
+
+    if let Some(format) = locations_format {
+        let mut found = false;
+        let mut printer = LocationsPrinter::new(io::stdout(), format);
+        printer.urls(locations_urls);
+        if batch {
+            return print_all_stdin(printer, min_context, max_context, context_percent, sort);
+        }
+        for f in io::stdin()
+            .lock()
+            .grep_lines()
+            .chunks_per_file(min_context, max_context)
+            .context_percent(context_percent)
+        {
+            printer.print(f?)?;
+            found = true;
+        }
+        return Ok(found);
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if let Some(style) = svg_style {
+        let mut found = false;
+        let printer = style.printer(printer_opts)?;
+        if batch {
+            return print_all_stdin(printer, min_context, max_context, context_percent, sort);
+        }
+        for f in io::stdin()
+            .lock()
+            .grep_lines()
+            .chunks_per_file(min_context, max_context)
+            .context_percent(context_percent)
+        {
+            printer.print(f?)?;
+            found = true;
+        }
+        return Ok(found);
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    if rtf {
+        let mut found = false;
+        let printer = RtfPrinter::new(io::stdout(), printer_opts)?;
+        warn_unknown_theme_env(printer.unknown_default_theme());
+        if batch {
+            return print_all_stdin(printer, min_context, max_context, context_percent, sort);
+        }
+        for f in io::stdin()
+            .lock()
+            .grep_lines()
+            .chunks_per_file(min_context, max_context)
+            .context_percent(context_percent)
+        {
+            printer.print(f?)?;
+            found = true;
+        }
+        return Ok(found);
+    }
+
     #[cfg(feature = "syntect-printer")]
     if printer_kind == PrinterKind::Syntect {
-        use hgrep::printer::Printer;
         use rayon::prelude::*;
-        let printer = SyntectPrinter::with_stdout(printer_opts)?;
+        let printer = SyntectPrinter::new(Output::new(paging)?, printer_opts)?;
+        warn_unknown_theme_env(printer.unknown_default_theme());
+        if batch {
+            return print_all_stdin(printer, min_context, max_context, context_percent, sort);
+        }
         return io::BufReader::new(io::stdin())
             .grep_lines()
             .chunks_per_file(min_context, max_context)
+            .context_percent(context_percent)
             .par_bridge()
             .map(|file| {
                 printer.print(file?)?;
@@ -622,6 +2139,10 @@ fn app() -> Result<bool> {
 
     #[cfg(feature = "bat-printer")]
     if printer_kind == PrinterKind::Bat {
+        if batch {
+            let printer = std::sync::Mutex::new(BatPrinter::new(printer_opts));
+            return print_all_stdin(printer, min_context, max_context, context_percent, sort);
+        }
         let mut found = false;
         let printer = BatPrinter::new(printer_opts);
         // XXX: io::stdin().lock() is not available since bat's implementation internally takes lock of stdin
@@ -630,6 +2151,7 @@ fn app() -> Result<bool> {
         for f in io::BufReader::new(io::stdin())
             .grep_lines()
             .chunks_per_file(min_context, max_context)
+            .context_percent(context_percent)
         {
             printer.print(f?)?;
             found = true;
@@ -640,6 +2162,23 @@ fn app() -> Result<bool> {
     unreachable!();
 }
 
+// The default theme of syntect printer can be set with $HGREP_THEME. Unlike --theme, an unknown theme is not an error
+// since the variable is set globally. See `PrinterOptions::default_theme`
+#[cfg(feature = "syntect-printer")]
+fn theme_from_env() -> Option<String> {
+    env::var("HGREP_THEME").ok().filter(|n| !n.is_empty())
+}
+
+#[cfg(feature = "syntect-printer")]
+fn warn_unknown_theme_env(unknown: Option<&str>) {
+    if let Some(name) = unknown {
+        eprintln!(
+            "\x1b[1;93mwarning:\x1b[0m Unknown theme {:?} in $HGREP_THEME. The default theme is used instead",
+            name,
+        );
+    }
+}
+
 fn main() {
     #[cfg(windows)]
     {
@@ -668,4 +2207,181 @@ mod tests {
     fn cli_parser() {
         command().debug_assert();
     }
+
+    #[test]
+    fn flags_take_no_value() {
+        let matches = command()
+            .try_get_matches_from(["hgrep", "--no-grid", "--first-only", "--range", "foo.rs:1"])
+            .unwrap();
+        assert!(matches.get_flag("no-grid"));
+        assert_eq!(
+            matches.get_one::<String>("first-only").map(String::as_str),
+            Some("chunk"),
+        );
+        assert!(!matches.get_flag("grid"));
+        assert_eq!(
+            matches.get_one::<String>("range").map(String::as_str),
+            Some("foo.rs:1"),
+        );
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    #[test]
+    fn paging_option() {
+        let matches = command().try_get_matches_from(["hgrep"]).unwrap();
+        assert_eq!(
+            matches.get_one::<String>("paging").map(String::as_str),
+            Some("never"),
+        );
+        assert!(command()
+            .try_get_matches_from(["hgrep", "--paging", "sometimes"])
+            .is_err());
+    }
+
+    #[test]
+    fn first_only_option() {
+        let first_only = |args: &[&str]| {
+            command()
+                .try_get_matches_from(args)
+                .unwrap()
+                .get_one::<String>("first-only")
+                .cloned()
+        };
+        assert_eq!(first_only(&["hgrep"]), None);
+        assert_eq!(first_only(&["hgrep", "-f"]).as_deref(), Some("chunk"));
+        assert_eq!(
+            first_only(&["hgrep", "--first-only=match"]).as_deref(),
+            Some("match")
+        );
+        // The value must be given with '=' since the next argument is the search pattern
+        #[cfg(feature = "ripgrep")]
+        assert_eq!(
+            first_only(&["hgrep", "--first-only", "match"]).as_deref(),
+            Some("chunk")
+        );
+        assert!(command()
+            .try_get_matches_from(["hgrep", "--first-only=line"])
+            .is_err());
+    }
+
+    #[test]
+    fn color_level_option() {
+        let matches = command().try_get_matches_from(["hgrep"]).unwrap();
+        assert_eq!(
+            matches.get_one::<String>("color-level").map(String::as_str),
+            Some("auto"),
+        );
+        let matches = command()
+            .try_get_matches_from(["hgrep", "--color-level", "TrueColor"])
+            .unwrap();
+        assert!(matches
+            .get_one::<String>("color-level")
+            .unwrap()
+            .eq_ignore_ascii_case("truecolor"));
+        assert!(command()
+            .try_get_matches_from(["hgrep", "--color-level", "8"])
+            .is_err());
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    #[test]
+    fn parse_invalid_utf8_option() {
+        use hgrep::printer::InvalidUtf8;
+        assert_eq!(parse_invalid_utf8("?").unwrap(), InvalidUtf8::Replace('?'));
+        assert_eq!(
+            parse_invalid_utf8("\u{b7}").unwrap(),
+            InvalidUtf8::Replace('\u{b7}'),
+        );
+        assert_eq!(parse_invalid_utf8("escape").unwrap(), InvalidUtf8::Escape);
+        assert!(parse_invalid_utf8("").is_err());
+        assert!(parse_invalid_utf8("??").is_err());
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    #[test]
+    fn theme_from_env_var() {
+        // Only this test touches the variable
+        env::set_var("HGREP_THEME", "Nord");
+        assert_eq!(theme_from_env().as_deref(), Some("Nord"));
+        env::set_var("HGREP_THEME", "");
+        assert_eq!(theme_from_env(), None);
+        env::remove_var("HGREP_THEME");
+        assert_eq!(theme_from_env(), None);
+    }
+
+    #[cfg(feature = "syntect-printer")]
+    #[test]
+    fn parse_delimiter_option() {
+        assert_eq!(parse_delimiter(",").unwrap(), ',');
+        assert_eq!(parse_delimiter("|").unwrap(), '|');
+        assert_eq!(parse_delimiter("\\t").unwrap(), '\t');
+        assert_eq!(parse_delimiter("TAB").unwrap(), '\t');
+        assert_eq!(parse_delimiter("\t").unwrap(), '\t');
+        assert!(parse_delimiter("").is_err());
+        assert!(parse_delimiter(",,").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "syntect-printer")]
+    fn parse_hex_color_ok() {
+        let tests = [
+            ("#336699", (0x33, 0x66, 0x99)),
+            ("#ABCdef", (0xab, 0xcd, 0xef)),
+            ("#000000", (0, 0, 0)),
+            ("#fff", (0xff, 0xff, 0xff)),
+            ("#1a9", (0x11, 0xaa, 0x99)),
+        ];
+        for (input, want) in tests {
+            let got = parse_hex_color(input).unwrap();
+            assert_eq!(got, want, "input={:?}", input);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "syntect-printer")]
+    fn parse_hex_color_error() {
+        let tests = [
+            ("336699", "must start with '#'"),
+            ("", "must start with '#'"),
+            ("#", "has 0 digits"),
+            ("#12345", "has 5 digits"),
+            ("#1234567", "has 7 digits"),
+            ("#abcdeg", "non-hexadecimal digit"),
+            ("#+12", "non-hexadecimal digit"),
+            ("#ｆｆｆ", "non-hexadecimal digit"),
+        ];
+        for (input, want) in tests {
+            let msg = format!("{}", parse_hex_color(input).unwrap_err());
+            assert!(msg.contains(want), "wanted {:?} in {:?}", want, msg);
+        }
+    }
+
+    #[test]
+    fn parse_range_ok() {
+        let tests = [
+            ("foo.rs:1-10", ("foo.rs", (1, 10))),
+            ("foo.rs:3", ("foo.rs", (3, 3))),
+            ("C:\\foo.rs:3-4", ("C:\\foo.rs", (3, 4))),
+        ];
+        for (input, (path, range)) in tests {
+            let got = parse_range(input).unwrap();
+            assert_eq!(got, (PathBuf::from(path), range), "input={:?}", input);
+        }
+    }
+
+    #[test]
+    fn parse_range_error() {
+        let tests = [
+            ("foo.rs", "Path is missing"),
+            (":1-2", "Path is missing"),
+            ("foo.rs:a-2", "could not parse \"a\" as line number"),
+            ("foo.rs:1-", "could not parse \"\" as line number"),
+            ("foo.rs:0-2", "Invalid line range 0-2"),
+            ("foo.rs:5-2", "Invalid line range 5-2"),
+        ];
+        for (input, want) in tests {
+            let msg = format!("{}", parse_range(input).unwrap_err());
+            assert!(msg.contains(want), "wanted {:?} in {:?}", want, msg);
+        }
+    }
 }
@@ -0,0 +1,206 @@
+use crate::syntect::LockableWrite;
+use anyhow::{Context, Result};
+use std::env;
+use std::io::{self, Stdout, StdoutLock, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PagingMode {
+    Never,
+    // Use pager only when stdout is connected to terminal
+    Auto,
+    Always,
+}
+
+const DEFAULT_PAGER: &str = "less -R";
+
+// Colors are not shown by `less` without -R. It is added unless raw control characters are already enabled by -R or -r
+// (including combined short flags like -FRX)
+fn split_command(cmd: &str) -> Option<(&str, Vec<&str>)> {
+    let mut words = cmd.split_whitespace();
+    let prog = words.next()?;
+    let mut args: Vec<_> = words.collect();
+    let is_less = Path::new(prog).file_stem().is_some_and(|s| s == "less");
+    let raw = args.iter().any(|a| match a.strip_prefix("--") {
+        Some(long) => long.eq_ignore_ascii_case("raw-control-chars"),
+        None => a.starts_with('-') && a.contains(['R', 'r']),
+    });
+    if is_less && !raw {
+        args.push("-R");
+    }
+    Some((prog, args))
+}
+
+pub struct Pager {
+    child: Child,
+    stdin: Option<Mutex<ChildStdin>>,
+}
+
+impl Pager {
+    pub fn new(cmd: &str) -> Result<Self> {
+        let (prog, args) =
+            split_command(cmd).unwrap_or_else(|| split_command(DEFAULT_PAGER).unwrap());
+        let mut child = Command::new(prog)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("could not spawn pager command {:?}", cmd))?;
+        let stdin = child.stdin.take().map(Mutex::new);
+        Ok(Self { child, stdin })
+    }
+
+    // Spawn the pager command at $PAGER. `less -R` is used when it is not set
+    pub fn from_env() -> Result<Self> {
+        match env::var("PAGER") {
+            Ok(cmd) if !cmd.trim().is_empty() => Self::new(&cmd),
+            _ => Self::new(DEFAULT_PAGER),
+        }
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        // Close stdin to notify EOF to the pager, then wait for the user quitting the pager
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
+
+pub struct PagerLock<'a>(Option<MutexGuard<'a, ChildStdin>>);
+
+impl<'a> Write for PagerLock<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> LockableWrite<'a> for Pager {
+    type Locked = PagerLock<'a>;
+    fn lock(&'a self) -> Self::Locked {
+        // When other thread panicked while writing, the pager is no longer available
+        PagerLock(self.stdin.as_ref().and_then(|m| m.lock().ok()))
+    }
+}
+
+// Output of printer which is stdout or stdin of pager process
+pub enum Output {
+    Stdout(Stdout),
+    Pager(Pager),
+}
+
+impl Output {
+    pub fn new(mode: PagingMode) -> Result<Self> {
+        let paging = match mode {
+            PagingMode::Never => false,
+            PagingMode::Auto => terminal_size::terminal_size().is_some(),
+            PagingMode::Always => true,
+        };
+        if paging {
+            Ok(Output::Pager(Pager::from_env()?))
+        } else {
+            Ok(Output::Stdout(io::stdout()))
+        }
+    }
+}
+
+pub enum OutputLock<'a> {
+    Stdout(StdoutLock<'a>),
+    Pager(PagerLock<'a>),
+}
+
+impl<'a> Write for OutputLock<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputLock::Stdout(w) => w.write(buf),
+            OutputLock::Pager(w) => w.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputLock::Stdout(w) => w.flush(),
+            OutputLock::Pager(w) => w.flush(),
+        }
+    }
+}
+
+impl<'a> LockableWrite<'a> for Output {
+    type Locked = OutputLock<'a>;
+    fn lock(&'a self) -> Self::Locked {
+        match self {
+            Output::Stdout(s) => OutputLock::Stdout(s.lock()),
+            Output::Pager(p) => OutputLock::Pager(p.lock()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(split_command("less -R"), Some(("less", vec!["-R"])));
+        assert_eq!(split_command("less"), Some(("less", vec!["-R"])));
+        assert_eq!(split_command("  more  "), Some(("more", vec![])));
+        assert_eq!(
+            split_command("less -R -F"),
+            Some(("less", vec!["-R", "-F"]))
+        );
+        assert_eq!(split_command(""), None);
+        assert_eq!(split_command("less -F"), Some(("less", vec!["-F", "-R"])));
+        assert_eq!(
+            split_command("/usr/bin/less"),
+            Some(("/usr/bin/less", vec!["-R"]))
+        );
+        assert_eq!(split_command("less -FRX"), Some(("less", vec!["-FRX"])));
+        assert_eq!(split_command("less -r"), Some(("less", vec!["-r"])));
+        assert_eq!(
+            split_command("less --RAW-CONTROL-CHARS"),
+            Some(("less", vec!["--RAW-CONTROL-CHARS"]))
+        );
+        assert_eq!(
+            split_command("less --quit-if-one-screen"),
+            Some(("less", vec!["--quit-if-one-screen", "-R"]))
+        );
+        assert_eq!(split_command("lesspipe"), Some(("lesspipe", vec![])));
+    }
+
+    #[test]
+    fn test_spawn_error() {
+        let err = match Pager::new("this-command-does-not-exist") {
+            Err(e) => e,
+            Ok(_) => panic!("error did not occur"),
+        };
+        let msg = format!("{}", err);
+        assert!(msg.contains("could not spawn pager command"), "{:?}", msg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pager_quits_early() {
+        use crate::chunk::File;
+        use crate::printer::{Printer, PrinterOptions};
+        use crate::syntect::SyntectPrinter;
+        use std::path::PathBuf;
+
+        // `true` exits without reading stdin like the user quits pager immediately
+        let pager = Pager::new("true").unwrap();
+        let printer = SyntectPrinter::new(Output::Pager(pager), PrinterOptions::default()).unwrap();
+        for _ in 0..3 {
+            let contents = "fn main() {}\n".repeat(1000).into_bytes();
+            let file = File::new(PathBuf::from("test.rs"), vec![], vec![(1, 1000)], contents);
+            printer.print(file).unwrap();
+        }
+    }
+}
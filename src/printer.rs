@@ -1,6 +1,8 @@
 use crate::chunk::File;
 use anyhow::Result;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use term::terminfo::TermInfo;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -39,17 +41,331 @@ impl TermColorSupport {
     }
 }
 
+// Whether the terminal background is light or dark. It chooses a theme from the light/dark theme pair
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThemeMode {
+    Light,
+    Dark,
+}
+
+impl ThemeMode {
+    // $COLORFGBG is set by some terminals (e.g. rxvt, Konsole) as "{fg};{bg}" with ANSI color numbers
+    fn from_colorfgbg(var: &str) -> Option<Self> {
+        match var.rsplit(';').next()?.parse::<u8>().ok()? {
+            7 | 9..=15 => Some(ThemeMode::Light),
+            _ => Some(ThemeMode::Dark),
+        }
+    }
+
+    pub fn detect() -> Option<Self> {
+        env::var("COLORFGBG")
+            .ok()
+            .and_then(|v| Self::from_colorfgbg(&v))
+    }
+}
+
+// Metadata of the search which produced matches. Printers can show it to explain why lines matched
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct SearchInfo {
+    pub case_insensitive: bool,
+}
+
+impl SearchInfo {
+    pub fn note(&self) -> Option<&'static str> {
+        self.case_insensitive.then_some("case-insensitive")
+    }
+}
+
+// Style of the file path in the header of each file. Colors are RGB values and `None` means the default color of the
+// theme. A background color is painted to the end of the line even if `background_color` is not enabled. Colors are
+// ignored on terminals which support only 16 colors
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HeaderStyle {
+    pub fg: Option<(u8, u8, u8)>,
+    pub bg: Option<(u8, u8, u8)>,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+impl Default for HeaderStyle {
+    fn default() -> Self {
+        Self {
+            fg: None,
+            bg: None,
+            bold: true,
+            underline: false,
+        }
+    }
+}
+
+// Horizontal alignment of the file path in the header of each file
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum HeaderAlign {
+    #[default]
+    Left,
+    // The path and the notes after it are aligned to the right edge of the terminal
+    Right,
+}
+
+// Border drawn around the output of each file
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum BorderStyle {
+    #[default]
+    None,
+    // Enclose each file in a box with rounded corners like a card. It is useful for screenshots. The box takes two
+    // columns of the terminal for its left and right sides
+    Rounded,
+}
+
+// Which part of each file is printed
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum FirstOnly {
+    #[default]
+    None,
+    // Only the first code snippet per file
+    Chunk,
+    // Only the first matched line with its context per file. Other matched lines in the context are not highlighted
+    Match,
+}
+
+// Which lines are painted with the background color of matched lines
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum MatchEmphasis {
+    // Only matched lines
+    #[default]
+    MatchedOnly,
+    // All lines in the chunks including context lines
+    WholeChunk,
+    // No line. Matched regions and line numbers of matched lines are still highlighted
+    None,
+}
+
+// Font style of syntax highlighting which can be disabled for terminals rendering it poorly. Italic is never emitted to
+// terminals, but it is used by the SVG and RTF printers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FontStyleKind {
+    Bold,
+    Italic,
+    Underline,
+}
+
+// How line numbers in the gutter are counted
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Numbering {
+    // Line numbers in the file
+    #[default]
+    Absolute,
+    // Line numbers counted from 1 at the start of each chunk. This is useful to share snippets
+    ChunkLocal,
+}
+
+// Line numbers listed in the header of each file like `(lines 12,40-42)`. Consecutive line numbers are coalesced into
+// ranges
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum HeaderLines {
+    // Nothing is listed
+    #[default]
+    None,
+    // Line numbers of matched lines
+    Matches,
+    // Line ranges of chunks
+    Chunks,
+}
+
+// How to draw byte sequences which are invalid as UTF-8
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InvalidUtf8 {
+    // Replace each invalid sequence with the character like `String::from_utf8_lossy` does with U+FFFD
+    Replace(char),
+    // Draw each invalid byte as an escape like `\xff` in a dim color
+    Escape,
+}
+
+impl Default for InvalidUtf8 {
+    fn default() -> Self {
+        InvalidUtf8::Replace(char::REPLACEMENT_CHARACTER)
+    }
+}
+
 pub struct PrinterOptions<'main> {
+    // Tab characters are expanded to this number of spaces. 0 means hard tabs, which are written as-is and advance the
+    // cursor to the next tab stop of the terminal (every 8 columns). Their widths are still counted for text-wrapping
     pub tab_width: usize,
+    // When true, an expanded tab advances to the next multiple of `tab_width` columns like text editors do, instead of
+    // always inserting `tab_width` spaces
+    pub align_tabs: bool,
     pub theme: Option<&'main str>,
+    // Theme pair to switch the theme depending on the terminal background. A theme in the pair is preferred over `theme`
+    pub theme_light: Option<&'main str>,
+    pub theme_dark: Option<&'main str>,
+    // Mode to choose a theme from the pair. When it is `None`, the mode is detected from the environment
+    pub theme_mode: Option<ThemeMode>,
+    // Themes tried in order when the theme chosen from the above options is not available. It is not an error that some
+    // of them are not available. This is useful for sharing configurations across machines with different custom themes
+    pub theme_fallbacks: Vec<&'main str>,
+    // Theme used instead of the built-in default when no theme is chosen from the above options. Unlike them, an unknown
+    // theme is not an error. The built-in default is used and the unknown theme is reported by
+    // `SyntectPrinter::unknown_default_theme`. Only syntect printer uses this
+    pub default_theme: Option<&'main str>,
     pub grid: bool,
     pub background_color: bool,
     pub color_support: TermColorSupport,
     pub term_width: u16,
     pub custom_assets: bool,
     pub text_wrap: TextWrapMode,
-    pub first_only: bool,
+    pub first_only: FirstOnly,
+    // Skip blank context lines at the start and the end of each chunk. Matched lines are always printed
+    pub trim_chunk_blanks: bool,
     pub ascii_lines: bool,
+    pub search_info: Option<SearchInfo>,
+    // Underline matched regions with the RGB color instead of painting them with the region colors of the theme. The
+    // color of underline falls back to the foreground color on terminals which support only 16 colors
+    pub match_underline: Option<(u8, u8, u8)>,
+    // Pairs of a glob pattern and a syntax name. When the file name or the path matches the glob, the syntax is used.
+    // They are preferred over the builtin detection
+    pub syntax_mappings: Vec<(&'main str, &'main str)>,
+    // Name or file extension of the language to highlight all files with regardless of their paths like `bat -l`. The
+    // language of `File::language` is still preferred
+    pub language: Option<&'main str>,
+    // Print only the rows in the window of each file. The rows include header and footer lines and are counted after
+    // text-wrapping. `window_height` is the number of rows and `None` means no limit
+    pub scroll_offset: usize,
+    pub window_height: Option<usize>,
+    // Shift each line left by the number of cells to pan across wide lines. When a wide character or a tab lies across
+    // the left edge, its visible cells are rendered as spaces
+    pub h_scroll: usize,
+    // When true, a file which is not valid UTF-8 is not rendered and only its header is printed with the reason.
+    // Otherwise invalid byte sequences are replaced with U+FFFD
+    pub strict_utf8: bool,
+    // When false, a file containing a NUL byte in its first bytes is considered binary. It is not rendered and only its
+    // header is printed with a notice
+    pub binary_as_text: bool,
+    // Show the number of lines skipped between two chunks in the separator line
+    pub separator_gap: bool,
+    pub header_style: HeaderStyle,
+    // Wrap each line number in an OSC 8 hyperlink to `file://{path}#L{lnum}`. Callers should enable this only when the
+    // output is a terminal
+    pub hyperlinks: bool,
+    // Draw vertical guides at each indentation level (every `tab_width` columns) in leading whitespaces of lines. Guides
+    // replace spaces so they don't change the width of lines
+    pub indent_guides: bool,
+    // Show a tiny bar after the file path in the header. Its length grows with the number of matched lines in log scale
+    pub match_sparkline: bool,
+    // When text-wrapping is disabled and a matched line overflows the terminal, print the whole line again on the
+    // following faint rows so that its contents are not lost
+    pub overflow_tooltip: bool,
+    // Color brackets (), [] and {} by their nesting depth in each chunk. Brackets in string literals or comments are not
+    // colored. Colors of matched regions are preferred over them
+    pub bracket_colors: bool,
+    // Pad the file path in the header with spaces to this width so that the sparkline and notes after the path are
+    // aligned across files. A longer path is not truncated
+    pub header_path_width: Option<usize>,
+    // How to draw bytes in the file which are not decodable as UTF-8
+    pub invalid_utf8: InvalidUtf8,
+    // Never paint any background color so that a background image or transparency of the terminal is visible. Matched
+    // lines are indicated only by their line numbers and matched regions are underlined instead. This takes precedence
+    // over `background_color`
+    pub transparent: bool,
+    // Write tabs in string literals and comments as-is even when `tab_width` is not 0 so that tab-separated data in them
+    // is aligned by the terminal's tab stops. Other tabs such as indentation are expanded. Whether a tab is in a string
+    // or a comment is a heuristic based on the 'string' and 'comment' scopes given by the syntax definition
+    pub literal_tabs_in_strings: bool,
+    // Alignment of the file path in the header. When the path is right-aligned, the sparkline and notes follow it
+    pub header_align: HeaderAlign,
+    // When text-wrapping a line needs more than this number of wrapped rows, the rest of the line is truncated and a
+    // notice with the number of truncated bytes is drawn instead. This caps the output of single huge lines such as
+    // minified code. `None` means unlimited
+    pub auto_truncate_after: Option<usize>,
+    // Draw the dimmed line number again in the gutter of each row wrapped from a line instead of blank so that copied
+    // rows keep their line numbers
+    pub continuation_line_numbers: bool,
+    // Reserve a narrow column before line numbers and draw a marker (▶, or > with `ascii_lines`) in it on matched lines.
+    // This is a lightweight alternative to painting the background of matched lines
+    pub match_indicator: bool,
+    // When false, code is not syntax-highlighted and drawn in the default foreground color of the theme. Lines are not
+    // parsed at all so this is faster. Gutters, grids and matched regions are still drawn
+    pub highlight: bool,
+    // RGB color of "..." in the separator lines between chunks. `None` means the color of the gutter. The color is
+    // ignored on terminals which support only 16 colors
+    pub separator_color: Option<(u8, u8, u8)>,
+    pub border: BorderStyle,
+    // Always reserve the width of "..." in the gutter even when a file has only one chunk and no separator is drawn.
+    // This aligns gutters across files when printing many small files
+    pub reserve_separator_gutter: bool,
+    // Draw separators also before the first chunk and after the last chunk when lines at the start or the end of the
+    // file are not printed. No separator is drawn at a file boundary, for example when a line at the top of the file
+    // matched
+    pub edge_separators: bool,
+    // RGB background colors of matched regions indexed by the search pattern of each region (see
+    // `LineMatch::with_patterns`). Regions of the patterns without colors are painted with the color of the theme. The
+    // colors are ignored on terminals which support only 16 colors. When regions are underlined, they are the colors of
+    // the underlines
+    pub pattern_colors: Vec<(u8, u8, u8)>,
+    // Print an index which lists each file and its number of matched lines before printing the files. It is printed only
+    // when the files are printed at once with `Printer::print_all`
+    pub file_index: bool,
+    pub match_emphasis: MatchEmphasis,
+    // Time budget to highlight each file. When highlighting the file takes longer than this, the rest of the file is
+    // drawn as plain text with a notice. This bounds the time for pathological inputs such as huge lines or syntaxes with
+    // slow regular expressions. `None` means unlimited
+    pub highlight_budget: Option<Duration>,
+    // Show the total number of lines of the file in the header like "(5000 lines)"
+    pub show_line_count: bool,
+    // RGB foreground color of the text in files which are not highlighted because no syntax was found for them. This
+    // tells at a glance that the files were drawn as plain text. `None` means the foreground color of the theme. The
+    // color is ignored on terminals which support only 16 colors
+    pub plain_text_fg: Option<(u8, u8, u8)>,
+    // RGB background color of matched regions instead of the region color of the theme. The color is ignored on
+    // terminals which support only 16 colors
+    pub match_color: Option<(u8, u8, u8)>,
+    // Dim the text of matched lines except for the matched regions so that the regions stand out in long lines
+    pub focus_match: bool,
+    // Character drawn in dim style at the end of each line like `¶` or `$` to reveal trailing whitespaces and line
+    // endings. It is drawn only at the true end of a line, not on wrapped rows. `None` means no marker
+    pub show_eol: Option<char>,
+    pub numbering: Numbering,
+    // Terminate the last row of the output with a newline. Disabling this is useful to embed the output in another text
+    // without an extra empty line. Rows of multiple files are still separated by newlines
+    pub trailing_newline: bool,
+    // Minimum width of the text next to the gutter. When the terminal is narrower than the gutter plus this width, line
+    // numbers are drawn on their own rows above the lines and the gutter shrinks (stacked layout) to keep the output
+    // readable on tiny panes. `None` disables the stacked layout
+    pub min_text_width: Option<u16>,
+    // Collapse consecutive identical lines in a chunk into one row with a `(×N)` suffix. The gutter shows the range of
+    // the collapsed line numbers. Matched lines are collapsed only with matched lines
+    pub collapse_repeats: bool,
+    // Look for the themes and syntaxes which users installed for bat in bat's config directory (`$BAT_CONFIG_DIR` or
+    // `~/.config/bat`) and cache directory (`$BAT_CACHE_PATH` or `~/.cache/bat`). Themes are looked for only when they
+    // are not bundled
+    pub bat_assets: bool,
+    pub header_lines: HeaderLines,
+    // Draw only the gutter with line numbers for each line without the text. Headers, footers, borders, separators and
+    // notices are not drawn and lines are not collapsed so that each row corresponds to one line. The rows can be pasted
+    // next to the code copied from somewhere else
+    pub gutter_only: bool,
+    // Indent the continuation rows of wrapped lines to the first non-whitespace column of the lines like soft-wrap of
+    // editors. The indentation is limited to half of the text width
+    pub hanging_indent: bool,
+    // Minimum number of columns of line numbers in gutter. This keeps gutters of multiple files aligned regardless of
+    // the largest line number of each file. `None` means the width is computed from the line numbers
+    pub min_lnum_width: Option<u16>,
+    // When false, neither matched lines nor matched regions are painted. Line numbers of matched lines are still
+    // highlighted so that matches can be located
+    pub highlight_matches: bool,
+    // Align the fields of delimiter-separated lines like TSV or CSV into columns. The width of each column is the widest
+    // field in the chunk. Delimiters in double-quoted fields are ignored
+    pub tabular: bool,
+    // Delimiter of fields for `tabular`. `None` means ',' for `.csv` files and a tab for other files
+    pub delimiter: Option<char>,
+    // Total width of the output including separators, headers and wrapped lines. `None` means `term_width`. Setting
+    // this makes the output independent of the terminal window
+    pub output_width: Option<u16>,
+    // Paint line numbers of matched lines and the gutter in the same color as line numbers of context lines
+    pub plain_gutter: bool,
+    // Print a form feed character between files so that each file starts on a new page when printed on paper
+    pub form_feed: bool,
+    // Font styles of themes which are never emitted. Bold of headers and underlines of matched regions are not affected
+    pub disabled_font_styles: Vec<FontStyleKind>,
 }
 
 impl<'main> Default for PrinterOptions<'main> {
@@ -57,20 +373,392 @@ impl<'main> Default for PrinterOptions<'main> {
         use terminal_size::{terminal_size, Width};
         Self {
             tab_width: 4,
+            align_tabs: false,
             theme: None,
+            theme_light: None,
+            theme_dark: None,
+            theme_mode: None,
+            theme_fallbacks: vec![],
+            default_theme: None,
             grid: true,
             background_color: false,
             color_support: TermColorSupport::detect(),
             custom_assets: false,
             term_width: terminal_size().map(|(Width(w), _)| w).unwrap_or(80), // Note: `tput` returns 80 when tty is not found
             text_wrap: TextWrapMode::Char,
-            first_only: false,
+            first_only: FirstOnly::None,
+            trim_chunk_blanks: false,
             ascii_lines: false,
+            search_info: None,
+            match_underline: None,
+            syntax_mappings: vec![],
+            language: None,
+            scroll_offset: 0,
+            window_height: None,
+            h_scroll: 0,
+            strict_utf8: false,
+            binary_as_text: false,
+            separator_gap: false,
+            header_style: HeaderStyle::default(),
+            hyperlinks: false,
+            indent_guides: false,
+            match_sparkline: false,
+            overflow_tooltip: false,
+            bracket_colors: false,
+            header_path_width: None,
+            invalid_utf8: InvalidUtf8::default(),
+            transparent: false,
+            literal_tabs_in_strings: false,
+            header_align: HeaderAlign::default(),
+            auto_truncate_after: None,
+            continuation_line_numbers: false,
+            match_indicator: false,
+            highlight: true,
+            separator_color: None,
+            border: BorderStyle::default(),
+            reserve_separator_gutter: false,
+            edge_separators: false,
+            pattern_colors: vec![],
+            file_index: false,
+            match_emphasis: MatchEmphasis::default(),
+            highlight_budget: None,
+            show_line_count: false,
+            plain_text_fg: None,
+            match_color: None,
+            focus_match: false,
+            show_eol: None,
+            numbering: Numbering::default(),
+            trailing_newline: true,
+            min_text_width: Some(10),
+            collapse_repeats: false,
+            bat_assets: true,
+            header_lines: HeaderLines::default(),
+            gutter_only: false,
+            hanging_indent: false,
+            min_lnum_width: None,
+            highlight_matches: true,
+            tabular: false,
+            delimiter: None,
+            output_width: None,
+            plain_gutter: false,
+            form_feed: false,
+            disabled_font_styles: vec![],
+        }
+    }
+}
+
+impl<'main> PrinterOptions<'main> {
+    // Name of the theme to use considering the light/dark theme pair
+    pub fn theme_name(&self) -> Option<&'main str> {
+        if self.theme_light.is_none() && self.theme_dark.is_none() {
+            return self.theme;
         }
+        let mode = self
+            .theme_mode
+            .or_else(ThemeMode::detect)
+            .unwrap_or(ThemeMode::Dark); // Most terminals have dark background by default
+        let name = match mode {
+            ThemeMode::Light => self.theme_light,
+            ThemeMode::Dark => self.theme_dark,
+        };
+        name.or(self.theme)
+    }
+
+    // All theme names specified in the options
+    pub fn theme_names(&self) -> impl Iterator<Item = &'main str> {
+        [self.theme, self.theme_light, self.theme_dark]
+            .into_iter()
+            .flatten()
     }
 }
 
+// Order of files in a batch of output
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum SortOrder {
+    // Print files in the order they are found. Each file is printed as soon as it is searched
+    #[default]
+    None,
+    // Sort files by their paths. Since all files need to be searched before sorting, nothing is printed until the
+    // search finishes
+    Path,
+    // Sort files by their paths in reverse order. The last file is printed first
+    PathReverse,
+}
+
 // Trait to replace printer implementation for unit tests
 pub trait Printer {
     fn print(&self, file: File) -> Result<()>;
+
+    // Print the batch of files in the sort order. Lines in each file are not reordered
+    fn print_all(&self, mut files: Vec<File>, sort: SortOrder) -> Result<()> {
+        sort_files(&mut files, sort);
+        files.into_iter().try_for_each(|f| self.print(f))
+    }
+}
+
+pub fn sort_files(files: &mut [File], sort: SortOrder) {
+    // Stable sort keeps the order of files of the same path
+    match sort {
+        SortOrder::None => {}
+        SortOrder::Path => files.sort_by(|a, b| a.path.cmp(&b.path)),
+        SortOrder::PathReverse => files.sort_by(|a, b| b.path.cmp(&a.path)),
+    }
+}
+
+const ARCHIVE_EXTENSIONS: &[&str] = &[
+    ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz", ".tar.zst", ".zip", ".jar",
+];
+
+// Path of file in archive is represented as "{archive}!{member}" like "foo.tar!src/lib.rs". This function returns the
+// member's path of the virtual path.
+pub(crate) fn archive_member_path(path: &Path) -> Option<&Path> {
+    let path = path.to_str()?;
+    path.match_indices('!').find_map(|(idx, _)| {
+        let (archive, member) = (path[..idx].to_ascii_lowercase(), &path[idx + 1..]);
+        let is_archive = ARCHIVE_EXTENSIONS
+            .iter()
+            .any(|ext| archive.len() > ext.len() && archive.ends_with(ext));
+        (is_archive && !member.is_empty()).then(|| Path::new(member))
+    })
+}
+
+// File at a revision of version control system is represented as "{path}@{rev}" like "src/lib.rs@HEAD~1". Its contents
+// come from e.g. `git show {rev}:{path}`. This function returns the real path without the revision. To avoid stripping
+// '@' in file names like "icon@2x.png", a revision containing '.' is accepted only when the real file name has extension
+// and a path is not split when it exists on file system. A relative path is resolved with `cwd` to check the existence.
+pub(crate) fn revision_path<'a>(path: &'a Path, cwd: Option<&Path>) -> Option<&'a Path> {
+    let s = path.to_str()?;
+    let name_start = s.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    let idx = name_start + s[name_start..].rfind('@')?;
+    let (name, rev) = (&s[name_start..idx], &s[idx + 1..]);
+    if name.is_empty() || rev.is_empty() {
+        return None;
+    }
+    let has_extension = name.rfind('.').map(|i| i > 0).unwrap_or(false);
+    if rev.contains('.') && !has_extension {
+        return None;
+    }
+    // Only paths which look like files at some revision are checked so that printing each file doesn't need extra `stat`
+    let exists = if path.is_absolute() {
+        path.exists()
+    } else {
+        cwd.is_some_and(|cwd| cwd.join(path).exists())
+    };
+    (!exists).then(|| Path::new(&s[..idx]))
+}
+
+// URL of the file for OSC 8 hyperlinks. Files which don't exist on file system such as archive members or files at some
+// revision have no URL. A relative path is resolved with `cwd`
+pub(crate) fn file_url(path: &Path, cwd: Option<&Path>) -> Option<String> {
+    if archive_member_path(path).is_some() || revision_path(path, cwd).is_some() {
+        return None;
+    }
+    let path: PathBuf = if path.is_absolute() {
+        path.components().collect()
+    } else {
+        cwd?.join(path)
+            .components()
+            .filter(|c| *c != std::path::Component::CurDir)
+            .collect()
+    };
+    let path = path.to_str()?;
+
+    let mut url = String::from("file://");
+    if !path.starts_with('/') {
+        url.push('/'); // e.g. C:\foo => file:///C:/foo
+    }
+    for b in path.bytes() {
+        match b {
+            b'\\' => url.push('/'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                url.push(b as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    Some(url)
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::LineMatch;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_print_all_sort_order() {
+        struct PathPrinter(Mutex<Vec<String>>);
+        impl Printer for PathPrinter {
+            fn print(&self, file: File) -> Result<()> {
+                let lnum = file.line_matches[0].line_number;
+                let printed = format!("{}:{}", file.path.display(), lnum);
+                self.0.lock().unwrap().push(printed);
+                Ok(())
+            }
+        }
+
+        let file = |path: &str, lnum| {
+            File::new(
+                PathBuf::from(path),
+                vec![LineMatch::lnum(lnum)],
+                vec![(lnum, lnum)],
+                vec![],
+            )
+        };
+        let tests = [
+            (SortOrder::None, ["b:1", "a:1", "c:1", "a:2"]),
+            (SortOrder::Path, ["a:1", "a:2", "b:1", "c:1"]),
+            (SortOrder::PathReverse, ["c:1", "b:1", "a:1", "a:2"]),
+        ];
+        for (sort, want) in tests {
+            let files = vec![file("b", 1), file("a", 1), file("c", 1), file("a", 2)];
+            let printer = PathPrinter(Mutex::new(vec![]));
+            printer.print_all(files, sort).unwrap();
+            assert_eq!(printer.0.into_inner().unwrap(), want, "{:?}", sort);
+        }
+    }
+
+    #[test]
+    fn test_theme_mode_from_colorfgbg() {
+        let tests = [
+            ("15;0", Some(ThemeMode::Dark)),
+            ("0;15", Some(ThemeMode::Light)),
+            ("0;7", Some(ThemeMode::Light)),
+            ("7;8", Some(ThemeMode::Dark)),
+            ("default;default;0", Some(ThemeMode::Dark)),
+            ("15;default", None),
+            ("", None),
+        ];
+        for (input, want) in tests {
+            assert_eq!(ThemeMode::from_colorfgbg(input), want, "input={:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_theme_name() {
+        let opts = PrinterOptions {
+            theme: Some("Nord"),
+            ..Default::default()
+        };
+        assert_eq!(opts.theme_name(), Some("Nord"));
+
+        let opts = PrinterOptions {
+            theme: Some("Nord"),
+            theme_light: Some("GitHub"),
+            theme_dark: Some("Dracula"),
+            theme_mode: Some(ThemeMode::Light),
+            ..Default::default()
+        };
+        assert_eq!(opts.theme_name(), Some("GitHub"));
+
+        let opts = PrinterOptions {
+            theme_mode: Some(ThemeMode::Dark),
+            ..opts
+        };
+        assert_eq!(opts.theme_name(), Some("Dracula"));
+
+        let opts = PrinterOptions {
+            theme: Some("Nord"),
+            theme_light: Some("GitHub"),
+            theme_mode: Some(ThemeMode::Dark),
+            ..Default::default()
+        };
+        assert_eq!(opts.theme_name(), Some("Nord"));
+
+        let names: Vec<_> = opts.theme_names().collect();
+        assert_eq!(names, ["Nord", "GitHub"]);
+    }
+
+    #[test]
+    fn test_archive_member_path() {
+        let tests = [
+            ("foo.tar!src/lib.rs", Some("src/lib.rs")),
+            ("path/to/foo.tar.gz!Makefile", Some("Makefile")),
+            ("FOO.ZIP!a!b.txt", Some("a!b.txt")),
+            ("foo.tar!", None),
+            (".tar!foo.rs", None),
+            ("foo!bar.rs", None),
+            ("src/lib.rs", None),
+        ];
+        for (input, want) in tests {
+            let have = archive_member_path(Path::new(input));
+            assert_eq!(have, want.map(Path::new), "input={:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_revision_path() {
+        let tests = [
+            ("src/lib.rs@HEAD~1", Some("src/lib.rs")),
+            ("Makefile@main", Some("Makefile")),
+            ("lib.rs@v1.2.0", Some("lib.rs")),
+            ("foo@bar/lib.rs@abc123", Some("foo@bar/lib.rs")),
+            ("icon@2x.png", None),
+            ("@types/node", None),
+            ("lib.rs@", None),
+            ("src/lib.rs", None),
+        ];
+        for (input, want) in tests {
+            let have = revision_path(Path::new(input), None);
+            assert_eq!(have, want.map(Path::new), "input={:?}", input);
+        }
+
+        // Real file whose name contains '@' is not a file at some revision
+        let dir = std::env::temp_dir().join(format!("hgrep-test-revision-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.md@v1.0");
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(revision_path(&file, None), None);
+        assert!(file_url(&file, None).is_some());
+        let missing = dir.join("lib.rs@v1.0");
+        let want = dir.join("lib.rs");
+        assert_eq!(revision_path(&missing, None), Some(want.as_path()));
+        // A relative path is resolved with the given directory instead of the current directory
+        let relative = Path::new("notes.md@v1.0");
+        assert_eq!(revision_path(relative, Some(&dir)), None);
+        assert_eq!(revision_path(relative, None), Some(Path::new("notes.md")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_url() {
+        let cwd = std::env::current_dir().unwrap();
+        let url = file_url(Path::new("./src/lib.rs"), Some(&cwd)).unwrap();
+        assert_eq!(file_url(&cwd.join("src/lib.rs"), None).unwrap(), url);
+        assert!(url.starts_with("file:///"), "{:?}", url);
+        assert!(url.ends_with("/src/lib.rs"), "{:?}", url);
+        assert!(!url.contains("/./"), "{:?}", url);
+        assert_eq!(file_url(Path::new("src/lib.rs"), None), None);
+        #[cfg(not(windows))]
+        {
+            assert_eq!(
+                file_url(Path::new("/path/to/a b/\u{3042}.rs"), None).unwrap(),
+                "file:///path/to/a%20b/%E3%81%82.rs",
+            );
+            assert_eq!(
+                file_url(Path::new("./src/lib.rs"), Some(Path::new("/work"))).unwrap(),
+                "file:///work/src/lib.rs",
+            );
+        }
+        assert_eq!(file_url(Path::new("foo.tar!src/lib.rs"), Some(&cwd)), None);
+        assert_eq!(file_url(Path::new("src/lib.rs@HEAD"), Some(&cwd)), None);
+    }
 }
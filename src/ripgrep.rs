@@ -1,6 +1,6 @@
-use crate::chunk::Files;
+use crate::chunk::{ContextPercent, File as ChunkFile, Files, LineMatch};
 use crate::grep::GrepMatch;
-use crate::printer::Printer;
+use crate::printer::{Printer, SortOrder};
 use anyhow::{Context, Result};
 use grep_matcher::{LineTerminator, Matcher};
 use grep_pcre2::{RegexMatcher as Pcre2Matcher, RegexMatcherBuilder as Pcre2MatcherBuilder};
@@ -43,6 +43,7 @@ fn parse_size(input: &str) -> Result<u64> {
 pub struct Config<'main> {
     min_context: u64,
     max_context: u64,
+    context_percent: Option<ContextPercent>,
     no_ignore: bool,
     hidden: bool,
     case_insensitive: bool,
@@ -68,6 +69,10 @@ pub struct Config<'main> {
     no_unicode: bool,
     regex_size_limit: Option<usize>,
     dfa_size_limit: Option<usize>,
+    text: bool,
+    sort: SortOrder,
+    batch: bool,
+    highlight_all: bool,
 }
 
 impl<'main> Config<'main> {
@@ -87,6 +92,12 @@ impl<'main> Config<'main> {
         self
     }
 
+    // See `Files::context_percent`
+    pub fn context_percent(&mut self, percent: ContextPercent) -> &mut Self {
+        self.context_percent = Some(percent);
+        self
+    }
+
     pub fn no_ignore(&mut self, yes: bool) -> &mut Self {
         self.no_ignore = yes;
         self
@@ -113,6 +124,17 @@ impl<'main> Config<'main> {
         self
     }
 
+    /// Whether the pattern is searched case insensitively with this configuration. With smart case, the pattern is
+    /// searched case insensitively when it has no uppercase literal like ripgrep. Escape sequences such as `\W` and
+    /// `\p{Lu}` are not literals.
+    pub fn is_case_insensitive(&self, pattern: &str) -> bool {
+        if self.smart_case {
+            !has_uppercase_literal(pattern, self.fixed_strings)
+        } else {
+            self.case_insensitive
+        }
+    }
+
     pub fn globs(&mut self, globs: impl Iterator<Item = &'main str>) -> &mut Self {
         self.globs = globs.collect();
         self
@@ -187,6 +209,24 @@ impl<'main> Config<'main> {
         self
     }
 
+    pub fn sort(&mut self, order: SortOrder) -> &mut Self {
+        self.sort = order;
+        self
+    }
+
+    // Look for the occurrences of the pattern also in context lines so that printers can highlight them. This is useful
+    // when some lines containing the pattern are not matched lines such as with `invert_match` or `max_count`
+    pub fn highlight_all(&mut self, yes: bool) -> &mut Self {
+        self.highlight_all = yes;
+        self
+    }
+
+    // Print all files at once with `Printer::print_all` after the search finishes even if they are not sorted
+    pub fn batch(&mut self, yes: bool) -> &mut Self {
+        self.batch = yes;
+        self
+    }
+
     pub fn types(&mut self, types: impl Iterator<Item = &'main str>) -> &mut Self {
         self.types = types.collect();
         self
@@ -212,6 +252,11 @@ impl<'main> Config<'main> {
         self
     }
 
+    pub fn text(&mut self, yes: bool) -> &mut Self {
+        self.text = yes;
+        self
+    }
+
     pub fn no_unicode(&mut self, yes: bool) -> &mut Self {
         self.no_unicode = yes;
         self
@@ -344,8 +389,13 @@ impl<'main> Config<'main> {
         } else {
             MmapChoice::never()
         };
+        let binary = if self.text {
+            BinaryDetection::none()
+        } else {
+            BinaryDetection::quit(0)
+        };
         builder
-            .binary_detection(BinaryDetection::quit(0))
+            .binary_detection(binary)
             .line_number(true)
             .multi_line(self.multiline)
             .memory_map(mmap)
@@ -509,17 +559,15 @@ impl<'a, M: Matcher> Sink for Matches<'a, M> {
                 ranges.push((m.start(), m.end()));
                 true
             })
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))?;
+            .map_err(|e| io::Error::other(format!("{}", e)))?;
         let mut regions = LineRegions::new(&ranges);
 
-        let mut line_number = line_number;
-        for line in mat.lines() {
+        for (line_number, line) in (line_number..).zip(mat.lines()) {
             self.buf.push(GrepMatch {
                 path: path.to_owned(),
                 line_number,
                 ranges: regions.line_ranges(line.len()),
             });
-            line_number += 1;
         }
 
         Ok(true)
@@ -585,11 +633,40 @@ where
         Ok(Some(matches.buf))
     }
 
+    // Find the occurrences of the pattern in the context lines of the file. See `Config::highlight_all`
+    fn find_in_context(&self, file: ChunkFile) -> Result<ChunkFile> {
+        if !self.config.highlight_all {
+            return Ok(file);
+        }
+        let mut found = vec![];
+        for line in file.chunk_lines() {
+            if line.line_match.is_some() {
+                continue;
+            }
+            let bytes = line.bytes.strip_suffix(b"\n").unwrap_or(line.bytes);
+            let mut ranges = vec![];
+            self.matcher
+                .find_iter(bytes, |m| {
+                    if !m.is_empty() {
+                        ranges.push((m.start(), m.end()));
+                    }
+                    true
+                })
+                .map_err(|e| io::Error::other(format!("{}", e)))?;
+            if !ranges.is_empty() {
+                found.push(LineMatch::new(line.line_number, ranges));
+            }
+        }
+        Ok(file.with_context_regions(found))
+    }
+
     fn print_matches(&self, matches: Vec<GrepMatch>) -> Result<bool> {
         let (min, max) = (self.config.min_context, self.config.max_context);
         let mut found = false;
-        for file in Files::new(matches.into_iter().map(Ok), min, max) {
-            self.printer.print(file?)?;
+        let files = Files::new(matches.into_iter().map(Ok), min, max)
+            .context_percent(self.config.context_percent);
+        for file in files {
+            self.printer.print(self.find_in_context(file?)?)?;
             found = true;
         }
         Ok(found)
@@ -599,6 +676,9 @@ where
     where
         I: Iterator<Item = Result<PathBuf>> + Send,
     {
+        if self.config.sort != SortOrder::None || self.config.batch {
+            return self.grep_batch(paths);
+        }
         paths
             .par_bridge()
             .filter_map(|path| match path {
@@ -608,6 +688,62 @@ where
             .map(|matches| self.print_matches(matches?))
             .try_reduce(|| false, |a, b| Ok(a || b))
     }
+
+    // Search files in parallel and print all of them at once in the sort order
+    fn grep_batch<I>(&self, paths: I) -> Result<bool>
+    where
+        I: Iterator<Item = Result<PathBuf>> + Send,
+    {
+        let (min, max) = (self.config.min_context, self.config.max_context);
+        let files = paths
+            .par_bridge()
+            .filter_map(|path| match path {
+                Ok(path) => self.search(path).transpose(),
+                Err(err) => Some(Err(err)),
+            })
+            .map(|matches| {
+                Files::new(matches?.into_iter().map(Ok), min, max)
+                    .context_percent(self.config.context_percent)
+                    .map(|file| self.find_in_context(file?))
+                    .collect()
+            })
+            .collect::<Result<Vec<Vec<_>>>>()?;
+        let files: Vec<_> = files.into_iter().flatten().collect();
+        let found = !files.is_empty();
+        self.printer.print_all(files, self.config.sort)?;
+        Ok(found)
+    }
+}
+
+// Character after a backslash is a part of an escape sequence. Braces of escapes like `\p{Greek}` or `\x{41}` and names
+// of capture groups like `(?P<Name>...)` are skipped as well
+fn has_uppercase_literal(pattern: &str, fixed_strings: bool) -> bool {
+    if fixed_strings {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+                if chars.peek() == Some(&'{') {
+                    chars.find(|&c| c == '}');
+                }
+            }
+            '(' if chars.peek() == Some(&'?') => {
+                chars.next();
+                if chars.peek() == Some(&'P') {
+                    chars.next();
+                }
+                if chars.peek() == Some(&'<') {
+                    chars.find(|&c| c == '>');
+                }
+            }
+            c if c.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+    false
 }
 
 #[cfg(test)]
@@ -655,6 +791,13 @@ mod tests {
                 }
             }
         }
+
+        // Files are printed in parallel so their order is not stable. Sort them to compare with expected results
+        fn into_sorted_files(self) -> Vec<File> {
+            let mut files = self.0.into_inner().unwrap();
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            files
+        }
     }
 
     fn read_all_inputs(dir: &Path) -> Vec<String> {
@@ -708,8 +851,7 @@ mod tests {
 
         printer.validate_and_remove_region_ranges();
 
-        let mut got = printer.0.into_inner().unwrap();
-        got.sort_by(|a, b| a.path.cmp(&b.path));
+        let got = printer.into_sorted_files();
 
         let mut expected = read_all_expected_chunks(&dir, &inputs);
         expected.sort_by(|a, b| a.path.cmp(&b.path));
@@ -718,6 +860,39 @@ mod tests {
         assert_eq!(expected, got);
     }
 
+    #[test]
+    fn test_grep_sorted_by_path() {
+        let dir = Path::new("testdata").join("chunk");
+        let mut inputs = read_all_inputs(&dir);
+        inputs.sort_unstable();
+        inputs.reverse();
+
+        let printer = DummyPrinter::default();
+        let paths = inputs
+            .iter()
+            .map(|s| dir.join(format!("{}.in", s)).into_os_string())
+            .collect::<Vec<_>>();
+        let paths = paths.iter().map(AsRef::as_ref);
+        let mut config = Config::new(3, 6);
+        config.sort(SortOrder::Path);
+
+        let found = grep(&printer, r"\*$", Some(paths), config).unwrap();
+        assert!(found);
+
+        // Files are printed in the order of paths without sorting them after printing
+        let got: Vec<_> = printer
+            .0
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        let mut want = got.clone();
+        want.sort();
+        assert!(got.len() > 1, "{:?}", got);
+        assert_eq!(got, want);
+    }
+
     #[test]
     fn test_grep_no_match_found() {
         let path = Path::new("testdata").join("chunk").join("single_max.in");
@@ -763,6 +938,34 @@ mod tests {
         assert_eq!(msg, "dummy error");
     }
 
+    #[test]
+    fn test_smart_case() {
+        let tests = [
+            ("foo", false, true),
+            ("Foo", false, false),
+            (r"\W+", false, true),
+            (r"\S\D\B", false, true),
+            (r"\p{Lu}", false, true),
+            (r"\P{Greek}\x{41}", false, true),
+            (r"\\Foo", false, false),
+            (r"(?P<Name>foo)", false, true),
+            (r"(?<Name>foo)", false, true),
+            (r"(?i:foo)Bar", false, false),
+            (r"\W", true, false), // Fixed string
+        ];
+        for (pat, fixed, want) in tests {
+            let mut config = Config::default();
+            config.fixed_strings(fixed).smart_case(true);
+            assert_eq!(config.is_case_insensitive(pat), want, "pattern={:?}", pat);
+        }
+
+        let mut config = Config::default();
+        config.case_insensitive(true);
+        assert!(config.is_case_insensitive("Foo"));
+        config.case_insensitive(false);
+        assert!(!config.is_case_insensitive("foo"));
+    }
+
     #[test]
     fn test_print_types() {
         let config = Config::default();
@@ -812,10 +1015,7 @@ mod tests {
                 let line_number = s.next().unwrap().parse().unwrap();
                 let start = s.next().unwrap().parse().unwrap();
                 let end = s.next().unwrap().parse().unwrap();
-                line_matches.push(LineMatch {
-                    line_number,
-                    ranges: vec![(start, end)],
-                })
+                line_matches.push(LineMatch::new(line_number, vec![(start, end)]))
             }
         }
 
@@ -880,6 +1080,35 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_highlight_all() {
+        let path = Path::new("testdata")
+            .join("ripgrep")
+            .join("highlight_all.txt");
+        for highlight_all in [true, false] {
+            let printer = DummyPrinter::default();
+            let mut config = Config::new(1, 2);
+            config.invert_match(true).highlight_all(highlight_all);
+            let found = grep(&printer, "foo", Some(iter::once(path.as_path())), config).unwrap();
+            assert!(found);
+
+            let files = printer.0.into_inner().unwrap();
+            assert_eq!(files.len(), 1);
+            let file = &files[0];
+            let matched: Vec<_> = file.line_matches.iter().map(|m| m.line_number).collect();
+            assert_eq!(matched, [2, 4]);
+            let want = if highlight_all {
+                vec![
+                    LineMatch::new(1, vec![(0, 3)]),
+                    LineMatch::new(3, vec![(0, 3), (6, 9)]),
+                ]
+            } else {
+                vec![]
+            };
+            assert_eq!(file.context_regions(), &want[..]);
+        }
+    }
+
     macro_rules! line_regions_tests {
         {$(
             $name:ident(
@@ -0,0 +1,360 @@
+use crate::chunk::File;
+use crate::printer::{Printer, PrinterOptions, TermColorSupport};
+use crate::syntect::{
+    blend_fg_color, LineKind, Palette, RenderedRow, SyntectAssets, SyntectPrinter,
+};
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use syntect::highlighting::{Color, FontStyle};
+
+const FONT: &str = "Courier New";
+const FONT_SIZE: u32 = 20; // In half points
+
+/// Printer which prints each file as an RTF document. The document can be pasted into word processors or emails with
+/// the colors of the theme.
+///
+/// Lines are highlighted in the same way as [`SyntectPrinter`] with its options such as the theme. Colors of texts are
+/// put in the color table of the document and each run of text refers to them with `\cf` (foreground) and `\highlight`
+/// (background). Every file is printed as one complete RTF document, so print one file per output to get a valid
+/// document.
+pub struct RtfPrinter<'main, W: Write> {
+    out: Mutex<W>, // Protected with mutex because it should print file by file
+    renderer: SyntectPrinter<'main, io::Sink>,
+}
+
+impl<'main, W: Write> RtfPrinter<'main, W> {
+    pub fn new(out: W, opts: PrinterOptions<'main>) -> Result<Self> {
+        let renderer = SyntectPrinter::new(io::sink(), Self::rtf_options(opts))?;
+        Ok(Self {
+            out: Mutex::new(out),
+            renderer,
+        })
+    }
+
+    /// Create a printer with preloaded assets. This panics on the same conditions as [`SyntectPrinter::with_assets`].
+    pub fn with_assets(assets: SyntectAssets, out: W, opts: PrinterOptions<'main>) -> Self {
+        let renderer = SyntectPrinter::with_assets(assets, io::sink(), Self::rtf_options(opts));
+        Self {
+            out: Mutex::new(out),
+            renderer,
+        }
+    }
+
+    // Color table of RTF can have any RGB color regardless of the colors supported by the terminal
+    fn rtf_options(mut opts: PrinterOptions<'main>) -> PrinterOptions<'main> {
+        opts.color_support = TermColorSupport::True;
+        opts
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+
+    /// See [`SyntectPrinter::unknown_default_theme`].
+    pub fn unknown_default_theme(&self) -> Option<&'main str> {
+        self.renderer.unknown_default_theme()
+    }
+
+    // `None` means nothing is rendered for the file
+    fn render_rtf(&self, file: &File) -> Option<String> {
+        let (path, rows) = self.renderer.render_rows(file)?;
+
+        let palette = Palette::new(self.renderer.theme());
+        let background = palette.document_background();
+        let mut colors = ColorTable::default();
+        let mut color = |c: Color| {
+            let c = blend_fg_color(c, background);
+            colors.index(if c.a == 0xff { c } else { palette.foreground })
+        };
+
+        let lnum_width = rows
+            .iter()
+            .filter_map(|r| match r {
+                RenderedRow::Line { lnum, .. } => Some(lnum.to_string().len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        let mut body = String::new();
+        let _ = writeln!(
+            body,
+            "{{\\b\\cf{}\\highlight{} {}}}\\par",
+            color(palette.foreground),
+            color(background),
+            escape(&path.to_string_lossy()),
+        );
+        for row in &rows {
+            let (lnum, kind, spans) = match row {
+                RenderedRow::Line { lnum, kind, spans } => (*lnum, *kind, spans),
+                RenderedRow::Separator => {
+                    let _ = writeln!(
+                        body,
+                        "{{\\cf{}\\highlight{} {:>w$}}}\\par",
+                        color(palette.gutter_fg),
+                        color(background),
+                        "...",
+                        w = lnum_width + 1,
+                    );
+                    continue;
+                }
+                RenderedRow::Notice(notice) => {
+                    let _ = writeln!(
+                        body,
+                        "{{\\i\\cf{}\\highlight{} {}}}\\par",
+                        color(palette.gutter_fg),
+                        color(background),
+                        escape(notice),
+                    );
+                    continue;
+                }
+            };
+
+            let (line_bg, mark) = match kind {
+                LineKind::Context => (background, ' '),
+                LineKind::Matched => (palette.match_bg, ' '),
+                LineKind::Removed => (palette.removed_bg, '-'),
+                LineKind::Added => (palette.added_bg, '+'),
+            };
+            let lnum_fg = if kind == LineKind::Context {
+                palette.gutter_fg
+            } else {
+                palette.match_lnum_fg
+            };
+            let _ = write!(
+                body,
+                "{{\\cf{}\\highlight{} {}{:>w$}  }}",
+                color(lnum_fg),
+                color(line_bg),
+                mark,
+                lnum,
+                w = lnum_width,
+            );
+            for span in spans {
+                if span.text.is_empty() {
+                    continue;
+                }
+                let (fg, bg) = if span.in_match {
+                    (palette.region_fg, palette.region_bg)
+                } else {
+                    (span.style.foreground, line_bg)
+                };
+                let _ = write!(body, "{{\\cf{}\\highlight{}", color(fg), color(bg));
+                let font_style = span.style.font_style;
+                if font_style.contains(FontStyle::BOLD) {
+                    body.push_str("\\b");
+                }
+                if font_style.contains(FontStyle::ITALIC) {
+                    body.push_str("\\i");
+                }
+                if font_style.contains(FontStyle::UNDERLINE) {
+                    body.push_str("\\ul");
+                }
+                let _ = write!(body, " {}}}", escape(&span.text));
+            }
+            body.push_str("\\par\n");
+        }
+
+        let mut rtf = String::new();
+        let _ = writeln!(
+            rtf,
+            "{{\\rtf1\\ansi\\deff0{{\\fonttbl{{\\f0\\fmodern {};}}}}",
+            FONT
+        );
+        rtf.push_str("{\\colortbl;");
+        for c in &colors.0 {
+            let _ = write!(rtf, "\\red{}\\green{}\\blue{};", c.r, c.g, c.b);
+        }
+        rtf.push_str("}\n");
+        let _ = writeln!(rtf, "\\pard\\plain\\f0\\fs{}", FONT_SIZE);
+        rtf.push_str(&body);
+        rtf.push_str("}\n");
+        Some(rtf)
+    }
+}
+
+// Colors referred from the document. Index 0 of the color table is the default color so the index of the first color
+// is 1
+#[derive(Default)]
+struct ColorTable(Vec<Color>);
+
+impl ColorTable {
+    fn index(&mut self, c: Color) -> usize {
+        let c = Color { a: 0xff, ..c };
+        let idx = match self.0.iter().position(|&x| x == c) {
+            Some(idx) => idx,
+            None => {
+                self.0.push(c);
+                self.0.len() - 1
+            }
+        };
+        idx + 1
+    }
+}
+
+// RTF is written in 7-bit ASCII. Other characters are written with `\u` control words in UTF-16 code units with '?' as
+// the fallback for readers which don't support Unicode
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '{' => escaped.push_str("\\{"),
+            '}' => escaped.push_str("\\}"),
+            '\t' => escaped.push_str("\\tab "),
+            c if c.is_ascii_control() => escaped.push('?'),
+            c if c.is_ascii() => escaped.push(c),
+            c => {
+                let mut buf = [0; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    let _ = write!(escaped, "\\u{}?", *unit as i16); // The parameter is a signed 16-bit integer
+                }
+            }
+        }
+    }
+    escaped
+}
+
+impl<'main, W: Write> Printer for RtfPrinter<'main, W> {
+    fn print(&self, file: File) -> Result<()> {
+        use crate::io::IgnoreBrokenPipe;
+
+        let Some(rtf) = self.render_rtf(&file) else {
+            return Ok(());
+        };
+        let mut out = self.out.lock().unwrap();
+        out.write_all(rtf.as_bytes())
+            .and_then(|_| out.flush())
+            .ignore_broken_pipe()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::LineMatch;
+    use crate::printer::FontStyleKind;
+    use lazy_static::lazy_static;
+    use std::path::PathBuf;
+
+    lazy_static! {
+        static ref ASSETS: SyntectAssets = SyntectAssets::load(None).unwrap();
+    }
+
+    fn print_rtf(file: File) -> String {
+        print_rtf_with(file, PrinterOptions::default())
+    }
+
+    fn print_rtf_with(file: File, opts: PrinterOptions<'_>) -> String {
+        let printer = RtfPrinter::with_assets(ASSETS.clone(), vec![], opts);
+        printer.print(file).unwrap();
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_print_rtf() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::new(2, vec![(4, 7)])],
+            vec![(1, 3)],
+            b"fn main() {\n    foo(\"\\\\\");\n}\n".to_vec(),
+        );
+        let rtf = print_rtf(file);
+        assert!(rtf.starts_with("{\\rtf1\\ansi"), "{}", rtf);
+        assert!(rtf.ends_with("}\n"), "{}", rtf);
+        assert_eq!(rtf.matches("\\par\n").count(), 4, "{}", rtf); // Path and 3 lines
+        assert!(rtf.contains(" test.rs}\\par\n"), "{}", rtf);
+        assert!(rtf.contains(" 2  }"), "{}", rtf);
+        assert!(rtf.contains(" \\{}"), "{}", rtf);
+        assert!(rtf.contains("\\\\"), "{}", rtf);
+
+        // Every color in the table is referred and the matched region is painted with the region color of the theme
+        let table = rtf.lines().nth(1).unwrap();
+        let num_colors = table.matches("\\red").count();
+        assert!(num_colors > 3, "{}", table);
+        for idx in 1..=num_colors {
+            assert!(
+                rtf.contains(&format!("\\cf{}", idx))
+                    || rtf.contains(&format!("\\highlight{}", idx))
+            );
+        }
+        let palette = Palette::new(&ASSETS.theme_set.themes["Monokai Extended"]);
+        let region = format!(
+            "\\red{}\\green{}\\blue{};",
+            palette.region_bg.r, palette.region_bg.g, palette.region_bg.b
+        );
+        let region_idx = table
+            .split(';')
+            .position(|c| region.starts_with(c))
+            .unwrap();
+        assert!(
+            rtf.contains(&format!("\\highlight{} foo}}", region_idx)),
+            "{:?} in {}",
+            region_idx,
+            rtf
+        );
+    }
+
+    #[test]
+    fn test_disabled_font_styles() {
+        // Monokai Extended underlines the class names and italicizes the parameters
+        let file = File::new(
+            PathBuf::from("test.py"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 2)],
+            b"class Foo(Bar):\n    def f(self, x): pass\n".to_vec(),
+        );
+        let rtf = print_rtf(file.clone());
+        assert!(rtf.contains("\\i self}"), "{}", rtf);
+        assert!(rtf.contains("\\ul Foo}"), "{}", rtf);
+
+        let opts = PrinterOptions {
+            disabled_font_styles: vec![FontStyleKind::Italic],
+            ..Default::default()
+        };
+        let rtf = print_rtf_with(file, opts);
+        assert!(!rtf.contains("\\i "), "{}", rtf);
+        assert!(rtf.contains("\\ul Foo}"), "{}", rtf);
+    }
+
+    #[test]
+    fn test_escape() {
+        let tests = [
+            ("foo", "foo"),
+            ("{a}", "\\{a\\}"),
+            ("a\\b", "a\\\\b"),
+            ("\ta", "\\tab a"),
+            ("\x07", "?"),
+            ("é", "\\u233?"),
+            ("あ", "\\u12354?"),
+            ("\u{ff5e}", "\\u-162?"),
+            ("🐶", "\\u-10179?\\u-9162?"),
+        ];
+        for (input, want) in tests {
+            assert_eq!(escape(input), want, "input={:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_separator_and_nothing_to_print() {
+        let contents = (1..=20)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let file = File::new(
+            PathBuf::from("test.txt"),
+            vec![LineMatch::lnum(2), LineMatch::lnum(18)],
+            vec![(1, 3), (17, 19)],
+            contents.into_bytes(),
+        );
+        let rtf = print_rtf(file);
+        assert_eq!(rtf.matches(" ...}\\par").count(), 1, "{}", rtf);
+        assert!(rtf.contains(" 19  }"), "{}", rtf);
+        assert!(!rtf.contains("line 10"), "{}", rtf);
+
+        let file = File::new(PathBuf::from("x.txt"), vec![], vec![], vec![]);
+        assert!(print_rtf(file).is_empty());
+    }
+}
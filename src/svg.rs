@@ -0,0 +1,609 @@
+use crate::chunk::File;
+use crate::printer::{Printer, PrinterOptions, TermColorSupport};
+use crate::syntect::{
+    blend_fg_color, LineKind, Palette, RenderedRow, StyledSpan, SyntectAssets, SyntectPrinter,
+};
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use syntect::highlighting::{Color, FontStyle};
+use unicode_width::UnicodeWidthChar;
+
+/// Font family used when no font is specified with [`SvgPrinter::font_family`].
+pub const DEFAULT_FONT_FAMILY: &str =
+    "ui-monospace, SFMono-Regular, Menlo, Consolas, 'DejaVu Sans Mono', 'Liberation Mono', monospace";
+/// Font size in pixels used when no size is specified with [`SvgPrinter::font_size`].
+pub const DEFAULT_FONT_SIZE: f64 = 14.0;
+/// Ratio of the row height to the font size used when no ratio is specified with [`SvgPrinter::line_height`].
+pub const DEFAULT_LINE_HEIGHT: f64 = 1.4;
+const CELL_WIDTH_RATIO: f64 = 0.6; // Advance of one cell in common monospace fonts relative to the font size
+const CAP_HEIGHT_RATIO: f64 = 0.7;
+const PADDING: f64 = 16.0;
+const TITLE_BAR_HEIGHT: f64 = 36.0; // Height at the default font size. The window chrome is scaled with the font size
+const HARD_TAB_WIDTH: usize = 8;
+
+/// Printer which prints each file as a standalone SVG image like carbon or silicon. It is useful to share crisp
+/// screenshots of search results.
+///
+/// Lines are highlighted in the same way as [`SyntectPrinter`] with its options such as the theme, but options only for
+/// terminals such as the terminal width are not applied. Each line is one row of the image and never wrapped. Every
+/// file is printed as one complete `<svg>` document, so print one file per output to get a valid image.
+pub struct SvgPrinter<'main, W: Write> {
+    out: Mutex<W>, // Protected with mutex because it should print file by file
+    renderer: SyntectPrinter<'main, io::Sink>,
+    window_chrome: bool,
+    font_family: String,
+    font_size: f64,
+    line_height: f64,
+}
+
+impl<'main, W: Write> SvgPrinter<'main, W> {
+    pub fn new(out: W, opts: PrinterOptions<'main>) -> Result<Self> {
+        let renderer = SyntectPrinter::new(io::sink(), Self::svg_options(opts))?;
+        Ok(Self::with_renderer(out, renderer))
+    }
+
+    /// Create a printer with preloaded assets. This panics on the same conditions as [`SyntectPrinter::with_assets`].
+    pub fn with_assets(assets: SyntectAssets, out: W, opts: PrinterOptions<'main>) -> Self {
+        let renderer = SyntectPrinter::with_assets(assets, io::sink(), Self::svg_options(opts));
+        Self::with_renderer(out, renderer)
+    }
+
+    fn with_renderer(out: W, renderer: SyntectPrinter<'main, io::Sink>) -> Self {
+        Self {
+            out: Mutex::new(out),
+            renderer,
+            window_chrome: false,
+            font_family: DEFAULT_FONT_FAMILY.to_string(),
+            font_size: DEFAULT_FONT_SIZE,
+            line_height: DEFAULT_LINE_HEIGHT,
+        }
+    }
+
+    // Images can use any RGB color regardless of the colors supported by the terminal
+    fn svg_options(mut opts: PrinterOptions<'main>) -> PrinterOptions<'main> {
+        opts.color_support = TermColorSupport::True;
+        opts
+    }
+
+    /// Draw a title bar with three buttons like a window of macOS. The path of the file is put in the title bar.
+    pub fn window_chrome(&mut self, enabled: bool) {
+        self.window_chrome = enabled;
+    }
+
+    /// Font family of the text in the syntax of CSS like `'Fira Code', monospace`. Monospace fonts should be used since
+    /// texts are laid out in cells.
+    pub fn font_family(&mut self, family: impl Into<String>) {
+        self.font_family = family.into();
+    }
+
+    /// Font size in pixels. Width of one cell is 0.6 times the font size, which is the advance of common monospace fonts.
+    pub fn font_size(&mut self, px: f64) {
+        self.font_size = px;
+    }
+
+    /// Height of each row relative to the font size like unitless `line-height` of CSS.
+    pub fn line_height(&mut self, ratio: f64) {
+        self.line_height = ratio;
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+
+    /// See [`SyntectPrinter::unknown_default_theme`].
+    pub fn unknown_default_theme(&self) -> Option<&'main str> {
+        self.renderer.unknown_default_theme()
+    }
+
+    // `None` means nothing is rendered for the file
+    fn render_svg(&self, file: &File) -> Option<String> {
+        let (path, rows) = self.renderer.render_rows(file)?;
+        let path = path.to_string_lossy();
+
+        let palette = Palette::new(self.renderer.theme());
+        let background = palette.document_background();
+        let color = |c: Color| hex(blend_fg_color(c, background), palette.foreground);
+
+        let cell_width = self.font_size * CELL_WIDTH_RATIO;
+        let line_height = self.font_size * self.line_height;
+        let baseline_offset = (line_height + self.font_size * CAP_HEIGHT_RATIO) / 2.0; // Center capital letters in rows
+
+        let tab_width = match self.renderer.options().tab_width {
+            0 => HARD_TAB_WIDTH,
+            w => w,
+        };
+        let lnum_width = rows
+            .iter()
+            .filter_map(|r| match r {
+                RenderedRow::Line { lnum, .. } => Some(lnum.to_string().len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        // One cell for diff marks, line numbers and two cells before the text
+        let text_x = PADDING + (lnum_width + 3) as f64 * cell_width;
+        let lnum_end = PADDING + (lnum_width + 1) as f64 * cell_width;
+
+        let laid_out: Vec<_> = rows
+            .iter()
+            .map(|row| match row {
+                RenderedRow::Line { spans, .. } => lay_out(spans, tab_width),
+                _ => vec![],
+            })
+            .collect();
+        let max_cells = laid_out
+            .iter()
+            .filter_map(|cells| cells.last().map(|c| c.col + c.width))
+            .max()
+            .unwrap_or(0);
+
+        let chrome_scale = self.font_size / DEFAULT_FONT_SIZE;
+        let title_bar_height = TITLE_BAR_HEIGHT * chrome_scale;
+        let title_width = str_width(&path) as f64 * cell_width
+            + if self.window_chrome {
+                160.0 * chrome_scale
+            } else {
+                0.0
+            };
+        let width = f64::max(
+            text_x + max_cells as f64 * cell_width + PADDING,
+            title_width + 2.0 * PADDING,
+        );
+        let rows_top = if self.window_chrome {
+            title_bar_height + PADDING / 2.0
+        } else {
+            PADDING + line_height * 1.5 // Path and a half row of margin
+        };
+        let height = rows_top + rows.len() as f64 * line_height + PADDING;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.1}" height="{h:.1}" viewBox="0 0 {w:.1} {h:.1}" font-family="{}" font-size="{}">"#,
+            escape(&self.font_family),
+            self.font_size,
+            w = width,
+            h = height,
+        );
+        let radius = if self.window_chrome { 8 } else { 0 };
+        let _ = writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" rx="{}" fill="{}"/>"#,
+            radius,
+            hex(background, background),
+        );
+        if self.window_chrome {
+            let cy = title_bar_height / 2.0;
+            for (i, button) in ["#ff5f56", "#ffbd2e", "#27c93f"].iter().enumerate() {
+                let cx = PADDING + (4.0 + i as f64 * 20.0) * chrome_scale;
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="{}"/>"#,
+                    cx,
+                    cy,
+                    6.0 * chrome_scale,
+                    button,
+                );
+            }
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" text-anchor="middle" fill="{}">{}</text>"#,
+                width / 2.0,
+                cy + self.font_size * CAP_HEIGHT_RATIO / 2.0, // Center capital letters in the title bar
+                color(palette.gutter_fg),
+                escape(&path),
+            );
+        } else {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" font-weight="bold" fill="{}">{}</text>"#,
+                PADDING,
+                PADDING + baseline_offset,
+                color(palette.foreground),
+                escape(&path),
+            );
+        }
+
+        for (i, (row, cells)) in rows.iter().zip(laid_out.iter()).enumerate() {
+            let top = rows_top + i as f64 * line_height;
+            let baseline = top + baseline_offset;
+            let (lnum, kind) = match row {
+                RenderedRow::Line { lnum, kind, .. } => (*lnum, *kind),
+                RenderedRow::Separator => {
+                    let _ = writeln!(
+                        svg,
+                        r#"<text x="{:.1}" y="{:.1}" text-anchor="end" fill="{}">⋮</text>"#,
+                        lnum_end,
+                        baseline,
+                        color(palette.gutter_fg),
+                    );
+                    continue;
+                }
+                RenderedRow::Notice(notice) => {
+                    let _ = writeln!(
+                        svg,
+                        r#"<text x="{:.1}" y="{:.1}" font-style="italic" fill="{}">{}</text>"#,
+                        text_x,
+                        baseline,
+                        color(palette.gutter_fg),
+                        escape(notice),
+                    );
+                    continue;
+                }
+            };
+
+            let (line_bg, mark) = match kind {
+                LineKind::Context => (None, None),
+                LineKind::Matched => (Some(palette.match_bg), None),
+                LineKind::Removed => (Some(palette.removed_bg), Some('-')),
+                LineKind::Added => (Some(palette.added_bg), Some('+')),
+            };
+            if let Some(bg) = line_bg {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="0" y="{:.1}" width="100%" height="{:.1}" fill="{}"/>"#,
+                    top,
+                    line_height,
+                    color(bg),
+                );
+            }
+            for cell in cells.iter().filter(|c| c.in_match) {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+                    text_x + cell.col as f64 * cell_width,
+                    top,
+                    cell.width as f64 * cell_width,
+                    line_height,
+                    color(palette.region_bg),
+                );
+            }
+
+            let lnum_fg = if kind == LineKind::Context {
+                palette.gutter_fg
+            } else {
+                palette.match_lnum_fg
+            };
+            if let Some(mark) = mark {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{:.1}" y="{:.1}" fill="{}">{}</text>"#,
+                    PADDING,
+                    baseline,
+                    color(lnum_fg),
+                    mark,
+                );
+            }
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" text-anchor="end" fill="{}">{}</text>"#,
+                lnum_end,
+                baseline,
+                color(lnum_fg),
+                lnum,
+            );
+
+            if cells.is_empty() {
+                continue;
+            }
+            let _ = write!(svg, r#"<text y="{:.1}" xml:space="preserve">"#, baseline);
+            for cell in cells {
+                let fg = if cell.in_match {
+                    palette.region_fg
+                } else {
+                    cell.style_fg
+                };
+                let x = text_x + cell.col as f64 * cell_width;
+                let _ = write!(svg, r#"<tspan x="{:.1}" fill="{}""#, x, color(fg));
+                if cell.font_style.contains(FontStyle::BOLD) {
+                    svg.push_str(r#" font-weight="bold""#);
+                }
+                if cell.font_style.contains(FontStyle::ITALIC) {
+                    svg.push_str(r#" font-style="italic""#);
+                }
+                if cell.font_style.contains(FontStyle::UNDERLINE) {
+                    svg.push_str(r#" text-decoration="underline""#);
+                }
+                if !cell.text.chars().all(|c| c.width_cjk() == Some(1)) {
+                    // Glyphs of wide characters don't always take exactly two cells in fonts. Stretch the text to the
+                    // cells so that the following text is aligned with the other lines
+                    let _ = write!(
+                        svg,
+                        r#" textLength="{:.1}" lengthAdjust="spacingAndGlyphs""#,
+                        cell.width as f64 * cell_width,
+                    );
+                }
+                let _ = write!(svg, ">{}</tspan>", escape(&cell.text));
+            }
+            svg.push_str("</text>\n");
+        }
+        svg.push_str("</svg>\n");
+        Some(svg)
+    }
+}
+
+// Text of a span placed at the column of cells. Tabs are expanded and control characters are replaced
+struct Cells {
+    col: usize,
+    width: usize,
+    text: String,
+    style_fg: Color,
+    font_style: FontStyle,
+    in_match: bool,
+}
+
+fn lay_out(spans: &[StyledSpan], tab_width: usize) -> Vec<Cells> {
+    let mut laid_out = Vec::with_capacity(spans.len());
+    let mut col = 0;
+    for span in spans {
+        let start = col;
+        let mut text = String::with_capacity(span.text.len());
+        for c in span.text.chars() {
+            match c {
+                '\t' => {
+                    let w = tab_width - col % tab_width;
+                    text.extend(std::iter::repeat_n(' ', w));
+                    col += w;
+                }
+                c if c.is_control() => {
+                    text.push('\u{fffd}');
+                    col += 1;
+                }
+                c => {
+                    text.push(c);
+                    col += c.width_cjk().unwrap_or(0);
+                }
+            }
+        }
+        if text.is_empty() {
+            continue;
+        }
+        laid_out.push(Cells {
+            col: start,
+            width: col - start,
+            text,
+            style_fg: span.style.foreground,
+            font_style: span.style.font_style,
+            in_match: span.in_match,
+        });
+    }
+    laid_out
+}
+
+fn str_width(s: &str) -> usize {
+    s.chars().map(|c| c.width_cjk().unwrap_or(0)).sum()
+}
+
+// Colors with the special alpha values of 16 colors and 256 colors themes have no RGB value. `fallback` is used for them
+fn hex(c: Color, fallback: Color) -> String {
+    let c = if c.a == 0xff { c } else { fallback };
+    if c.a != 0xff {
+        return "currentColor".to_string();
+    }
+    format!("#{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl<'main, W: Write> Printer for SvgPrinter<'main, W> {
+    fn print(&self, file: File) -> Result<()> {
+        use crate::io::IgnoreBrokenPipe;
+
+        let Some(svg) = self.render_svg(&file) else {
+            return Ok(());
+        };
+        let mut out = self.out.lock().unwrap();
+        out.write_all(svg.as_bytes())
+            .and_then(|_| out.flush())
+            .ignore_broken_pipe()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::LineMatch;
+    use crate::printer::FontStyleKind;
+    use lazy_static::lazy_static;
+    use std::path::PathBuf;
+
+    lazy_static! {
+        static ref ASSETS: SyntectAssets = SyntectAssets::load(None).unwrap();
+    }
+
+    fn print_svg(file: File, opts: PrinterOptions<'_>, window_chrome: bool) -> String {
+        let mut printer = SvgPrinter::with_assets(ASSETS.clone(), vec![], opts);
+        printer.window_chrome(window_chrome);
+        printer.print(file).unwrap();
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    fn x_of(cols: usize, lnum_width: usize) -> String {
+        format!(
+            "{:.1}",
+            PADDING + (lnum_width + 3 + cols) as f64 * DEFAULT_FONT_SIZE * CELL_WIDTH_RATIO
+        )
+    }
+
+    #[test]
+    fn test_print_svg() {
+        let file = File::new(
+            PathBuf::from("a&b.rs"),
+            vec![LineMatch::new(2, vec![(4, 7)])],
+            vec![(1, 3)],
+            b"fn main() {\n    foo::<u8>();\n}\n".to_vec(),
+        );
+        let svg = print_svg(file, PrinterOptions::default(), false);
+        assert!(svg.starts_with("<svg xmlns="), "{}", svg);
+        assert!(svg.ends_with("</svg>\n"), "{}", svg);
+        assert!(svg.contains(">a&amp;b.rs</text>"), "{}", svg);
+        assert!(svg.contains(">&lt;</tspan>"), "{}", svg);
+        assert!(svg.contains(">&gt;</tspan>"), "{}", svg);
+        assert!(!svg.contains("<circle"), "{}", svg);
+        // Matched line and matched region are painted with rectangles
+        assert_eq!(
+            svg.matches(r#"width="100%" height="19.6""#).count(),
+            1,
+            "{}",
+            svg
+        );
+        let region = format!(r#"<rect x="{}" y="#, x_of(4, 1));
+        assert!(svg.contains(&region), "{:?} in {}", region, svg);
+        for lnum in 1..=3 {
+            assert!(svg.contains(&format!(">{}</text>", lnum)), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_wide_chars_and_tabs() {
+        let file = File::new(
+            PathBuf::from("test.txt"),
+            vec![
+                LineMatch::new(1, vec![(6, 7)]),
+                LineMatch::new(2, vec![(1, 4)]),
+            ],
+            vec![(1, 2)],
+            "あいx\n\tfoo\n".as_bytes().to_vec(),
+        );
+        let opts = PrinterOptions {
+            tab_width: 4,
+            ..Default::default()
+        };
+        let svg = print_svg(file, opts, false);
+        // 'x' is put after 4 cells of two wide characters
+        let x = format!(r#"<tspan x="{}""#, x_of(4, 1));
+        assert!(svg.contains(&x), "{:?} in {}", x, svg);
+        assert!(svg.contains(r#"textLength="33.6""#), "{}", svg);
+        // The tab is expanded to 4 spaces
+        let foo = format!(r#"<tspan x="{}""#, x_of(4, 1));
+        assert_eq!(svg.matches(&foo).count(), 2, "{}", svg);
+        assert!(svg.contains(">    </tspan>"), "{}", svg);
+    }
+
+    #[test]
+    fn test_window_chrome() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 1)],
+            b"fn main() {}\n".to_vec(),
+        );
+        let svg = print_svg(file, PrinterOptions::default(), true);
+        assert_eq!(svg.matches("<circle").count(), 3, "{}", svg);
+        assert!(svg.contains(r#"rx="8""#), "{}", svg);
+        assert!(svg.contains(r#"text-anchor="middle" fill="#), "{}", svg);
+        assert!(svg.contains(r#"cy="18.0" r="6.0""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_window_chrome_scaled_with_font_size() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 1)],
+            b"fn main() {}\n".to_vec(),
+        );
+        let mut printer =
+            SvgPrinter::with_assets(ASSETS.clone(), vec![], PrinterOptions::default());
+        printer.window_chrome(true);
+        printer.font_size(DEFAULT_FONT_SIZE * 2.0);
+        printer.print(file).unwrap();
+        let svg = String::from_utf8(printer.into_inner()).unwrap();
+        // Title bar is twice as high as the default and the first row starts below it
+        let title_bar_height = TITLE_BAR_HEIGHT * 2.0;
+        let cy = format!(r#"cy="{:.1}" r="12.0""#, title_bar_height / 2.0);
+        assert_eq!(svg.matches(&cy).count(), 3, "{:?} in {}", cy, svg);
+        let row = format!(r#"<rect x="0" y="{:.1}""#, title_bar_height + PADDING / 2.0);
+        assert!(svg.contains(&row), "{:?} in {}", row, svg);
+    }
+
+    #[test]
+    fn test_font_hints() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::new(1, vec![(3, 7)])],
+            vec![(1, 1)],
+            b"fn main() {}\n".to_vec(),
+        );
+        let mut printer =
+            SvgPrinter::with_assets(ASSETS.clone(), vec![], PrinterOptions::default());
+        printer.font_family("'Fira Code', monospace");
+        printer.font_size(20.0);
+        printer.line_height(2.0);
+        printer.print(file).unwrap();
+        let svg = String::from_utf8(printer.into_inner()).unwrap();
+        assert!(
+            svg.contains(r#" font-family="&apos;Fira Code&apos;, monospace" font-size="20">"#),
+            "{}",
+            svg
+        );
+        // Row is 40px high and one cell is 12px wide
+        assert!(svg.contains(r#"width="100%" height="40.0""#), "{}", svg);
+        let region = format!(r#"<rect x="{:.1}" "#, PADDING + 4.0 * 12.0 + 3.0 * 12.0);
+        assert!(svg.contains(&region), "{:?} in {}", region, svg);
+        assert!(svg.contains(r#"width="48.0" height="40.0""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_separator_and_nothing_to_print() {
+        let contents = (1..=20)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let file = File::new(
+            PathBuf::from("test.txt"),
+            vec![LineMatch::lnum(2), LineMatch::lnum(18)],
+            vec![(1, 3), (17, 19)],
+            contents.into_bytes(),
+        );
+        let svg = print_svg(file, PrinterOptions::default(), false);
+        assert_eq!(svg.matches(">⋮</text>").count(), 1, "{}", svg);
+        assert!(svg.contains(">19</text>"), "{}", svg);
+        assert!(!svg.contains(">10</text>"), "{}", svg);
+
+        let file = File::new(PathBuf::from("x.txt"), vec![], vec![], vec![]);
+        let svg = print_svg(file, PrinterOptions::default(), false);
+        assert!(svg.is_empty(), "{}", svg);
+    }
+
+    #[test]
+    fn test_disabled_font_styles() {
+        // Monokai Extended underlines the class names and italicizes the parameters
+        let file = File::new(
+            PathBuf::from("test.py"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 2)],
+            b"class Foo(Bar):\n    def f(self, x): pass\n".to_vec(),
+        );
+        let svg = print_svg(file.clone(), PrinterOptions::default(), false);
+        assert!(svg.contains(r#"font-style="italic">self"#), "{}", svg);
+        assert!(
+            svg.contains(r#"text-decoration="underline">Foo"#),
+            "{}",
+            svg
+        );
+
+        let opts = PrinterOptions {
+            disabled_font_styles: vec![FontStyleKind::Italic, FontStyleKind::Underline],
+            ..Default::default()
+        };
+        let svg = print_svg(file, opts, false);
+        assert!(!svg.contains(r#"font-style="italic""#), "{}", svg);
+        assert!(!svg.contains("text-decoration"), "{}", svg);
+    }
+}
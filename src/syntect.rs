@@ -1,20 +1,34 @@
-use crate::chunk::{File, Line};
-use crate::printer::{Printer, PrinterOptions, TermColorSupport, TextWrapMode};
+use crate::chunk::{File, Line, LinesInclusive};
+use crate::dirs::{bat_cache_dir, bat_config_dir};
+use crate::error::{HgrepError, Result};
+use crate::printer::{
+    archive_member_path, file_url, json_string, revision_path, sort_files, BorderStyle, FirstOnly,
+    FontStyleKind, HeaderAlign, HeaderLines, HeaderStyle, InvalidUtf8, MatchEmphasis, Numbering,
+    Printer, PrinterOptions, SearchInfo, SortOrder, TermColorSupport, TextWrapMode,
+};
 use ansi_colours::ansi256_from_rgb;
-use anyhow::Result;
 use flate2::read::ZlibDecoder;
-use memchr::{memchr_iter, Memchr};
+use globset::{GlobBuilder, GlobMatcher};
+use memchr::{memchr, memchr_iter};
+use std::borrow::Cow;
 use std::cmp;
+use std::collections::BTreeMap;
+use std::env;
 use std::ffi::OsStr;
 use std::fmt;
+use std::fs;
 use std::io::{self, Stdout, StdoutLock, Write};
+use std::iter;
+use std::mem;
 use std::ops::{Deref, DerefMut};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::Chars;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use syntect::highlighting::{
     Color, FontStyle, HighlightIterator, HighlightState, Highlighter, Style, Theme, ThemeSet,
 };
-use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::parsing::{ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 // Note for lifetimes:
@@ -25,11 +39,93 @@ const SYNTAX_SET_BIN: &[u8] = include_bytes!("../assets/syntaxes.bin");
 const THEME_SET_BIN: &[u8] = include_bytes!("../assets/themes.bin");
 
 fn load_bat_themes() -> Result<ThemeSet> {
-    Ok(bincode::deserialize_from(ZlibDecoder::new(THEME_SET_BIN))?)
+    load_bat_themes_with(|_| true)
+}
+
+// Bundled themes are serialized as one `ThemeSet` so every theme needs to be deserialized in order. Only the themes whose
+// names satisfy `keep` are collected and the others are dropped as soon as they are deserialized. This relies on that
+// bincode serializes `ThemeSet` as its only field, which is the number of themes followed by the pairs of name and theme
+fn load_bat_themes_with(keep: impl Fn(&str) -> bool) -> Result<ThemeSet> {
+    let mut reader = ZlibDecoder::new(THEME_SET_BIN);
+    let len: u64 = bincode::deserialize_from(&mut reader).map_err(|e| HgrepError::ThemeLoad(e))?;
+    let mut set = ThemeSet::new();
+    for _ in 0..len {
+        let name: String =
+            bincode::deserialize_from(&mut reader).map_err(|e| HgrepError::ThemeLoad(e))?;
+        let theme: Theme =
+            bincode::deserialize_from(&mut reader).map_err(|e| HgrepError::ThemeLoad(e))?;
+        if keep(&name) {
+            set.themes.insert(name, theme);
+        }
+    }
+    Ok(set)
 }
 
 fn load_syntax_set() -> Result<SyntaxSet> {
-    Ok(bincode::deserialize_from(ZlibDecoder::new(SYNTAX_SET_BIN))?)
+    bincode::deserialize_from(ZlibDecoder::new(SYNTAX_SET_BIN))
+        .map_err(|e| HgrepError::SyntaxLoad(e))
+}
+
+// Read the assets in bat's cache directory built by `bat cache --build`. Files which cannot be deserialized such as the
+// ones built by incompatible versions of bat should be ignored
+fn read_bat_cache(cache_dir: Option<&Path>, file_name: &str) -> Option<Vec<u8>> {
+    fs::read(cache_dir?.join(file_name)).ok()
+}
+
+// Bat serializes the theme set in its cache without compression. Each theme in the set is serialized and compressed
+// separately so that bat can deserialize only the theme it uses (`LazyThemeSet` of bat). Broken themes are ignored
+fn load_bat_cache_themes(cache_dir: Option<&Path>) -> Option<ThemeSet> {
+    let contents = read_bat_cache(cache_dir, "themes.bin")?;
+    let lazy: BTreeMap<String, Vec<u8>> = bincode::deserialize(&contents).ok()?;
+    let mut themes = ThemeSet::new();
+    for (name, serialized) in lazy {
+        if let Ok(theme) = bincode::deserialize_from(ZlibDecoder::new(serialized.as_slice())) {
+            themes.themes.insert(name, theme);
+        }
+    }
+    Some(themes)
+}
+
+// Themes which users installed for bat. They are the themes in bat's cache and `.tmTheme` files in the `themes`
+// directory of bat's config directory. Broken theme files are ignored
+fn load_user_bat_themes(cache_dir: Option<&Path>, config_dir: Option<&Path>) -> ThemeSet {
+    let mut themes = load_bat_cache_themes(cache_dir).unwrap_or_default();
+    let dir = config_dir.map(|d| d.join("themes"));
+    let paths = dir.and_then(|d| ThemeSet::discover_theme_paths(d).ok());
+    for path in paths.unwrap_or_default() {
+        let (Some(name), Ok(theme)) = (path.file_stem(), ThemeSet::get_theme(&path)) else {
+            continue;
+        };
+        themes
+            .themes
+            .insert(name.to_string_lossy().into_owned(), theme);
+    }
+    themes
+}
+
+// Syntaxes which users installed for bat. The syntax set in bat's cache is preferred since it contains all syntaxes bat
+// uses. Otherwise `.sublime-syntax` files in the `syntaxes` directory of bat's config directory are added to the bundled
+// syntaxes
+fn with_user_bat_syntaxes(
+    bundled: SyntaxSet,
+    cache_dir: Option<&Path>,
+    config_dir: Option<&Path>,
+) -> SyntaxSet {
+    // Unlike themes, bat compresses the whole syntax set in its cache
+    let cached = read_bat_cache(cache_dir, "syntaxes.bin")
+        .and_then(|c| bincode::deserialize_from(ZlibDecoder::new(c.as_slice())).ok());
+    if let Some(cached) = cached {
+        return cached;
+    }
+    let Some(dir) = config_dir
+        .map(|d| d.join("syntaxes"))
+        .filter(|d| d.is_dir())
+    else {
+        return bundled;
+    };
+    let mut builder = bundled.into_builder();
+    let _ = builder.add_from_folder(dir, true); // Syntaxes loaded before a broken syntax file are still available
+    builder.build()
 }
 
 pub trait LockableWrite<'a> {
@@ -44,6 +140,14 @@ impl<'a> LockableWrite<'a> for Stdout {
     }
 }
 
+// For printers which only render files with `SyntectPrinter::render` and never print bytes
+impl<'a> LockableWrite<'a> for io::Sink {
+    type Locked = io::Sink;
+    fn lock(&'a self) -> Self::Locked {
+        io::sink()
+    }
+}
+
 pub fn list_themes<W: Write>(out: W, opts: &PrinterOptions<'_>) -> Result<()> {
     let syntaxes = load_syntax_set()?;
     list_themes_with_syntaxes(out, opts, &syntaxes)
@@ -58,7 +162,11 @@ fn list_themes_with_syntaxes<W: Write>(
 
     let themes = {
         let mut m = load_bat_themes()?.themes;
-        m.extend(ThemeSet::load_defaults().themes.into_iter());
+        m.extend(ThemeSet::load_defaults().themes);
+        if opts.bat_assets {
+            let (cache_dir, config_dir) = (bat_cache_dir(), bat_config_dir());
+            m.extend(load_user_bat_themes(cache_dir.as_deref(), config_dir.as_deref()).themes);
+        }
         let mut v: Vec<_> = m.into_iter().collect();
         v.sort_by(|l, r| l.0.cmp(&r.0));
         v
@@ -86,58 +194,272 @@ fn list_themes_with_syntaxes<W: Write>(
     Ok(())
 }
 
-// Use u64::log10 once it is stabilized: https://github.com/rust-lang/rust/issues/70887
 #[inline]
 fn num_digits(n: u64) -> u16 {
-    (n as f64).log10() as u16 + 1
+    n.checked_ilog10().unwrap_or(0) as u16 + 1 // 0 has one digit
 }
 
 #[derive(Debug)]
-pub struct PrintError {
-    message: String,
+struct Token<'line> {
+    style: Style,
+    text: &'line str,
 }
 
-impl PrintError {
-    fn new<S: Into<String>>(msg: S) -> Self {
-        Self {
-            message: msg.into(),
+impl<'line> Token<'line> {
+    fn chomp(&mut self) {
+        if self.text.ends_with('\n') {
+            self.text = &self.text[..self.text.len() - 1];
+            if self.text.ends_with('\r') {
+                self.text = &self.text[..self.text.len() - 1];
+            }
         }
     }
 }
 
-impl std::error::Error for PrintError {}
+struct DecodedLine<'a> {
+    text: Cow<'a, str>,
+    // Matched regions as byte offsets in `text`
+    regions: Option<Vec<(usize, usize)>>,
+    // Ranges of the `\xNN` escapes in `text`
+    escapes: Vec<(usize, usize)>,
+}
 
-impl fmt::Display for PrintError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Error while printing output with syntect: {}",
-            &self.message
-        )
+// Decode the line replacing sequences which are invalid as UTF-8. Byte offsets of the matched regions are converted into
+// offsets in the decoded line
+fn decode_line(
+    bytes: &[u8],
+    regions: Option<Vec<(usize, usize)>>,
+    invalid_utf8: InvalidUtf8,
+) -> DecodedLine<'_> {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return DecodedLine {
+            text: Cow::Borrowed(text),
+            regions,
+            escapes: vec![],
+        };
+    }
+
+    let mut line = String::with_capacity(bytes.len());
+    let mut escapes = vec![];
+    // Offsets in the bytes and the decoded line at the start of each invalid sequence and at the end of it
+    let mut offsets = vec![(0, 0)];
+    let mut offset = 0;
+    for chunk in bytes.utf8_chunks() {
+        line.push_str(chunk.valid());
+        offset += chunk.valid().len();
+        let invalid = chunk.invalid();
+        if invalid.is_empty() {
+            continue;
+        }
+        offsets.push((offset, line.len()));
+        match invalid_utf8 {
+            InvalidUtf8::Replace(c) => line.push(c),
+            InvalidUtf8::Escape => {
+                let start = line.len();
+                for b in invalid {
+                    line.push_str(&format!("\\x{:02x}", b));
+                }
+                escapes.push((start, line.len()));
+            }
+        }
+        offset += invalid.len();
+        offsets.push((offset, line.len()));
+    }
+
+    let convert = |o: usize| {
+        let idx = offsets.partition_point(|(b, _)| *b <= o) - 1; // `offsets[0]` is (0, 0)
+        let (b, l) = offsets[idx];
+        if idx % 2 == 0 {
+            l + (o - b) // In a valid sequence
+        } else if invalid_utf8 == InvalidUtf8::Escape {
+            l + (o - b) * 4 // Each byte is escaped as `\xNN`
+        } else {
+            l // In the middle of the invalid sequence replaced with one character
+        }
+    };
+    let regions = regions.map(|regions| {
+        regions
+            .into_iter()
+            .map(|(s, e)| (convert(s), convert(e)))
+            .collect()
+    });
+    DecodedLine {
+        text: Cow::Owned(line),
+        regions,
+        escapes,
     }
 }
 
-#[derive(Debug)]
-struct Token<'line> {
-    style: Style,
-    text: &'line str,
+// Delimiter of the fields in the file for the tabular layout. `None` means the layout is disabled
+fn tabular_delimiter(opts: &PrinterOptions<'_>, path: &Path) -> Option<char> {
+    if !opts.tabular {
+        return None;
+    }
+    let delimiter =
+        opts.delimiter
+            .unwrap_or_else(|| match path.extension().and_then(OsStr::to_str) {
+                Some(ext) if ext.eq_ignore_ascii_case("csv") => ',',
+                _ => '\t',
+            });
+    Some(delimiter)
 }
 
-impl<'line> Token<'line> {
-    fn chomp(&mut self) {
-        if self.text.ends_with('\n') {
-            self.text = &self.text[..self.text.len() - 1];
-            if self.text.ends_with('\r') {
-                self.text = &self.text[..self.text.len() - 1];
+// Byte offsets of the delimiters which separate the fields in the line. Delimiters in double-quoted fields are ignored
+fn delimiter_offsets(line: &str, delimiter: char) -> Vec<usize> {
+    let mut in_quotes = false;
+    line.char_indices()
+        .filter(|&(_, c)| {
+            if c == '"' {
+                in_quotes = !in_quotes;
+            }
+            c == delimiter && !in_quotes
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Width of each field in the line which is followed by a delimiter
+fn field_widths(line: &str, delimiter: char) -> Vec<usize> {
+    let mut start = 0;
+    delimiter_offsets(line, delimiter)
+        .into_iter()
+        .map(|offset| {
+            let width = line[start..offset]
+                .chars()
+                .map(|c| c.width_cjk().unwrap_or(0))
+                .sum();
+            start = offset + delimiter.len_utf8();
+            width
+        })
+        .collect()
+}
+
+// Maximum width of each field in the lines of each chunk for the tabular layout
+fn column_widths(contents: &[u8], chunks: &[(u64, u64)], delimiter: char) -> Vec<Vec<usize>> {
+    let mut columns = vec![vec![]; chunks.len()];
+    let mut idx = 0;
+    for Line(bytes, lnum) in LinesInclusive::new(contents) {
+        while chunks.get(idx).is_some_and(|(_, end)| *end < lnum) {
+            idx += 1;
+        }
+        let Some(&(start, _)) = chunks.get(idx) else {
+            break;
+        };
+        if lnum < start {
+            continue;
+        }
+        let widths: &mut Vec<usize> = &mut columns[idx];
+        for (i, w) in field_widths(&String::from_utf8_lossy(bytes), delimiter)
+            .into_iter()
+            .enumerate()
+        {
+            match widths.get_mut(i) {
+                Some(width) => *width = cmp::max(*width, w),
+                None => widths.push(w),
+            }
+        }
+    }
+    columns
+}
+
+// Pad the fields of the line with spaces so that they are aligned to the columns. A tab delimiter is replaced with a
+// space since the padding makes the tab stop meaningless. Byte offsets of the regions and the escapes are moved
+fn align_fields<'a>(line: DecodedLine<'a>, widths: &[usize], delimiter: char) -> DecodedLine<'a> {
+    let text = line.text.as_ref();
+    let field_widths = field_widths(text, delimiter);
+    let offsets = delimiter_offsets(text, delimiter);
+    let mut aligned = String::with_capacity(text.len() + widths.iter().sum::<usize>());
+    let mut inserted = vec![]; // Byte offset of each padding in the original text and the total length inserted so far
+    let mut start = 0;
+    let mut total = 0;
+    for (offset, (field, column)) in offsets.into_iter().zip(field_widths.iter().zip(widths)) {
+        aligned.push_str(&text[start..offset]);
+        let pad = column.saturating_sub(*field);
+        aligned.extend(iter::repeat_n(' ', pad));
+        total += pad;
+        inserted.push((offset, total));
+        aligned.push(if delimiter == '\t' { ' ' } else { delimiter });
+        start = offset + delimiter.len_utf8();
+    }
+    if inserted.is_empty() {
+        return line;
+    }
+    aligned.push_str(&text[start..]);
+
+    // The padding is put before the delimiter. A range starting at the delimiter starts after the padding and a range
+    // ending at the delimiter ends before the padding
+    let convert = |o: usize, is_start: bool| {
+        let idx = inserted.partition_point(|&(p, _)| p < o || is_start && p == o);
+        o + idx.checked_sub(1).map(|i| inserted[i].1).unwrap_or(0)
+    };
+    let convert_range = |(s, e): (usize, usize)| (convert(s, true), convert(e, false));
+    DecodedLine {
+        text: Cow::Owned(aligned),
+        regions: line
+            .regions
+            .map(|rs| rs.into_iter().map(convert_range).collect()),
+        escapes: line.escapes.into_iter().map(convert_range).collect(),
+    }
+}
+
+// Split the tokens at the boundaries of the ranges and draw the text in the ranges with the faint foreground color
+fn dim_ranges<'line>(tokens: Vec<Token<'line>>, ranges: &[(usize, usize)]) -> Vec<Token<'line>> {
+    if ranges.is_empty() {
+        return tokens;
+    }
+    let mut dimmed = Vec::with_capacity(tokens.len());
+    let mut offset = 0;
+    for Token { style, text } in tokens {
+        let mut rest = text;
+        while !rest.is_empty() {
+            let range = ranges.iter().find(|(s, e)| *s <= offset && offset < *e);
+            let boundary = match range {
+                Some((_, e)) => *e,
+                None => ranges
+                    .iter()
+                    .map(|(s, _)| *s)
+                    .find(|s| *s > offset)
+                    .unwrap_or(usize::MAX),
+            };
+            let len = cmp::min(rest.len(), boundary - offset); // Escapes are ASCII so this is at char boundary
+            let mut style = style;
+            if range.is_some() && style.foreground.a == 0xff {
+                // Alpha 0 and 1 are special cases for 16 colors and 256 colors themes. Don't touch them
+                style.foreground.a = 0x80;
+                style.foreground = blend_fg_color(style.foreground, style.background);
             }
+            dimmed.push(Token {
+                style,
+                text: &rest[..len],
+            });
+            rest = &rest[len..];
+            offset += len;
+        }
+    }
+    dimmed
+}
+
+// Byte ranges in a line which are not covered by any of the regions. The last range extends to the end of the line
+fn ranges_between(regions: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut regions = regions.to_vec();
+    regions.sort_unstable();
+    let mut ranges = Vec::with_capacity(regions.len() + 1);
+    let mut offset = 0;
+    for (s, e) in regions {
+        if offset < s {
+            ranges.push((offset, s));
         }
+        offset = cmp::max(offset, e);
     }
+    ranges.push((offset, usize::MAX));
+    ranges
 }
 
 #[derive(Clone, Copy)]
 enum RegionBoundary {
     Start,
     End,
+    Switch, // The next region of another pattern starts at the end of the current region
     NotFound,
 }
 
@@ -153,13 +475,18 @@ struct DrawEvents<'a, 'line: 'a> {
     tokens: &'a [Token<'line>],
     chars_in_token: Chars<'line>,
     regions: &'a [(usize, usize)],
+    patterns: &'a [usize], // Pattern index of each region. Empty means all regions are of the first pattern
     current_style: Style,
     in_region: bool,
     byte_offset: usize,
 }
 
 impl<'a, 'line: 'a> DrawEvents<'a, 'line> {
-    fn new(tokens: &'a [Token<'line>], regions: &'a [(usize, usize)]) -> Self {
+    fn new(
+        tokens: &'a [Token<'line>],
+        regions: &'a [(usize, usize)],
+        patterns: &'a [usize],
+    ) -> Self {
         let (chars_in_token, current_style, tokens) =
             if let Some((head, tail)) = tokens.split_first() {
                 (head.text.chars(), head.style, tail)
@@ -171,6 +498,7 @@ impl<'a, 'line: 'a> DrawEvents<'a, 'line> {
             tokens,
             chars_in_token,
             regions,
+            patterns,
             current_style,
             in_region: false,
             byte_offset: 0,
@@ -183,15 +511,18 @@ impl<'a, 'line: 'a> DrawEvents<'a, 'line> {
         // Eat done regions
         let num_done_regions = self.regions.iter().take_while(|(_, e)| *e < o).count();
         if num_done_regions > 0 {
-            self.regions = &self.regions[num_done_regions..];
+            self.skip_regions(num_done_regions);
         }
 
         match self.regions.first().copied() {
             Some((s, e)) if o == s && o < e => RegionBoundary::Start,
             Some((_, e)) if o == e => {
-                // When the next region is adjacent, skip changing highlight
+                // When the next region is adjacent, skip changing highlight unless its color is different
                 match self.regions.get(1) {
-                    Some((s, _)) if o == *s => RegionBoundary::NotFound,
+                    Some((s, _)) if o == *s && self.pattern(1) == self.pattern(0) => {
+                        RegionBoundary::NotFound
+                    }
+                    Some((s, _)) if o == *s => RegionBoundary::Switch,
                     _ => RegionBoundary::End,
                 }
             }
@@ -199,6 +530,40 @@ impl<'a, 'line: 'a> DrawEvents<'a, 'line> {
         }
     }
 
+    fn skip_regions(&mut self, num: usize) {
+        self.regions = &self.regions[num..];
+        self.patterns = self.patterns.get(num..).unwrap_or(&[]);
+    }
+
+    fn pattern(&self, idx: usize) -> usize {
+        self.patterns.get(idx).copied().unwrap_or(0)
+    }
+
+    // Pattern index of the region being drawn or starting next
+    fn region_pattern(&self) -> usize {
+        self.pattern(0)
+    }
+
+    // Take the rest of the current token until the next tab character or the next boundary of regions. The taken text is
+    // drawn without any event. The result is empty when some event is pending at the current position
+    fn take_plain_text(&mut self) -> &'line str {
+        let o = self.byte_offset;
+        let rest = self.chars_in_token.as_str();
+        let mut len = rest.find('\t').unwrap_or(rest.len());
+        let boundary = self
+            .regions
+            .iter()
+            .flat_map(|&(s, e)| [s, e])
+            .find(|&b| b >= o);
+        if let Some(b) = boundary {
+            len = cmp::min(len, b - o);
+        }
+        let (text, rest) = rest.split_at(len);
+        self.chars_in_token = rest.chars();
+        self.byte_offset += len;
+        text
+    }
+
     fn next_event(&mut self) -> DrawEvent {
         match self.region_boundary() {
             RegionBoundary::Start if !self.in_region => {
@@ -209,6 +574,11 @@ impl<'a, 'line: 'a> DrawEvents<'a, 'line> {
                 self.in_region = false;
                 return DrawEvent::RegionEnd;
             }
+            RegionBoundary::Switch => {
+                self.skip_regions(1);
+                self.in_region = true;
+                return DrawEvent::RegionStart;
+            }
             _ => { /* fall through */ }
         }
 
@@ -231,7 +601,7 @@ impl<'a, 'line: 'a> DrawEvents<'a, 'line> {
 
 #[inline]
 #[allow(clippy::many_single_char_names)]
-fn blend_fg_color(fg: Color, bg: Color) -> Color {
+pub(crate) fn blend_fg_color(fg: Color, bg: Color) -> Color {
     if fg.a == 0xff || fg.a == 0 || fg.a == 1 {
         return fg; // 0 and 1 are special cases for 16 colors and 256 colors themes
     }
@@ -272,22 +642,33 @@ fn weak_blend_fg_color(mut fg: Color, bg: Color) -> Color {
 
 #[inline]
 fn diff_u8(x: u8, y: u8) -> u8 {
-    if x > y {
-        x - y
-    } else {
-        y - x
+    x.abs_diff(y)
+}
+
+// Font styles which are not disabled by `PrinterOptions::disabled_font_styles`
+fn enabled_font_styles(disabled: &[FontStyleKind]) -> FontStyle {
+    let mut styles = FontStyle::all();
+    for kind in disabled {
+        styles.remove(match kind {
+            FontStyleKind::Bold => FontStyle::BOLD,
+            FontStyleKind::Italic => FontStyle::ITALIC,
+            FontStyleKind::Underline => FontStyle::UNDERLINE,
+        });
     }
+    styles
 }
 
 #[derive(Debug)]
-struct Palette {
-    foreground: Color,
-    background: Color,
-    match_bg: Color,
-    match_lnum_fg: Color,
-    region_fg: Color,
-    region_bg: Color,
-    gutter_fg: Color,
+pub(crate) struct Palette {
+    pub(crate) foreground: Color,
+    pub(crate) background: Color,
+    pub(crate) match_bg: Color,
+    pub(crate) match_lnum_fg: Color,
+    pub(crate) region_fg: Color,
+    pub(crate) region_bg: Color,
+    pub(crate) gutter_fg: Color,
+    pub(crate) removed_bg: Color,
+    pub(crate) added_bg: Color,
 }
 
 impl Palette {
@@ -309,6 +690,18 @@ impl Palette {
         b: 0,
         a: 0,
     };
+    const RED_COLOR_16: Color = Color {
+        r: 1, // Red
+        g: 0,
+        b: 0,
+        a: 0,
+    };
+    const GREEN_COLOR_16: Color = Color {
+        r: 2, // Green
+        g: 0,
+        b: 0,
+        a: 0,
+    };
     const ANSI16: Palette = Palette {
         foreground: Self::NO_COLOR,
         background: Self::NO_COLOR,
@@ -317,44 +710,74 @@ impl Palette {
         region_fg: Self::BLACK_COLOR_16,
         region_bg: Self::YELLOW_COLOR_16,
         gutter_fg: Self::NO_COLOR,
+        removed_bg: Self::RED_COLOR_16,
+        added_bg: Self::GREEN_COLOR_16,
     };
 
-    fn new(theme: &Theme) -> Self {
+    // Background of documents such as images. White is used when the theme has no background color
+    pub(crate) fn document_background(&self) -> Color {
+        if self.background.a == 0xff {
+            self.background
+        } else {
+            Color::WHITE
+        }
+    }
+
+    pub(crate) fn new(theme: &Theme) -> Self {
         let background = theme.settings.background.unwrap_or(Self::NO_COLOR);
-        let foreground = theme.settings.foreground.unwrap_or(Self::NO_COLOR);
+        let foreground = match theme.settings.foreground {
+            Some(fg) => fg,
+            // Some minimal themes omit the foreground. Passing through the terminal's color would reset all colors on
+            // setting colors of line numbers. Choose a color readable on the background instead
+            None if background.a == 0xff => Self::default_foreground(background),
+            None => Self::NO_COLOR,
+        };
         let foreground = blend_fg_color(foreground, background);
 
         if foreground.a == 1 && background.a == 1 {
             return Self::ANSI16;
         }
 
+        // When the theme has no background, terminal's background is used. Guess it from the foreground to derive
+        // other colors so that line numbers, matched lines and regions are still visible
+        let base = if background.a == 1 && foreground.a == 0xff {
+            Self::default_foreground(foreground)
+        } else {
+            background
+        };
+
         // gutter and gutter_foreground are not fit to show line numbers and borders in some color themes
-        let gutter_fg = weak_blend_fg_color(foreground, background);
-        let match_lnum_fg = blend_fg_color(foreground, background);
+        let gutter_fg = weak_blend_fg_color(foreground, base);
+        let match_lnum_fg = blend_fg_color(foreground, base);
 
         let match_bg = theme
             .settings
             .line_highlight
-            .unwrap_or_else(|| weak_blend_fg_color(foreground, background));
+            .unwrap_or_else(|| weak_blend_fg_color(foreground, base));
 
         let (region_fg, region_bg) = if let Some(bg) = theme.settings.find_highlight {
             let fg = theme.settings.find_highlight_foreground.unwrap_or_else(|| {
                 let avg = color_average(bg);
                 let avg_fg = color_average(foreground);
-                let avg_bg = color_average(background);
+                let avg_bg = color_average(base);
                 // Choose foreground or background looking at distance
                 if diff_u8(avg_fg, avg) > diff_u8(avg_bg, avg) {
                     foreground
                 } else {
-                    background
+                    base
                 }
             });
             let fg = blend_fg_color(fg, bg);
             (fg, bg)
         } else {
-            (background, foreground)
+            (base, foreground)
         };
 
+        // Themes don't define colors for diffs. Tint the background with red and green like diff viewers
+        let tint = |r, g, b| blend_fg_color(Color { r, g, b, a: 0x40 }, base);
+        let removed_bg = tint(0xff, 0x00, 0x00);
+        let added_bg = tint(0x00, 0xff, 0x00);
+
         Self {
             foreground,
             background,
@@ -363,6 +786,22 @@ impl Palette {
             region_fg,
             region_bg,
             gutter_fg,
+            removed_bg,
+            added_bg,
+        }
+    }
+
+    fn default_foreground(background: Color) -> Color {
+        let v = if color_average(background) > 0x80 {
+            0x20
+        } else {
+            0xe0
+        };
+        Color {
+            r: v,
+            g: v,
+            b: v,
+            a: 0xff,
         }
     }
 
@@ -375,9 +814,14 @@ struct Canvas<W: Write> {
     out: W,
     true_color: bool,
     has_background: bool,
+    transparent: bool,
     palette: Palette,
     current_fg: Option<Color>,
     current_bg: Option<Color>,
+    match_underline: Option<Color>,
+    pattern_colors: Vec<Color>,
+    region_pattern: usize,  // Pattern index of the region being drawn
+    font_styles: FontStyle, // Font styles of syntax highlighting which are allowed to be emitted
 }
 
 impl<W: Write> Deref for Canvas<W> {
@@ -394,19 +838,53 @@ impl<W: Write> DerefMut for Canvas<W> {
 
 impl<W: Write> Canvas<W> {
     fn new(out: W, opts: &PrinterOptions<'_>, theme: &Theme) -> Self {
-        let palette = if opts.color_support == TermColorSupport::Ansi16 {
+        let mut palette = if opts.color_support == TermColorSupport::Ansi16 {
             Palette::ANSI16
         } else {
             Palette::new(theme)
         };
+        if let (Some((r, g, b)), false) = (opts.match_color, palette.is_ansi16()) {
+            palette.region_bg = Color { r, g, b, a: 255 };
+        }
+        if opts.plain_gutter {
+            palette.match_lnum_fg = palette.gutter_fg;
+        }
+
+        let mut match_underline = opts
+            .match_underline
+            .map(|(r, g, b)| Color { r, g, b, a: 255 });
+        if opts.transparent {
+            // Matched regions cannot be painted. Underline them with the color of region background instead
+            match_underline = match_underline.or(Some(palette.region_bg));
+        }
+
+        let font_styles = enabled_font_styles(&opts.disabled_font_styles);
+
+        let pattern_colors = if palette.is_ansi16() {
+            vec![]
+        } else {
+            opts.pattern_colors
+                .iter()
+                .map(|&(r, g, b)| Color { r, g, b, a: 255 })
+                .collect()
+        };
 
         Self {
             out,
             true_color: opts.color_support == TermColorSupport::True,
-            has_background: !palette.is_ansi16() && opts.background_color,
+            // Without the background color of the theme, painting the default background would reset all colors
+            has_background: !palette.is_ansi16()
+                && palette.background != Palette::NO_COLOR
+                && opts.background_color
+                && !opts.transparent,
+            transparent: opts.transparent,
             palette,
             current_fg: None,
             current_bg: None,
+            match_underline,
+            pattern_colors,
+            region_pattern: 0,
+            font_styles,
         }
     }
 
@@ -443,6 +921,9 @@ impl<W: Write> Canvas<W> {
     }
 
     fn set_bg(&mut self, c: Color) -> io::Result<()> {
+        if self.transparent {
+            return Ok(());
+        }
         if self.current_bg != Some(c) {
             self.set_color(40, c)?;
             self.current_bg = Some(c);
@@ -486,6 +967,11 @@ impl<W: Write> Canvas<W> {
         Ok(())
     }
 
+    fn set_dim(&mut self) -> io::Result<()> {
+        self.out.write_all(b"\x1b[2m")?;
+        Ok(())
+    }
+
     fn unset_bold(&mut self) -> io::Result<()> {
         self.out.write_all(b"\x1b[22m")?;
         Ok(())
@@ -497,6 +983,7 @@ impl<W: Write> Canvas<W> {
     }
 
     fn set_font_style(&mut self, style: FontStyle) -> io::Result<()> {
+        let style = style & self.font_styles;
         if style.contains(FontStyle::BOLD) {
             self.set_bold()?;
         }
@@ -507,6 +994,7 @@ impl<W: Write> Canvas<W> {
     }
 
     fn unset_font_style(&mut self, style: FontStyle) -> io::Result<()> {
+        let style = style & self.font_styles;
         if style.contains(FontStyle::BOLD) {
             self.unset_bold()?;
         }
@@ -516,6 +1004,14 @@ impl<W: Write> Canvas<W> {
         Ok(())
     }
 
+    fn unset_bg(&mut self) -> io::Result<()> {
+        if self.current_bg.is_some() {
+            self.out.write_all(b"\x1b[49m")?;
+            self.current_bg = None;
+        }
+        Ok(())
+    }
+
     fn set_style(&mut self, style: Style) -> io::Result<()> {
         self.set_background(style.background)?;
         self.set_fg(style.foreground)?;
@@ -533,9 +1029,49 @@ impl<W: Write> Canvas<W> {
         self.set_font_style(style.font_style)
     }
 
-    fn set_region_color(&mut self) -> io::Result<()> {
+    fn set_region_color(&mut self, style: Style) -> io::Result<()> {
+        let pattern_color = self.pattern_colors.get(self.region_pattern).copied();
+        if let Some(color) = self.match_underline {
+            // Keep the syntax colors and underline the region instead
+            self.set_match_style(style)?;
+            return self.set_region_underline(pattern_color.unwrap_or(color));
+        }
         self.set_fg(self.palette.region_fg)?;
-        self.set_bg(self.palette.region_bg)
+        self.set_bg(pattern_color.unwrap_or(self.palette.region_bg))
+    }
+
+    fn override_colors(&mut self, opts: &FileOptions) {
+        if self.palette.is_ansi16() {
+            return;
+        }
+        let rgb = |(r, g, b)| Color { r, g, b, a: 255 };
+        if let Some(bg) = opts.match_bg.map(rgb) {
+            self.palette.match_bg = bg;
+        }
+        if let Some(bg) = opts.region_bg.map(rgb) {
+            if self.transparent && self.match_underline == Some(self.palette.region_bg) {
+                self.match_underline = Some(bg); // Transparent mode underlines regions with the region color
+            }
+            self.palette.region_bg = bg;
+        }
+    }
+
+    fn set_region_underline(&mut self, color: Color) -> io::Result<()> {
+        self.set_underline()?;
+        if self.palette.is_ansi16() {
+            return Ok(()); // Terminals with 16 colors may not support underline colors. Fall back to plain underline
+        }
+        self.set_color(50, color) // 58 sets underline color
+    }
+
+    fn unset_region_underline(&mut self) -> io::Result<()> {
+        if self.match_underline.is_some() {
+            self.unset_underline()?;
+            if !self.palette.is_ansi16() {
+                self.out.write_all(b"\x1b[59m")?; // Reset underline color
+            }
+        }
+        Ok(())
     }
 
     fn set_gutter_color(&mut self) -> io::Result<()> {
@@ -549,7 +1085,8 @@ impl<W: Write> Canvas<W> {
     }
 
     fn fill_spaces(&mut self, written_width: usize, max_width: usize) -> io::Result<()> {
-        if written_width < max_width {
+        // Spaces are only for painting the background
+        if written_width < max_width && !self.transparent {
             self.draw_spaces(max_width - written_width)?;
         }
         Ok(())
@@ -580,27 +1117,51 @@ struct LineChars<'a> {
     horizontal: &'a str,
     vertical: &'a str,
     vertical_and_right: &'a str,
+    vertical_and_left: &'a str,
+    down_and_right: &'a str,
+    down_and_left: &'a str,
+    up_and_right: &'a str,
+    up_and_left: &'a str,
     down_and_horizontal: &'a str,
     up_and_horizontal: &'a str,
     dashed_horizontal: &'a str,
+    indent_guide: &'a str,
+    sparkline: [&'a str; 8],
+    match_indicator: &'a str,
 }
 
 const UNICODE_LINE_CHARS: LineChars<'static> = LineChars {
     horizontal: "─",
     vertical: "│",
     vertical_and_right: "├",
+    vertical_and_left: "┤",
+    down_and_right: "╭",
+    down_and_left: "╮",
+    up_and_right: "╰",
+    up_and_left: "╯",
     down_and_horizontal: "┬",
     up_and_horizontal: "┴",
     dashed_horizontal: "╶",
+    indent_guide: "╎",
+    sparkline: ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"],
+    match_indicator: "▶",
 };
 
 const ASCII_LINE_CHARS: LineChars<'static> = LineChars {
     horizontal: "-",
     vertical: "|",
     vertical_and_right: "|",
+    vertical_and_left: "|",
+    down_and_right: "+",
+    down_and_left: "+",
+    up_and_right: "+",
+    up_and_left: "+",
     down_and_horizontal: "-",
     up_and_horizontal: "-",
     dashed_horizontal: "-",
+    indent_guide: "|",
+    sparkline: ["|"; 8],
+    match_indicator: ">",
 };
 
 // Note: More flexible version of syntect::easy::HighlightLines for our use case
@@ -609,6 +1170,22 @@ struct LineHighlighter<'a> {
     parse_state: ParseState,
     hl_state: HighlightState,
     syntaxes: &'a SyntaxSet,
+    // Scopes of string literals and comments. Empty when the detection is disabled
+    literal_scopes: Vec<Scope>,
+    // Characters detected in the literal scopes
+    literal_chars: Vec<char>,
+    // Byte offsets of the tabs in the literal scopes in the last highlighted line
+    literal_tabs: Vec<usize>,
+    // Byte offsets of the brackets in the literal scopes in the last highlighted line
+    literal_brackets: Vec<usize>,
+    // When true, lines are not parsed and each line is one token in the default style of the theme
+    plain: bool,
+    // Start time and time budget of highlighting the file
+    budget: Option<(Instant, Duration)>,
+    // Number of lines checked with `over_budget`
+    lines: usize,
+    // Foreground color which overrides the colors of all tokens
+    fg: Option<Color>,
 }
 
 impl<'a> LineHighlighter<'a> {
@@ -621,81 +1198,376 @@ impl<'a> LineHighlighter<'a> {
             parse_state,
             hl_state,
             syntaxes,
+            literal_scopes: vec![],
+            literal_chars: vec![],
+            literal_tabs: vec![],
+            literal_brackets: vec![],
+            plain: false,
+            budget: None,
+            lines: 0,
+            fg: None,
+        }
+    }
+
+    fn set_foreground(&mut self, fg: Color) {
+        self.fg = Some(fg);
+    }
+
+    // Foreground color of a token. `fg` is the overriding color
+    fn foreground(fg: Option<Color>, style: &Style) -> Color {
+        fg.unwrap_or_else(|| blend_fg_color(style.foreground, style.background))
+    }
+
+    fn disable_highlight(&mut self) {
+        self.plain = true;
+    }
+
+    fn set_budget(&mut self, budget: Duration) {
+        self.budget = Some((Instant::now(), budget));
+    }
+
+    // Called on each line of the file. Returns true only once when highlighting the lines took longer than the budget.
+    // Then the following lines are not highlighted. Elapsed time is checked at intervals since it is not free
+    fn over_budget(&mut self) -> bool {
+        const CHECK_INTERVAL: usize = 16;
+
+        let Some((start, budget)) = self.budget else {
+            return false;
+        };
+        let line = self.lines;
+        self.lines += 1;
+        if self.plain || !line.is_multiple_of(CHECK_INTERVAL) || start.elapsed() < budget {
+            return false;
+        }
+        self.disable_highlight();
+        true
+    }
+
+    fn plain_tokens<'line>(&self, line: &'line str) -> Vec<Token<'line>> {
+        let mut style = self.hl.get_default();
+        style.foreground = Self::foreground(self.fg, &style);
+        vec![Token { style, text: line }]
+    }
+
+    // Pathological input may make the syntax produce no token for a non-empty line. Fall back to the plain text so
+    // that the content of the line is not silently dropped
+    fn or_plain_tokens<'line>(
+        &self,
+        line: &'line str,
+        tokens: Vec<Token<'line>>,
+    ) -> Vec<Token<'line>> {
+        if tokens.is_empty() && !line.is_empty() {
+            self.plain_tokens(line)
+        } else {
+            tokens
+        }
+    }
+
+    fn detect_literal_chars(&mut self, chars: &[char]) {
+        self.literal_chars.extend_from_slice(chars);
+        self.literal_scopes = ["string", "comment"]
+            .iter()
+            .map(|s| Scope::new(s).unwrap())
+            .collect();
+    }
+
+    fn detect_literal_tabs(&mut self) {
+        self.detect_literal_chars(&['\t']);
+    }
+
+    fn detect_literal_brackets(&mut self) {
+        self.detect_literal_chars(&BRACKETS);
+    }
+
+    // Find the detected characters in the literal scopes. This must be called before the highlight state is updated
+    // with the line
+    fn find_literal_chars(&self, line: &str, ops: &[(usize, ScopeStackOp)]) -> Vec<usize> {
+        let mut stack = self.hl_state.path.clone();
+        let mut ops = ops.iter().peekable();
+        let mut offsets = vec![];
+        for (idx, _) in line.match_indices(self.literal_chars.as_slice()) {
+            while let Some((_, op)) = ops.next_if(|(o, _)| *o <= idx) {
+                stack.apply(op);
+            }
+            let literal = stack
+                .as_slice()
+                .iter()
+                .any(|s| self.literal_scopes.iter().any(|l| l.is_prefix_of(*s)));
+            if literal {
+                offsets.push(idx);
+            }
         }
+        offsets
+    }
+
+    // Byte offsets of the tabs in string literals or comments in the last line passed to `highlight`
+    fn take_literal_tabs(&mut self) -> Vec<usize> {
+        mem::take(&mut self.literal_tabs)
+    }
+
+    // Byte offsets of the brackets in string literals or comments in the last line passed to `highlight`
+    fn take_literal_brackets(&mut self) -> Vec<usize> {
+        mem::take(&mut self.literal_brackets)
     }
 
     fn skip_line(&mut self, line: &str) {
+        if self.plain {
+            return;
+        }
         let ops = self.parse_state.parse_line(line, self.syntaxes);
         for _ in HighlightIterator::new(&mut self.hl_state, &ops, line, &self.hl) {}
     }
 
     fn highlight<'line>(&mut self, line: &'line str) -> Vec<Token<'line>> {
+        if self.plain {
+            return self.plain_tokens(line);
+        }
         let ops = self.parse_state.parse_line(line, self.syntaxes);
-        HighlightIterator::new(&mut self.hl_state, &ops, line, &self.hl)
+        if !self.literal_chars.is_empty() {
+            let offsets = self.find_literal_chars(line, &ops);
+            (self.literal_tabs, self.literal_brackets) = offsets
+                .into_iter()
+                .partition(|&i| line.as_bytes()[i] == b'\t');
+        }
+        let fg = self.fg;
+        let tokens = HighlightIterator::new(&mut self.hl_state, &ops, line, &self.hl)
             .map(|(mut style, text)| {
-                style.foreground = blend_fg_color(style.foreground, style.background);
+                style.foreground = Self::foreground(fg, &style);
                 Token { style, text }
             })
-            .collect()
+            .collect();
+        self.or_plain_tokens(line, tokens)
+    }
+
+    // Highlight a line which is not in the file (e.g. replaced line) in the current context. The states are not updated
+    // so that the following lines of the file are still highlighted correctly
+    fn highlight_detached<'line>(&self, line: &'line str) -> Vec<Token<'line>> {
+        if self.plain {
+            return self.plain_tokens(line);
+        }
+        let mut parse_state = self.parse_state.clone();
+        let mut hl_state = self.hl_state.clone();
+        let ops = parse_state.parse_line(line, self.syntaxes);
+        let tokens = HighlightIterator::new(&mut hl_state, &ops, line, &self.hl)
+            .map(|(mut style, text)| {
+                style.foreground = Self::foreground(self.fg, &style);
+                Token { style, text }
+            })
+            .collect();
+        self.or_plain_tokens(line, tokens)
     }
 }
 
-// Like chunk::Lines, but includes newlines
-struct LinesInclusive<'a> {
-    lnum: usize,
-    prev: usize,
-    buf: &'a [u8],
-    iter: Memchr<'a>,
+/// Hook to customize tokens of each line before drawing it. It receives the path of the file, the line number, and
+/// the highlighted tokens of the line. Note that the hook is called for every drawn line so it should be cheap.
+pub type LineHook = dyn Fn(&Path, u64, &mut Vec<(Style, &str)>) + Send + Sync;
+
+fn apply_line_hook<'line>(
+    hook: &LineHook,
+    path: &Path,
+    lnum: u64,
+    tokens: Vec<Token<'line>>,
+) -> Vec<Token<'line>> {
+    let mut parts = tokens.into_iter().map(|t| (t.style, t.text)).collect();
+    hook(path, lnum, &mut parts);
+    parts
+        .into_iter()
+        .map(|(style, text)| Token { style, text })
+        .collect()
 }
-impl<'a> LinesInclusive<'a> {
-    pub fn new(buf: &'a [u8]) -> Self {
-        Self {
-            lnum: 1,
-            prev: 0,
-            buf,
-            iter: memchr_iter(b'\n', buf),
-        }
-    }
-}
-impl<'a> Iterator for LinesInclusive<'a> {
-    type Item = Line<'a>;
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(idx) = self.iter.next() {
-            let lnum = self.lnum;
-            let end = idx + 1;
-            let line = &self.buf[self.prev..end];
-            self.prev = end;
-            self.lnum += 1;
-            Some(Line(line, lnum as u64))
-        } else if self.prev == self.buf.len() {
-            None
-        } else {
-            let line = &self.buf[self.prev..];
-            self.prev = self.buf.len();
-            Some(Line(line, self.lnum as u64))
-        }
+
+/// Source of the symbol enclosing each chunk such as a function signature. It receives the path of the file and the line
+/// number of the first matched line in the chunk (or the first line when the chunk has no matched line), and returns the
+/// symbol name like `fn parse_line`. The symbol is drawn as "in {symbol}" above the chunk. This is an integration point
+/// for external data like ctags or LSP. It is called once per chunk.
+pub type SymbolSource = dyn Fn(&Path, u64) -> Option<String> + Send + Sync;
+
+/// Cell of the extra gutter column drawn by [`GutterAnnotator`]. `fg` and `bg` are the colors of the text. `None` means
+/// the colors of the gutter.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StyledCell {
+    pub text: String,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+}
+
+/// Source of an extra gutter column drawn at the left of line numbers. External data per line such as test coverage,
+/// git blame authors, or lint severities can be shown in the column. Since it is called for every drawn line, it should
+/// be cheap.
+pub trait GutterAnnotator: Send + Sync {
+    /// Width of the column in cells. Longer texts are truncated to the width.
+    fn width(&self) -> usize;
+    /// Cell of the line at `lnum` of the file at `path`. `None` leaves the cell blank.
+    fn annotate(&self, _path: &Path, _lnum: u64) -> Option<StyledCell> {
+        None
+    }
+}
+
+// Terminals put tab stops at every 8 columns by default
+const TERMINAL_TAB_STOP: usize = 8;
+
+// Width which the cursor advances by a hard tab written at the column
+#[inline]
+fn hard_tab_width(column: usize) -> usize {
+    TERMINAL_TAB_STOP - column % TERMINAL_TAB_STOP
+}
+
+// Like grep and ripgrep, a file is considered binary when a NUL byte is found in its first bytes
+const BINARY_DETECTION_BYTES: usize = 8 * 1024;
+
+fn is_binary(contents: &[u8]) -> bool {
+    let len = cmp::min(contents.len(), BINARY_DETECTION_BYTES);
+    memchr(b'\0', &contents[..len]).is_some()
+}
+
+// Bucket the number of matches into 1..=8 in log scale: 1 => 1, 2..=3 => 2, 4..=7 => 3, ..., 128.. => 8
+fn sparkline_level(count: usize) -> usize {
+    cmp::min(count.max(1).ilog2() as usize + 1, 8)
+}
+
+// Number of lines in the contents of a file. The last line may not end with a newline
+fn count_lines(contents: &[u8]) -> usize {
+    let newlines = memchr_iter(b'\n', contents).count();
+    match contents.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
     }
 }
 
+// Brackets colorized by `--bracket-colors`. '<' and '>' are not included because they are often used as operators
+const BRACKETS: [char; 6] = ['(', ')', '[', ']', '{', '}'];
+
+// Colors of brackets for each nesting depth
+const BRACKET_COLORS: [Color; 3] = [
+    Color {
+        r: 0xff,
+        g: 0xd7,
+        b: 0x00,
+        a: 0xff,
+    },
+    Color {
+        r: 0xda,
+        g: 0x70,
+        b: 0xd6,
+        a: 0xff,
+    },
+    Color {
+        r: 0x17,
+        g: 0x9f,
+        b: 0xff,
+        a: 0xff,
+    },
+];
+const BRACKET_COLORS_16: [Color; 3] = [
+    Color {
+        r: 3, // Yellow
+        g: 0,
+        b: 0,
+        a: 0,
+    },
+    Color {
+        r: 5, // Magenta
+        g: 0,
+        b: 0,
+        a: 0,
+    },
+    Color {
+        r: 4, // Blue
+        g: 0,
+        b: 0,
+        a: 0,
+    },
+];
+
 // Drawer is responsible for one-time screen drawing
 struct Drawer<'file, W: Write> {
     grid: bool,
-    term_width: u16,
+    output_width: u16,
     lnum_width: u16,
     first_only: bool,
+    edge_separators: bool,
     wrap: bool,
     tab_width: u16,
+    align_tabs: bool,
+    h_scroll: usize,
+    separator_gap: bool,
+    header_style: HeaderStyle,
+    file_url: Option<String>,
+    indent_guides: bool,
+    match_sparkline: bool,
+    match_count: usize,
+    show_line_count: bool,
+    line_count: usize,
+    header_lines: HeaderLines,
+    // Summary of the line numbers in the header. See `PrinterOptions::header_lines`
+    lines_summary: Option<String>,
+    focus_match: bool,
+    show_eol: Option<char>,
+    numbering: Numbering,
+    // Start line numbers of chunks to count chunk-local line numbers. Empty when line numbers are absolute
+    chunk_starts: Vec<u64>,
+    overflow_tooltip: bool,
+    bracket_colors: bool,
+    bracket_depth: usize,
+    literal_brackets: Vec<usize>,
+    header_path_width: Option<usize>,
+    header_align: HeaderAlign,
+    auto_truncate_after: Option<usize>,
+    continuation_line_numbers: bool,
+    min_text_width: Option<u16>,
+    // Line numbers are drawn on their own rows since the terminal is too narrow. See `PrinterOptions::min_text_width`
+    stacked: bool,
+    collapse_repeats: bool,
+    // Line number of the last line collapsed into the line being drawn
+    collapsed_until: Option<u64>,
+    gutter_only: bool,
+    hanging_indent: bool,
+    match_indicator: bool,
+    separator_color: Option<Color>,
+    border: bool,
+    diff_mark: Option<char>,
+    invalid_utf8: InvalidUtf8,
+    literal_tabs: Vec<usize>,
+    region_patterns: Vec<usize>,
+    context_regions: Vec<(usize, usize)>,
+    match_emphasis: MatchEmphasis,
+    highlight_matches: bool,
     chars: LineChars<'file>,
     canvas: Canvas<W>,
+    line_hook: Option<&'file LineHook>,
+    annotator: Option<&'file dyn GutterAnnotator>,
+    symbol_source: Option<&'file SymbolSource>,
+    // Enclosing symbols of chunks as (first line, last line, symbol)
+    chunk_symbols: Vec<(u64, u64, String)>,
+    at_chunk_start: bool,
+    // Delimiter of fields of the file for the tabular layout
+    tabular: Option<char>,
+    // Path of the file passed to the annotator
+    path: PathBuf,
+    search_info: Option<SearchInfo>,
 }
 
 impl<'file, W: Write> Drawer<'file, W> {
     fn new(out: W, opts: &PrinterOptions<'_>, theme: &'file Theme, chunks: &[(u64, u64)]) -> Self {
-        let last_lnum = chunks.last().map(|(_, e)| *e).unwrap_or(0);
-        let mut lnum_width = num_digits(last_lnum);
-        if chunks.len() > 1 {
+        let (mut lnum_width, chunk_starts) = match opts.numbering {
+            Numbering::Absolute => {
+                let last_lnum = chunks.last().map(|(_, e)| *e).unwrap_or(0);
+                (num_digits(last_lnum), vec![])
+            }
+            Numbering::ChunkLocal => {
+                let longest = chunks.iter().map(|(s, e)| e - s + 1).max().unwrap_or(0);
+                (
+                    num_digits(longest),
+                    chunks.iter().map(|(s, _)| *s).collect(),
+                )
+            }
+        };
+        if chunks.len() > 1 || opts.reserve_separator_gutter || opts.edge_separators {
             lnum_width = cmp::max(lnum_width, 3); // Consider '...' in gutter
         }
+        if let Some(width) = opts.min_lnum_width {
+            lnum_width = cmp::max(lnum_width, width);
+        }
 
         let chars = if opts.ascii_lines {
             ASCII_LINE_CHARS
@@ -703,50 +1575,245 @@ impl<'file, W: Write> Drawer<'file, W> {
             UNICODE_LINE_CHARS
         };
 
-        Drawer {
+        // Lines are laid out inside the left and right sides of the border
+        let border = opts.border != BorderStyle::None && !opts.gutter_only;
+        let output_width = opts.output_width.unwrap_or(opts.term_width);
+        let output_width = if border {
+            output_width.saturating_sub(2)
+        } else {
+            output_width
+        };
+
+        Drawer {
             grid: opts.grid,
-            term_width: opts.term_width,
+            output_width,
             lnum_width,
             wrap: opts.text_wrap == TextWrapMode::Char,
             tab_width: opts.tab_width as u16,
-            first_only: opts.first_only,
+            align_tabs: opts.align_tabs,
+            h_scroll: opts.h_scroll,
+            separator_gap: opts.separator_gap,
+            header_style: opts.header_style,
+            file_url: None,
+            indent_guides: opts.indent_guides,
+            match_sparkline: opts.match_sparkline,
+            match_count: 0,
+            show_line_count: opts.show_line_count,
+            line_count: 0,
+            header_lines: opts.header_lines,
+            lines_summary: None,
+            focus_match: opts.focus_match,
+            show_eol: opts.show_eol,
+            numbering: opts.numbering,
+            chunk_starts,
+            overflow_tooltip: opts.overflow_tooltip,
+            bracket_colors: opts.bracket_colors,
+            bracket_depth: 0,
+            literal_brackets: vec![],
+            header_path_width: opts.header_path_width,
+            header_align: opts.header_align,
+            auto_truncate_after: opts.auto_truncate_after,
+            continuation_line_numbers: opts.continuation_line_numbers,
+            min_text_width: opts.min_text_width,
+            stacked: false,
+            collapse_repeats: opts.collapse_repeats,
+            collapsed_until: None,
+            gutter_only: opts.gutter_only,
+            hanging_indent: opts.hanging_indent,
+            match_indicator: opts.match_indicator,
+            separator_color: opts
+                .separator_color
+                .filter(|_| opts.color_support != TermColorSupport::Ansi16)
+                .map(|(r, g, b)| Color { r, g, b, a: 255 }),
+            border,
+            diff_mark: None,
+            invalid_utf8: opts.invalid_utf8,
+            literal_tabs: vec![],
+            region_patterns: vec![],
+            context_regions: vec![],
+            match_emphasis: opts.match_emphasis,
+            highlight_matches: opts.highlight_matches,
+            first_only: opts.first_only != FirstOnly::None,
+            edge_separators: opts.edge_separators,
             chars,
             canvas: Canvas::new(out, opts, theme),
+            line_hook: None,
+            annotator: None,
+            symbol_source: None,
+            chunk_symbols: vec![],
+            at_chunk_start: false,
+            tabular: None,
+            path: PathBuf::new(),
+            search_info: opts.search_info,
         }
     }
 
     #[inline]
     fn gutter_width(&self) -> u16 {
-        if self.grid {
+        let width = if self.grid {
             self.lnum_width + 4
         } else {
             self.lnum_width + 2
+        };
+        width + self.match_indicator as u16 + self.annotation_width()
+    }
+
+    // Width of the annotation column including the margin after it
+    fn annotation_width(&self) -> u16 {
+        self.annotator.map(|a| a.width() as u16 + 1).unwrap_or(0)
+    }
+
+    // `lnum` is `None` on the rows without line numbers such as wrapped rows
+    fn draw_annotation(&mut self, lnum: Option<u64>) -> io::Result<()> {
+        let Some(annotator) = self.annotator else {
+            return Ok(());
+        };
+        let width = annotator.width();
+        let Some(StyledCell { text, fg, bg }) =
+            lnum.and_then(|l| annotator.annotate(&self.path, l))
+        else {
+            self.canvas.set_default_bg()?;
+            return self.canvas.draw_spaces(width + 1);
+        };
+        match bg {
+            Some(bg) => self.canvas.set_bg(bg)?,
+            None => self.canvas.set_default_bg()?,
+        }
+        self.canvas
+            .set_fg(fg.unwrap_or(self.canvas.palette.gutter_fg))?;
+        let mut written = 0;
+        for c in text.chars() {
+            let w = c.width_cjk().unwrap_or(0);
+            if written + w > width {
+                break;
+            }
+            write!(self.canvas, "{}", c)?;
+            written += w;
+        }
+        self.canvas.draw_spaces(width - written)?;
+        if self.canvas.has_background {
+            self.canvas.set_default_bg()?;
+        } else {
+            self.canvas.unset_bg()?;
         }
+        self.canvas.write_all(b" ")
     }
 
-    fn draw_horizontal_line(&mut self, sep: &str) -> io::Result<()> {
+    // `edges` are the left and right ends of the line which are drawn on the border
+    fn draw_horizontal_line(&mut self, sep: &str, edges: (&str, &str)) -> io::Result<()> {
         self.canvas.set_gutter_color()?;
+        if self.border {
+            self.canvas.write_all(edges.0.as_bytes())?;
+        }
         let gutter_width = self.gutter_width();
         for _ in 0..gutter_width - 2 {
             self.canvas.write_all(self.chars.horizontal.as_bytes())?;
         }
         self.canvas.write_all(sep.as_bytes())?;
-        for _ in 0..self.term_width - gutter_width + 1 {
+        for _ in 0..self.output_width - gutter_width + 1 {
             self.canvas.write_all(self.chars.horizontal.as_bytes())?;
         }
+        if self.border {
+            self.canvas.write_all(edges.1.as_bytes())?;
+        }
+        self.canvas.draw_newline()
+    }
+
+    fn draw_left_border(&mut self) -> io::Result<()> {
+        if self.border {
+            self.canvas.set_gutter_color()?;
+            self.canvas.write_all(self.chars.vertical.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Rows must be filled to the terminal width before calling this so that the right side of the border is aligned
+    fn draw_newline(&mut self) -> io::Result<()> {
+        if self.border {
+            self.canvas.set_gutter_color()?;
+            self.canvas.write_all(self.chars.vertical.as_bytes())?;
+        }
         self.canvas.draw_newline()
     }
 
+    // Whether rows should be filled with spaces to the end
+    fn fills_rows(&self) -> bool {
+        self.canvas.has_background || self.border
+    }
+
+    // Spaces before the right side of the border are always drawn so that it is aligned even in transparent mode
+    fn fill_spaces(&mut self, written_width: usize, max_width: usize) -> io::Result<()> {
+        if self.border {
+            self.canvas
+                .draw_spaces(max_width.saturating_sub(written_width))
+        } else {
+            self.canvas.fill_spaces(written_width, max_width)
+        }
+    }
+
+    // Line number drawn in the gutter. `lnum` is always the line number in the file
+    fn numbered(&self, lnum: u64) -> u64 {
+        match self.numbering {
+            Numbering::Absolute => lnum,
+            Numbering::ChunkLocal => {
+                let idx = self.chunk_starts.partition_point(|s| *s <= lnum);
+                let start = idx
+                    .checked_sub(1)
+                    .map(|i| self.chunk_starts[i])
+                    .unwrap_or(1);
+                lnum - start + 1
+            }
+        }
+    }
+
+    // Label of the line number in the gutter. It is a range like `12-15` when the following lines are collapsed into
+    // the line
+    fn lnum_label(&self, lnum: u64) -> String {
+        let shown = self.numbered(lnum);
+        match self.collapsed_until {
+            Some(last) => format!("{}-{}", shown, self.numbered(last)),
+            None => shown.to_string(),
+        }
+    }
+
     fn draw_line_number(&mut self, lnum: u64, matched: bool) -> io::Result<()> {
+        if self.stacked {
+            self.draw_line_number_row(lnum, matched)?;
+        }
+        self.draw_left_border()?;
+        self.draw_annotation(Some(lnum))?;
         if matched {
             self.canvas.set_match_lnum_color()?;
         } else {
             self.canvas.set_gutter_color()?;
         }
-        let width = num_digits(lnum);
+        if self.match_indicator {
+            let marker = if matched {
+                self.chars.match_indicator
+            } else {
+                " "
+            };
+            self.canvas.write_all(marker.as_bytes())?;
+        }
+        let shown = self.lnum_label(lnum);
+        let width = if self.stacked { 0 } else { shown.len() as u16 };
         self.canvas
             .draw_spaces((self.lnum_width - width) as usize)?;
-        write!(self.canvas, " {}", lnum)?;
+        // '-' or '+' of replace previews is put at the left margin of the line number
+        let mark = self.diff_mark.unwrap_or(' ');
+        if let (Some(url), false) = (&self.file_url, self.stacked) {
+            // OSC 8 hyperlink wraps only the number. The escape sequences occupy no cell. The link always points to
+            // the line in the file
+            write!(
+                self.canvas,
+                "{}\x1b]8;;{}#L{}\x1b\\{}\x1b]8;;\x1b\\",
+                mark, url, lnum, shown
+            )?;
+        } else if self.stacked {
+            write!(self.canvas, "{}", mark)?; // The number was drawn on the row above
+        } else {
+            write!(self.canvas, "{}{}", mark, shown)?;
+        }
         if self.grid {
             if matched {
                 self.canvas.set_gutter_color()?;
@@ -758,46 +1825,311 @@ impl<'file, W: Write> Drawer<'file, W> {
         Ok(()) // Do not reset color because another color text will follow
     }
 
-    fn draw_wrapping_gutter(&mut self) -> io::Result<()> {
+    // Row only for the line number above the line in the stacked layout
+    fn draw_line_number_row(&mut self, lnum: u64, matched: bool) -> io::Result<()> {
+        self.draw_left_border()?;
+        if matched {
+            self.canvas.set_match_lnum_color()?;
+        } else {
+            self.canvas.set_gutter_color()?;
+        }
+        let shown = self.lnum_label(lnum);
+        write!(self.canvas, " {}", shown)?;
+        if self.fills_rows() {
+            let width = shown.len() + 1;
+            self.canvas.set_default_bg()?;
+            self.fill_spaces(width, self.output_width as usize)?;
+        }
+        self.draw_newline()
+    }
+
+    // Switch to the stacked layout when the text would be narrower than `min_text_width`. The layout is decided before
+    // drawing a file since the gutter width depends on the options set after creating the drawer such as annotator
+    fn decide_layout(&mut self) {
+        let Some(min_width) = self.min_text_width.filter(|_| !self.gutter_only) else {
+            return;
+        };
+        if self.stacked || self.output_width >= self.gutter_width() + min_width {
+            return;
+        }
+        self.stacked = true;
+        self.lnum_width = 0;
+    }
+
+    // `lnum` is the line number of the wrapped line. It is drawn only when `continuation_line_numbers` is enabled
+    fn draw_wrapping_gutter(&mut self, lnum: Option<u64>) -> io::Result<()> {
+        self.draw_left_border()?;
+        self.draw_annotation(None)?;
         self.canvas.set_gutter_color()?;
-        self.canvas.draw_spaces(self.lnum_width as usize + 2)?;
+        if self.match_indicator {
+            self.canvas.write_all(b" ")?;
+        }
+        match lnum.filter(|_| self.continuation_line_numbers && !self.stacked) {
+            Some(lnum) => {
+                let lnum = self.numbered(lnum);
+                // Same layout as `draw_line_number`. The number is dimmed to be distinguished from the first row
+                let width = num_digits(lnum);
+                self.canvas
+                    .draw_spaces((self.lnum_width - width) as usize + 1)?;
+                self.canvas.set_dim()?;
+                write!(self.canvas, "{}", lnum)?;
+                self.canvas.unset_bold()?; // This also unsets dim
+                self.canvas.write_all(b" ")?;
+            }
+            None => self.canvas.draw_spaces(self.lnum_width as usize + 2)?,
+        }
         if self.grid {
             write!(self.canvas, "{} ", self.chars.vertical)?;
         }
         Ok(())
     }
 
-    fn draw_separator_line(&mut self) -> io::Result<()> {
+    // Draw the whole text of a line which overflows the terminal on the following faint rows. Syntax colors are not
+    // applied to the rows
+    fn draw_overflow_rows(&mut self, text: &str) -> io::Result<()> {
+        let body_width = (self.output_width - self.gutter_width()) as usize;
+        let mut width = 0;
+        self.draw_overflow_gutter()?;
+        let mut saw_zwj = false;
+        for c in text.chars() {
+            let w = match c {
+                '\t' if self.tab_width > 0 && self.align_tabs => {
+                    self.tab_width as usize - width % self.tab_width as usize
+                }
+                '\t' if self.tab_width > 0 => self.tab_width as usize,
+                '\t' => hard_tab_width(width), // Expand hard tabs to keep the rows aligned with the gutter
+                '\u{200d}' => 0,
+                _ if saw_zwj => 0,
+                c => c.width_cjk().unwrap_or(0),
+            };
+            saw_zwj = c == '\u{200d}';
+            if width + w > body_width {
+                if self.fills_rows() {
+                    self.fill_spaces(width, body_width)?;
+                }
+                self.draw_newline()?;
+                self.draw_overflow_gutter()?;
+                width = 0;
+            }
+            if c == '\t' {
+                self.canvas.draw_spaces(w)?;
+            } else {
+                write!(self.canvas, "{}", c)?;
+            }
+            width += w;
+        }
+        if self.fills_rows() {
+            self.fill_spaces(width, body_width)?;
+        }
+        self.draw_newline()
+    }
+
+    fn draw_overflow_gutter(&mut self) -> io::Result<()> {
+        self.draw_wrapping_gutter(None)?;
+        self.canvas.set_dim()
+    }
+
+    // `skipped` is the number of lines between the previous chunk and the next chunk
+    fn draw_separator_line(&mut self, skipped: u64) -> io::Result<()> {
+        if self.gutter_only {
+            return Ok(()); // Rows must correspond to the lines
+        }
+        self.draw_left_border()?;
         self.canvas.set_gutter_color()?;
+        // Gutter in the stacked layout has no room for "..."
+        let dots = if self.stacked { "" } else { "..." };
         // + 1 for left margin and - 3 for length of "..."
-        let left_margin = self.lnum_width + 1 - 3;
+        let left_margin = self.lnum_width + 1 - dots.len() as u16
+            + self.match_indicator as u16
+            + self.annotation_width();
         self.canvas.draw_spaces(left_margin as usize)?;
+        if let Some(fg) = self.separator_color {
+            self.canvas.set_fg(fg)?;
+        }
+        self.canvas.write_all(dots.as_bytes())?;
         let w = if self.grid {
-            write!(self.canvas, "... {}", self.chars.vertical_and_right)?;
-            5
+            self.canvas.set_gutter_color()?;
+            write!(self.canvas, " {}", self.chars.vertical_and_right)?;
+            dots.len() as u16 + 2
         } else {
-            write!(self.canvas, "...")?;
-            3
+            dots.len() as u16
         };
         self.canvas.set_default_bg()?;
-        let body_width = self.term_width - left_margin - w; // This crashes when terminal width is smaller than gutter
+        let mut body_width = self.output_width - left_margin - w; // This crashes when terminal width is smaller than gutter
+        if self.separator_gap {
+            let label = if skipped == 1 {
+                " 1 line ".to_string()
+            } else {
+                format!(" {} lines ", skipped)
+            };
+            // Put the label after some leading dashes only when the whole label fits in the line
+            let leading = 4;
+            let label_width = label.len() as u16;
+            if leading + label_width <= body_width {
+                for _ in 0..leading {
+                    self.canvas
+                        .write_all(self.chars.dashed_horizontal.as_bytes())?;
+                }
+                self.canvas.write_all(label.as_bytes())?;
+                body_width -= leading + label_width;
+            }
+        }
         for _ in 0..body_width {
             self.canvas
                 .write_all(self.chars.dashed_horizontal.as_bytes())?;
         }
-        self.canvas.draw_newline()
+        self.draw_newline()
+    }
+
+    // Split brackets out of the tokens and color them by their nesting depth. The depth is tracked across the drawn lines
+    // of each chunk. Since the tokens are only split, byte offsets of matched regions are still valid. Brackets in
+    // string literals or comments are not colorized
+    fn colorize_brackets<'line>(&mut self, tokens: Vec<Token<'line>>) -> Vec<Token<'line>> {
+        let colors = if self.canvas.palette.is_ansi16() {
+            &BRACKET_COLORS_16
+        } else {
+            &BRACKET_COLORS
+        };
+        let literal = mem::take(&mut self.literal_brackets);
+        let mut offset = 0; // Byte offset of `rest` in the line
+        let mut colorized = Vec::with_capacity(tokens.len());
+        for Token { style, text } in tokens {
+            let mut rest = text;
+            while let Some(idx) = rest.find(BRACKETS) {
+                if literal.binary_search(&(offset + idx)).is_ok() {
+                    colorized.push(Token {
+                        style,
+                        text: &rest[..idx + 1],
+                    });
+                    offset += idx + 1;
+                    rest = &rest[idx + 1..];
+                    continue;
+                }
+                if idx > 0 {
+                    colorized.push(Token {
+                        style,
+                        text: &rest[..idx],
+                    });
+                }
+                let depth = match rest.as_bytes()[idx] {
+                    b'(' | b'[' | b'{' => {
+                        self.bracket_depth += 1;
+                        self.bracket_depth - 1
+                    }
+                    _ => {
+                        self.bracket_depth = self.bracket_depth.saturating_sub(1);
+                        self.bracket_depth
+                    }
+                };
+                let mut style = style;
+                style.foreground = colors[depth % colors.len()];
+                colorized.push(Token {
+                    style,
+                    text: &rest[idx..idx + 1],
+                });
+                offset += idx + 1;
+                rest = &rest[idx + 1..];
+            }
+            if !rest.is_empty() {
+                offset += rest.len();
+                colorized.push(Token { style, text: rest });
+            }
+        }
+        colorized
+    }
+
+    // Draw a guide in gutter color and restore the text color `fg`
+    fn draw_indent_guide(&mut self, fg: Color) -> io::Result<()> {
+        self.canvas.set_fg(self.canvas.palette.gutter_fg)?;
+        self.canvas.write_all(self.chars.indent_guide.as_bytes())?;
+        self.canvas.set_fg(fg)
+    }
+
+    // Draw `width` cells of leading indentation starting at the column. A guide is drawn at each indentation level
+    fn draw_indent(&mut self, column: usize, width: usize, fg: Color) -> io::Result<()> {
+        let step = if self.tab_width > 0 {
+            self.tab_width as usize
+        } else {
+            TERMINAL_TAB_STOP
+        };
+        for col in column..column + width {
+            if col % step == 0 {
+                self.draw_indent_guide(fg)?;
+            } else {
+                self.canvas.write_all(b" ")?;
+            }
+        }
+        Ok(())
+    }
+
+    // Drawn on the row after a line which was truncated by `auto_truncate_after`. `bytes` is the length of the rest
+    fn draw_truncated_notice(&mut self, bytes: usize) -> io::Result<()> {
+        self.draw_notice_row(&format!("({} more bytes truncated)", bytes))
+    }
+
+    // Draw the symbol enclosing the chunk before the first line of the chunk
+    fn draw_chunk_symbol(&mut self, lnum: u64) -> io::Result<()> {
+        if !mem::take(&mut self.at_chunk_start) {
+            return Ok(());
+        }
+        let Some((_, _, symbol)) = self
+            .chunk_symbols
+            .iter()
+            .find(|(first, last, _)| (*first..=*last).contains(&lnum))
+        else {
+            return Ok(());
+        };
+        let notice = format!("in {}", symbol);
+        self.draw_notice_row(&notice)
+    }
+
+    fn draw_notice_row(&mut self, notice: &str) -> io::Result<()> {
+        if self.gutter_only {
+            return Ok(()); // Rows must correspond to the lines
+        }
+        let body_width = (self.output_width - self.gutter_width()) as usize;
+        // Truncate the notice so that it doesn't wrap at the edge of the terminal. It may contain wide characters such as
+        // a symbol name given by `SymbolSource`
+        let mut width = 0;
+        let mut end = notice.len();
+        for (idx, c) in notice.char_indices() {
+            let w = c.width_cjk().unwrap_or(0);
+            if width + w > body_width {
+                end = idx;
+                break;
+            }
+            width += w;
+        }
+        self.draw_overflow_gutter()?;
+        self.canvas.set_fg(self.canvas.palette.gutter_fg)?;
+        self.canvas.write_all(&notice.as_bytes()[..end])?;
+        if self.fills_rows() {
+            self.fill_spaces(width, body_width)?;
+        }
+        self.draw_newline()
     }
 
     fn draw_text_wrappping(
         &mut self,
+        lnum: u64,
         matched: bool,
         style: Style,
         in_region: bool,
+        indent: usize,
     ) -> io::Result<()> {
-        self.canvas.draw_newline()?;
-        self.draw_wrapping_gutter()?;
+        self.draw_newline()?;
+        self.draw_wrapping_gutter(Some(lnum))?;
+        if indent > 0 {
+            // Hanging indent is painted with the background of the line, not of the matched region
+            if matched {
+                self.canvas.set_match_bg_color()?;
+            } else {
+                self.canvas.set_style(style)?;
+            }
+            self.canvas.draw_spaces(indent)?;
+        }
         if in_region {
-            self.canvas.set_region_color()
+            self.canvas.set_region_color(style)
         } else if matched {
             self.canvas.set_match_style(style)
         } else {
@@ -821,14 +2153,72 @@ impl<'file, W: Write> Drawer<'file, W> {
             }
         }
 
-        let body_width = (self.term_width - self.gutter_width()) as usize;
-        let matched = regions.is_some();
+        if self.gutter_only {
+            self.draw_line_number(lnum, regions.is_some())?;
+            self.collapsed_until = None;
+            self.context_regions.clear();
+            return self.canvas.draw_newline();
+        }
+
+        if self.bracket_colors {
+            tokens = self.colorize_brackets(tokens);
+        }
+
+        if let (true, Some(regions)) = (self.focus_match, &regions) {
+            if !regions.is_empty() {
+                tokens = dim_ranges(tokens, &ranges_between(regions));
+            }
+        }
+
+        let gutter_width = self.gutter_width() as usize;
+        let body_width = self.output_width as usize - gutter_width;
+        let text_column = gutter_width + self.border as usize; // Column of the terminal where the text starts
+        let clip = self.border && !self.wrap; // Cut overflowing text not to break the right side of the border
+        let is_match = regions.is_some();
+        // Whether the line is painted with the background of matched lines. Lines of replace previews are always painted
+        // with the background of diffs
+        let matched = match self.match_emphasis {
+            _ if self.diff_mark.is_some() => is_match,
+            _ if !self.highlight_matches => false,
+            MatchEmphasis::MatchedOnly => is_match,
+            MatchEmphasis::WholeChunk => true,
+            MatchEmphasis::None => false,
+        };
+        let overflow_text: Option<String> = (self.overflow_tooltip && !self.wrap && is_match)
+            .then(|| tokens.iter().map(|t| t.text).collect());
+        // Continuation rows of wrapped lines are indented to the first non-whitespace column. At least half of the body
+        // width remains for the text
+        let indent = if self.hanging_indent && self.wrap {
+            let mut indent = 0;
+            for c in tokens.iter().flat_map(|t| t.text.chars()) {
+                indent += match c {
+                    ' ' => 1,
+                    '\t' if self.tab_width == 0 => hard_tab_width(text_column + indent),
+                    '\t' if self.align_tabs => {
+                        self.tab_width as usize - indent % self.tab_width as usize
+                    }
+                    '\t' => self.tab_width as usize,
+                    _ => break,
+                };
+            }
+            cmp::min(indent, body_width / 2)
+        } else {
+            0
+        };
 
+        let literal_tabs = mem::take(&mut self.literal_tabs);
+        let patterns = mem::take(&mut self.region_patterns);
+        let context_regions = mem::take(&mut self.context_regions);
         let tokens = tokens.as_slice();
-        let regions = regions.as_ref().map(AsRef::as_ref).unwrap_or(&[][..]);
-        let mut events = DrawEvents::new(tokens, regions);
+        let regions = match &regions {
+            _ if !self.highlight_matches => &[][..],
+            Some(regions) => regions.as_slice(),
+            None => &context_regions[..],
+        };
+        let mut events = DrawEvents::new(tokens, regions, &patterns);
 
-        self.draw_line_number(lnum, matched)?;
+        self.draw_line_number(lnum, is_match)?;
+        let repeats = self.collapsed_until.take().map(|last| last - lnum + 1);
         if matched {
             self.canvas.set_match_style(events.current_style)?;
         } else if !tokens.is_empty() {
@@ -837,19 +2227,141 @@ impl<'file, W: Write> Drawer<'file, W> {
 
         let mut width = 0; // Text width written to terminal
         let mut saw_zwj = false;
+        let mut skipped = 0; // Text width skipped by horizontal scroll
+        let mut scrolling = self.h_scroll > 0;
+        let mut in_indent = self.indent_guides; // True while drawing leading whitespaces
+        let max_wrapped_rows = self.auto_truncate_after.unwrap_or(usize::MAX);
+        let mut wrapped_rows = 0;
+        let mut truncated_at = None; // Byte offset of the first character which was not drawn due to truncation
+        let mut clipped = false;
         loop {
-            match events.next_event() {
-                DrawEvent::Char('\t') if self.tab_width > 0 => {
-                    let w = self.tab_width as usize;
+            // With `--wrap never` and no border, the text overflowing the right edge is not cut. It is still written for
+            // pagers scrolling horizontally such as `less -S`, so drawing cannot stop at the edge like clipping by the
+            // border or truncation. Instead, the text past the edge is written at once since its width no longer matters.
+            // This avoids processing a huge token such as a base64 blob char by char
+            if !self.wrap && !clip && !scrolling && !in_indent && width > body_width {
+                let text = events.take_plain_text();
+                if !text.is_empty() {
+                    self.canvas.write_all(text.as_bytes())?;
+                    continue;
+                }
+            }
+            let event = events.next_event();
+            if let DrawEvent::Char(c) = event {
+                in_indent &= c == ' ' || c == '\t';
+            }
+            // Tabs in string literals or comments are written as-is like hard tabs
+            let expand_tab = self.tab_width > 0
+                && (literal_tabs.is_empty()
+                    || literal_tabs
+                        .binary_search(&(events.byte_offset - 1))
+                        .is_err());
+            if let (DrawEvent::Char(c), true) = (&event, scrolling) {
+                let w = match *c {
+                    '\t' if expand_tab => {
+                        let tab_width = self.tab_width as usize;
+                        if self.align_tabs {
+                            tab_width - skipped % tab_width
+                        } else {
+                            tab_width
+                        }
+                    }
+                    '\t' => hard_tab_width(text_column + skipped),
+                    '\u{200d}' => 0,
+                    _ if saw_zwj => 0,
+                    c => c.width_cjk().unwrap_or(0),
+                };
+                // Zero-width characters following skipped characters (e.g. combining characters) are also skipped
+                if skipped < self.h_scroll || w == 0 {
+                    saw_zwj = *c == '\u{200d}';
+                    if skipped + w <= self.h_scroll {
+                        skipped += w;
+                    } else {
+                        // The character lies across the left edge. Render its visible cells as spaces
+                        let visible = skipped + w - self.h_scroll;
+                        skipped = self.h_scroll;
+                        self.canvas.draw_spaces(visible)?;
+                        width += visible;
+                        scrolling = false;
+                    }
+                    continue;
+                }
+                scrolling = false;
+            }
+
+            match event {
+                DrawEvent::Char('\t') if expand_tab => {
+                    let tab_width = self.tab_width as usize;
+                    let w = if self.align_tabs {
+                        tab_width - (skipped + width) % tab_width // Advance to the next tab stop
+                    } else {
+                        tab_width
+                    };
+                    if width + w > body_width && clip {
+                        clipped = true;
+                        break;
+                    }
                     if width + w > body_width && self.wrap {
+                        if wrapped_rows == max_wrapped_rows {
+                            truncated_at = Some(events.byte_offset - 1);
+                            break;
+                        }
                         self.canvas.draw_spaces(body_width - width)?;
-                        self.draw_text_wrappping(matched, events.current_style, events.in_region)?;
-                        width = 0;
+                        self.draw_text_wrappping(
+                            lnum,
+                            matched,
+                            events.current_style,
+                            events.in_region,
+                            indent,
+                        )?;
+                        wrapped_rows += 1;
+                        width = indent;
+                        in_indent = false;
+                    } else if in_indent && !events.in_region {
+                        self.draw_indent(skipped + width, w, events.current_style.foreground)?;
+                        width += w;
                     } else {
                         self.canvas.draw_spaces(w)?;
                         width += w;
                     }
                 }
+                DrawEvent::Char('\t') => {
+                    // Hard tab: The tab character is written as-is and the terminal moves the cursor to its next tab stop.
+                    // Count the width the terminal advances so that text-wrapping and filling spaces remain correct.
+                    let mut w = hard_tab_width(text_column + width);
+                    if width + w > body_width && clip {
+                        clipped = true;
+                        break;
+                    }
+                    if width + w > body_width && self.wrap {
+                        if wrapped_rows == max_wrapped_rows {
+                            truncated_at = Some(events.byte_offset - 1);
+                            break;
+                        }
+                        self.canvas.draw_spaces(body_width - width)?;
+                        self.draw_text_wrappping(
+                            lnum,
+                            matched,
+                            events.current_style,
+                            events.in_region,
+                            indent,
+                        )?;
+                        wrapped_rows += 1;
+                        width = indent;
+                        w = hard_tab_width(text_column + indent);
+                        in_indent = false;
+                    }
+                    if in_indent && !events.in_region {
+                        // Each hard tab in indentation is one indentation level
+                        self.draw_indent_guide(events.current_style.foreground)?;
+                        if w > 1 {
+                            self.canvas.write_all(b"\t")?; // The tab still moves the cursor to the same tab stop
+                        }
+                    } else {
+                        self.canvas.write_all(b"\t")?;
+                    }
+                    width += w;
+                }
                 DrawEvent::Char(c) => {
                     // Handle zero width joiner
                     let w = if c == '\u{200d}' {
@@ -861,16 +2373,37 @@ impl<'file, W: Write> Drawer<'file, W> {
                     } else {
                         c.width_cjk().unwrap_or(0)
                     };
+                    if width + w > body_width && clip {
+                        clipped = true;
+                        break;
+                    }
                     if width + w > body_width && self.wrap {
+                        if wrapped_rows == max_wrapped_rows {
+                            truncated_at = Some(events.byte_offset - c.len_utf8());
+                            break;
+                        }
                         self.canvas.draw_spaces(body_width - width)?;
-                        self.draw_text_wrappping(matched, events.current_style, events.in_region)?;
-                        width = 0;
+                        self.draw_text_wrappping(
+                            lnum,
+                            matched,
+                            events.current_style,
+                            events.in_region,
+                            indent,
+                        )?;
+                        wrapped_rows += 1;
+                        width = indent;
+                        in_indent = false;
+                    }
+                    if in_indent && !events.in_region {
+                        self.draw_indent(skipped + width, w, events.current_style.foreground)?;
+                    } else {
+                        write!(self.canvas, "{}", c)?;
                     }
-                    write!(self.canvas, "{}", c)?;
                     width += w;
                 }
                 DrawEvent::TokenBoundary(prev_style) => {
-                    if !events.in_region {
+                    let underline = self.canvas.match_underline.filter(|_| events.in_region);
+                    if !events.in_region || underline.is_some() {
                         self.canvas.unset_font_style(prev_style.font_style)?;
                         if !matched {
                             self.canvas
@@ -880,119 +2413,1062 @@ impl<'file, W: Write> Drawer<'file, W> {
                         self.canvas
                             .set_font_style(events.current_style.font_style)?;
                     }
+                    if let Some(color) = underline {
+                        if prev_style.font_style.contains(FontStyle::UNDERLINE) {
+                            self.canvas.set_region_underline(color)?; // Underline was unset by the previous font style
+                        }
+                    }
                 }
                 DrawEvent::RegionStart => {
-                    self.canvas.set_region_color()?;
+                    self.canvas.region_pattern = events.region_pattern();
+                    self.canvas.set_region_color(events.current_style)?;
                 }
                 DrawEvent::RegionEnd => {
-                    self.canvas.set_match_style(events.current_style)?;
+                    self.canvas.unset_region_underline()?;
+                    if matched {
+                        self.canvas.set_match_style(events.current_style)?;
+                    } else {
+                        if !self.canvas.has_background {
+                            self.canvas.unset_bg()?; // Background of the region remains without this
+                        }
+                        self.canvas.set_style(events.current_style)?;
+                    }
                 }
                 DrawEvent::Done => break,
             }
         }
 
+        // The EOL marker and the count of collapsed lines follow the text. They are not drawn when the end of the line is
+        // hidden by truncation, clipping or horizontal scroll
+        let mut trailer = self.show_eol.map(String::from).unwrap_or_default();
+        if let Some(n) = repeats {
+            trailer.push_str(&format!(" (×{})", n));
+        }
+        if let (false, None, false, false) = (trailer.is_empty(), truncated_at, clipped, scrolling)
+        {
+            let w = trailer.width_cjk();
+            let overflow = width + w > body_width;
+            let visible = if overflow && self.wrap && wrapped_rows < max_wrapped_rows {
+                self.canvas.draw_spaces(body_width - width)?;
+                self.draw_text_wrappping(lnum, matched, events.current_style, false, indent)?;
+                width = indent;
+                true
+            } else {
+                !overflow || !self.wrap && !clip
+            };
+            if visible {
+                self.canvas
+                    .unset_font_style(events.current_style.font_style)?;
+                self.canvas.set_default_fg()?;
+                self.canvas.set_dim()?;
+                self.canvas.write_all(trailer.as_bytes())?;
+                self.canvas.unset_bold()?; // This also unsets dim
+                width += w;
+            }
+        }
+
         if matched {
             self.canvas.set_match_bg_color()?;
         } else if width == 0 {
             self.canvas.set_default_bg()?;
         }
-        if self.canvas.has_background || matched {
-            self.canvas.fill_spaces(width, body_width)?;
+        if self.fills_rows() || matched {
+            self.fill_spaces(width, body_width)?;
         }
 
-        self.canvas.draw_newline()
+        self.draw_newline()?;
+
+        if let Some(offset) = truncated_at {
+            let len: usize = tokens.iter().map(|t| t.text.len()).sum();
+            self.draw_truncated_notice(len - offset)?;
+        }
+
+        match overflow_text {
+            Some(text) if width > body_width || skipped > 0 || clipped => {
+                self.draw_overflow_rows(&text)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Draw a line of replace preview. It is drawn as a matched line with the background of diffs
+    fn draw_diff_line(
+        &mut self,
+        tokens: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Vec<(usize, usize)>,
+        mark: char,
+        bg: Color,
+    ) -> io::Result<()> {
+        let match_bg = self.canvas.palette.match_bg;
+        self.canvas.palette.match_bg = bg;
+        self.diff_mark = Some(mark);
+        let ret = self.draw_line(tokens, lnum, Some(regions));
+        self.canvas.palette.match_bg = match_bg;
+        self.diff_mark = None;
+        ret
+    }
+
+    // Draw the matched line and its replacement stacked like unified diff. Matched regions are highlighted only in the
+    // line before the replacement
+    fn draw_replaced_line(
+        &mut self,
+        old: Vec<Token<'_>>,
+        new: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Vec<(usize, usize)>,
+    ) -> io::Result<()> {
+        let depth = self.bracket_depth;
+        let removed_bg = self.canvas.palette.removed_bg;
+        self.draw_diff_line(old, lnum, regions, '-', removed_bg)?;
+        // Brackets in the replacement start from the same depth. The following lines continue from the original line
+        let depth_after = mem::replace(&mut self.bracket_depth, depth);
+        let added_bg = self.canvas.palette.added_bg;
+        self.draw_diff_line(new, lnum, vec![], '+', added_bg)?;
+        self.bracket_depth = depth_after;
+        Ok(())
     }
 
-    fn draw_body(&mut self, file: &File, mut hl: LineHighlighter<'_>) -> io::Result<()> {
-        assert!(!file.chunks.is_empty());
+    fn draw_header(&mut self, path: &Path) -> io::Result<()> {
+        self.decide_layout();
+        if self.gutter_only {
+            return Ok(());
+        }
+        let top = (self.chars.down_and_right, self.chars.down_and_left);
+        self.draw_horizontal_line(self.chars.horizontal, top)?;
+        self.draw_header_path(path, None)?;
+        if self.grid {
+            let edges = (self.chars.vertical_and_right, self.chars.vertical_and_left);
+            self.draw_horizontal_line(self.chars.down_and_horizontal, edges)?;
+        }
+        Ok(())
+    }
 
-        let mut matched = file.line_matches.as_ref();
-        let mut chunks = file.chunks.iter();
-        let mut chunk = chunks.next().unwrap(); // OK since chunks is not empty
+    fn draw_header_path(&mut self, path: &Path, notice: Option<&str>) -> io::Result<()> {
+        let HeaderStyle {
+            fg,
+            bg,
+            bold,
+            underline,
+        } = self.header_style;
+        let rgb = |(r, g, b)| Color { r, g, b, a: 255 };
+        let is_ansi16 = self.canvas.palette.is_ansi16();
+        let bg = bg.filter(|_| !is_ansi16).map(rgb);
+        let fg = fg.filter(|_| !is_ansi16).map(rgb);
+
+        self.draw_left_border()?;
+        if let Some(bg) = bg {
+            self.canvas.set_bg(bg)?;
+        } else {
+            self.canvas.set_default_bg()?;
+        }
+        if let Some(fg) = fg {
+            self.canvas.set_fg(fg)?;
+        } else {
+            self.canvas.set_default_fg()?;
+        }
 
-        for Line(bytes, lnum) in LinesInclusive::new(&file.contents) {
-            let (start, end) = *chunk;
-            if lnum < start {
-                hl.skip_line(String::from_utf8_lossy(bytes).as_ref()); // Discard parsed result
-                continue;
+        let path = path.as_os_str().to_string_lossy();
+        let path_width = path.width_cjk();
+        let padding = self
+            .header_path_width
+            .map(|w| w.saturating_sub(path_width))
+            .unwrap_or(0);
+        let sparkline_level = (self.match_sparkline && self.match_count > 0)
+            .then(|| sparkline_level(self.match_count));
+        let line_count =
+            (self.show_line_count && self.line_count > 0).then(|| match self.line_count {
+                1 => "1 line".to_string(),
+                n => format!("{} lines", n),
+            });
+        let note = self.search_info.as_ref().and_then(SearchInfo::note);
+        let lines_summary = self.lines_summary.clone();
+        let notes = line_count
+            .as_deref()
+            .into_iter()
+            .chain(lines_summary.as_deref())
+            .chain(note)
+            .chain(notice);
+
+        let mut width = 0;
+        if self.header_align == HeaderAlign::Right {
+            // + 1 for the left margin and + 1 for the right margin
+            let content_width = 1
+                + path_width
+                + padding
+                + sparkline_level.map(|l| l + 1).unwrap_or(0)
+                + notes.clone().map(|n| n.width_cjk() + 3).sum::<usize>()
+                + 1;
+            let output_width = self.output_width as usize;
+            if content_width < output_width {
+                width = output_width - content_width;
+                self.canvas.draw_spaces(width)?;
             }
-            if start <= lnum && lnum <= end {
-                let regions = match matched.split_first() {
-                    Some((m, ms)) if m.line_number == lnum => {
-                        matched = ms;
-                        Some(m.ranges.clone()) // XXX: Cannot move out ranges in line match
-                    }
-                    _ => None,
-                };
-                let line = String::from_utf8_lossy(bytes);
-                // Collect to `Vec` rather than handing HighlightIterator as-is. HighlightIterator takes ownership of Highlighter
-                // while the iteration. When the highlighter is stored in `self`, it means the iterator takes ownership of `self`.
-                self.draw_line(hl.highlight(line.as_ref()), lnum, regions)?;
+        }
 
-                if lnum == end {
-                    if self.first_only {
-                        break;
-                    }
-                    if let Some(c) = chunks.next() {
-                        self.draw_separator_line()?;
-                        chunk = c;
-                    } else {
-                        break;
-                    }
-                }
+        if bold {
+            self.canvas.set_bold()?;
+        }
+        if underline {
+            self.canvas.set_underline()?;
+        }
+        write!(self.canvas, " {}", path)?;
+        width += path_width + 1;
+        if underline {
+            self.canvas.unset_underline()?; // Don't underline notes and trailing spaces
+        }
+
+        if padding > 0 {
+            // Align the following sparkline and notes across files
+            self.canvas.draw_spaces(padding)?;
+            width += padding;
+        }
+
+        if let Some(level) = sparkline_level {
+            // Rising bar whose length grows with the number of matched lines
+            self.canvas.set_fg(self.canvas.palette.match_lnum_fg)?;
+            self.canvas.write_all(b" ")?;
+            for glyph in &self.chars.sparkline[..level] {
+                self.canvas.write_all(glyph.as_bytes())?;
             }
+            width += level + 1;
         }
 
-        Ok(())
+        for note in notes {
+            self.canvas.unset_bold()?;
+            self.canvas.set_fg(self.canvas.palette.gutter_fg)?;
+            write!(self.canvas, " ({})", note)?;
+            width += note.width_cjk() + 3;
+        }
+        if self.fills_rows() || bg.is_some() {
+            self.fill_spaces(width, self.output_width as usize)?;
+        }
+        self.draw_newline()
     }
 
-    fn draw_header(&mut self, path: &Path) -> io::Result<()> {
-        self.draw_horizontal_line(self.chars.horizontal)?;
+    // One line of the index of files. `matches` is the number of matched lines in the file
+    fn draw_index_line(&mut self, path: &Path, matches: usize) -> io::Result<()> {
         self.canvas.set_default_bg()?;
-        let path = path.as_os_str().to_string_lossy();
         self.canvas.set_default_fg()?;
-        self.canvas.set_bold()?;
-        write!(self.canvas, " {}", path)?;
+        let path = path.as_os_str().to_string_lossy();
+        write!(self.canvas, "{}", path)?;
+        self.canvas.set_fg(self.canvas.palette.gutter_fg)?;
+        let note = if matches == 1 {
+            " (1 match)".to_string()
+        } else {
+            format!(" ({} matches)", matches)
+        };
+        self.canvas.write_all(note.as_bytes())?;
         if self.canvas.has_background {
-            self.canvas
-                .fill_spaces(path.width_cjk() + 1, self.term_width as usize)?;
+            let width = path.width_cjk() + note.len();
+            self.fill_spaces(width, self.output_width as usize)?;
         }
-        self.canvas.draw_newline()?;
-        if self.grid {
-            self.draw_horizontal_line(self.chars.down_and_horizontal)?;
+        self.canvas.draw_newline()
+    }
+
+    // Draw only the header with the reason why the file contents are not rendered
+    fn draw_skipped_file(&mut self, path: &Path, notice: &str) -> io::Result<()> {
+        let top = (self.chars.down_and_right, self.chars.down_and_left);
+        self.draw_horizontal_line(self.chars.horizontal, top)?;
+        self.draw_header_path(path, Some(notice))?;
+        let bottom = (self.chars.up_and_right, self.chars.up_and_left);
+        if self.grid || self.border {
+            self.draw_horizontal_line(self.chars.horizontal, bottom)?;
         }
         Ok(())
     }
 
     fn draw_footer(&mut self) -> io::Result<()> {
+        if self.gutter_only {
+            return Ok(());
+        }
+        let bottom = (self.chars.up_and_right, self.chars.up_and_left);
         if self.grid {
-            self.draw_horizontal_line(self.chars.up_and_horizontal)?;
+            self.draw_horizontal_line(self.chars.up_and_horizontal, bottom)?;
+        } else if self.border {
+            self.draw_horizontal_line(self.chars.horizontal, bottom)?;
         }
         Ok(())
     }
 
     fn draw_file(&mut self, file: &File, hl: LineHighlighter) -> io::Result<()> {
-        self.draw_header(&file.path)?;
-        self.draw_body(file, hl)?;
+        self.match_count = file.line_matches.len();
+        if self.show_line_count {
+            self.line_count = count_lines(&file.contents);
+        }
+        self.lines_summary = match self.header_lines {
+            HeaderLines::None => None,
+            HeaderLines::Matches => {
+                let lnums = file.line_matches.iter().map(|m| m.line_number);
+                summarize_lines(lnums.map(|n| (n, n)))
+            }
+            HeaderLines::Chunks => summarize_lines(file.chunks.iter().copied()),
+        };
+        self.bracket_depth = 0;
+        if let Some(source) = self.symbol_source {
+            let lmats = &file.line_matches;
+            self.chunk_symbols = merge_overlapping_chunks(&file.chunks)
+                .into_iter()
+                .filter_map(|(first, last)| {
+                    let idx = lmats.partition_point(|m| m.line_number < first);
+                    let lnum = match lmats.get(idx) {
+                        Some(m) if m.line_number <= last => m.line_number,
+                        _ => first,
+                    };
+                    source(&file.path, lnum).map(|symbol| (first, last, symbol))
+                })
+                .collect();
+            self.at_chunk_start = true;
+        }
+        // Each row in the gutter-only mode corresponds to one line so lines are not collapsed
+        let repeats = if self.collapse_repeats && !self.gutter_only {
+            repeated_runs(file)
+        } else {
+            vec![]
+        };
+        // Make room for the ranges of collapsed line numbers in the gutter
+        for &(first, last) in &repeats {
+            let width = num_digits(self.numbered(first)) + num_digits(self.numbered(last)) + 1;
+            self.lnum_width = cmp::max(self.lnum_width, width);
+        }
+        let opts = RenderOptions {
+            first_only: self.first_only,
+            edge_separators: self.edge_separators,
+            invalid_utf8: self.invalid_utf8,
+            line_hook: self.line_hook,
+            repeats: &repeats,
+            tabular: self.tabular,
+        };
+        render_file(self, file, hl, opts)
+    }
+}
+
+impl<'file, W: Write> RenderSink for Drawer<'file, W> {
+    fn header(&mut self, path: &Path) -> io::Result<()> {
+        self.draw_header(path)
+    }
+
+    fn line(
+        &mut self,
+        tokens: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Option<Vec<(usize, usize)>>,
+    ) -> io::Result<()> {
+        self.draw_chunk_symbol(lnum)?;
+        self.draw_line(tokens, lnum, regions)
+    }
+
+    fn replaced_line(
+        &mut self,
+        old: Vec<Token<'_>>,
+        new: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Vec<(usize, usize)>,
+    ) -> io::Result<()> {
+        self.draw_chunk_symbol(lnum)?;
+        self.draw_replaced_line(old, new, lnum, regions)
+    }
+
+    fn separator(&mut self, skipped: u64) -> io::Result<()> {
+        self.at_chunk_start = self.symbol_source.is_some();
+        self.bracket_depth = 0; // Brackets in the skipped lines are unknown
+        self.draw_separator_line(skipped)
+    }
+
+    fn footer(&mut self) -> io::Result<()> {
         self.draw_footer()
     }
+
+    fn literal_tabs(&mut self, offsets: Vec<usize>) {
+        self.literal_tabs = offsets;
+    }
+
+    fn literal_brackets(&mut self, offsets: Vec<usize>) {
+        self.literal_brackets = offsets;
+    }
+
+    fn region_patterns(&mut self, patterns: Vec<usize>) {
+        self.region_patterns = patterns;
+    }
+
+    fn context_regions(&mut self, regions: Vec<(usize, usize)>) {
+        self.context_regions = regions;
+    }
+
+    fn repeated(&mut self, last: u64) {
+        self.collapsed_until = Some(last);
+    }
+
+    fn highlight_stopped(&mut self) -> io::Result<()> {
+        self.draw_notice_row("(highlighting took too long, the rest is drawn as plain text)")
+    }
+
+    fn missing_lines(&mut self, ranges: &[(u64, u64)]) -> io::Result<()> {
+        match summarize_lines(ranges.iter().copied()) {
+            Some(lines) => self.draw_notice_row(&format!("({} no longer present)", lines)),
+            None => Ok(()),
+        }
+    }
+}
+
+// Receiver of the parts of a file in drawing order. `Drawer` draws them to terminal and `EventSink` converts them into
+// `RenderEvent`s
+trait RenderSink {
+    fn header(&mut self, path: &Path) -> io::Result<()>;
+    fn line(
+        &mut self,
+        tokens: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Option<Vec<(usize, usize)>>,
+    ) -> io::Result<()>;
+    // Matched line which has a replacement. `old` is the line in the file and `new` is the line after replacement
+    fn replaced_line(
+        &mut self,
+        old: Vec<Token<'_>>,
+        new: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Vec<(usize, usize)>,
+    ) -> io::Result<()>;
+    // `skipped` is the number of lines between the previous chunk and the next chunk
+    fn separator(&mut self, skipped: u64) -> io::Result<()>;
+    fn footer(&mut self) -> io::Result<()>;
+    // Byte offsets of the tabs which should be written as-is in the next line
+    fn literal_tabs(&mut self, _offsets: Vec<usize>) {}
+    // Byte offsets of the brackets which should not be colorized in the next line
+    fn literal_brackets(&mut self, _offsets: Vec<usize>) {}
+    // Pattern index of each matched region in the next line
+    fn region_patterns(&mut self, _patterns: Vec<usize>) {}
+    // Occurrences of the search pattern in the next line which is not a matched line. See `File::context_regions`
+    fn context_regions(&mut self, _regions: Vec<(usize, usize)>) {}
+    // The next line stands for the identical lines following it until the line number `last`
+    fn repeated(&mut self, _last: u64) {}
+    // Highlighting exceeded the time budget and the following lines are not highlighted
+    fn highlight_stopped(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    // Line ranges of the chunks which are beyond the end of the file. This happens when the file was modified after it
+    // was searched
+    fn missing_lines(&mut self, _ranges: &[(u64, u64)]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Summarize sorted line ranges like "lines 12,40-42". Overlapping or adjacent ranges are coalesced. `None` means no line
+fn summarize_lines(ranges: impl Iterator<Item = (u64, u64)>) -> Option<String> {
+    let mut coalesced: Vec<(u64, u64)> = vec![];
+    for (start, end) in ranges {
+        match coalesced.last_mut() {
+            Some((_, e)) if start <= e.saturating_add(1) => *e = cmp::max(*e, end),
+            _ => coalesced.push((start, end)),
+        }
+    }
+    match coalesced.as_slice() {
+        [] => return None,
+        [(s, e)] if s == e => return Some(format!("line {}", s)),
+        _ => {}
+    }
+    let ranges: Vec<_> = coalesced
+        .iter()
+        .map(|&(s, e)| {
+            if s == e {
+                s.to_string()
+            } else {
+                format!("{}-{}", s, e)
+            }
+        })
+        .collect();
+    Some(format!("lines {}", ranges.join(",")))
+}
+
+// Chunks of `File` may share some lines when they are not built by `Files` (e.g. they are built by library users). Merge
+// such chunks so that the same line is not drawn twice. The chunks are assumed to be sorted by their start lines
+fn merge_overlapping_chunks(chunks: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(chunks.len());
+    for &(start, end) in chunks {
+        match merged.last_mut() {
+            Some((_, e)) if start <= *e => *e = cmp::max(*e, end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+// Ranges of different patterns may overlap. Split them into sorted ranges which don't overlap. On the overlapped part, the
+// range later in `ranges` wins. Adjacent parts of the same pattern are joined
+fn flatten_pattern_ranges(
+    ranges: &[(usize, usize)],
+    patterns: &[usize],
+) -> (Vec<(usize, usize)>, Vec<usize>) {
+    let mut offsets: Vec<_> = ranges.iter().flat_map(|&(s, e)| [s, e]).collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+
+    let mut flattened: Vec<(usize, usize)> = vec![];
+    let mut flattened_patterns = vec![];
+    for w in offsets.windows(2) {
+        let (start, end) = (w[0], w[1]);
+        let Some(idx) = ranges.iter().rposition(|&(s, e)| s <= start && end <= e) else {
+            continue;
+        };
+        let pattern = patterns.get(idx).copied().unwrap_or(0);
+        match (flattened.last_mut(), flattened_patterns.last()) {
+            (Some((_, e)), Some(&p)) if *e == start && p == pattern => *e = end,
+            _ => {
+                flattened.push((start, end));
+                flattened_patterns.push(pattern);
+            }
+        }
+    }
+    (flattened, flattened_patterns)
+}
+
+// Runs of consecutive identical lines in each chunk as pairs of the first and the last line numbers. Matched lines and
+// unmatched lines are not in the same run. Matched lines with replacements are never collapsed
+fn repeated_runs(file: &File) -> Vec<(u64, u64)> {
+    let chunks = merge_overlapping_chunks(&file.chunks);
+    let line_match = |lnum: u64| {
+        let idx = file
+            .line_matches
+            .binary_search_by_key(&lnum, |m| m.line_number)
+            .ok()?;
+        Some(&file.line_matches[idx])
+    };
+
+    let mut runs = vec![];
+    let mut run: Option<(&[u8], bool, u64, u64)> = None; // Text, matched, first and last line numbers of current run
+    let mut chunks = chunks.iter().peekable();
+    for Line(bytes, lnum) in LinesInclusive::new(&file.contents) {
+        while chunks.next_if(|(_, end)| *end < lnum).is_some() {}
+        let Some(&&(start, _)) = chunks.peek() else {
+            break;
+        };
+        if lnum < start {
+            continue;
+        }
+        let text = trim_line_ending(bytes);
+        let m = line_match(lnum);
+        let collapsible = m.is_none_or(|m| m.replacement.is_none());
+        match &mut run {
+            // Runs don't continue across chunks
+            Some((t, matched, _, last))
+                if lnum != start && collapsible && *t == text && *matched == m.is_some() =>
+            {
+                *last = lnum;
+            }
+            _ => {
+                runs.extend(run.filter(|r| r.2 < r.3).map(|r| (r.2, r.3)));
+                run = collapsible.then_some((text, m.is_some(), lnum, lnum));
+            }
+        }
+    }
+    runs.extend(run.filter(|r| r.2 < r.3).map(|r| (r.2, r.3)));
+    runs
+}
+
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+// Options of `render_file` which don't depend on the sink
+struct RenderOptions<'a> {
+    first_only: bool,
+    edge_separators: bool,
+    invalid_utf8: InvalidUtf8,
+    line_hook: Option<&'a LineHook>,
+    // Runs of identical lines collapsed into their first lines. See `repeated_runs`
+    repeats: &'a [(u64, u64)],
+    // Delimiter of fields when the fields are aligned into columns. See `align_fields`
+    tabular: Option<char>,
 }
 
-fn load_themes(name: Option<&str>) -> Result<ThemeSet> {
-    let bat_defaults: ThemeSet = load_bat_themes()?;
-    match name {
-        None => Ok(bat_defaults),
-        Some(name) if bat_defaults.themes.contains_key(name) => Ok(bat_defaults),
-        Some(name) => {
-            let defaults = ThemeSet::load_defaults();
-            if defaults.themes.contains_key(name) {
-                Ok(defaults)
+// Highlight the lines in the chunks of the file and hand them to the sink
+fn render_file<S: RenderSink>(
+    sink: &mut S,
+    file: &File,
+    mut hl: LineHighlighter<'_>,
+    opts: RenderOptions<'_>,
+) -> io::Result<()> {
+    assert!(!file.chunks.is_empty());
+    let RenderOptions {
+        first_only,
+        edge_separators,
+        invalid_utf8,
+        line_hook,
+        mut repeats,
+        tabular,
+    } = opts;
+
+    sink.header(&file.path)?;
+
+    let mut matched = file.line_matches.as_ref();
+    let chunks = merge_overlapping_chunks(&file.chunks);
+    let columns = match tabular {
+        Some(delimiter) => column_widths(&file.contents, &chunks, delimiter),
+        None => vec![],
+    };
+    let mut chunks = chunks.iter();
+    let mut chunk = chunks.next().unwrap(); // OK since chunks is not empty
+    let mut chunk_idx = 0;
+    let mut stopped = false;
+    let mut last_drawn = 0;
+    let mut collapsed_until = 0; // Lines until this line number are collapsed into the line drawn before
+    let mut done = false; // All chunks were drawn before reaching the end of the file
+
+    // Lines before the first chunk. Nothing is skipped when the chunk starts at the top of the file
+    if edge_separators && chunk.0 > 1 {
+        sink.separator(chunk.0 - 1)?;
+    }
+
+    for Line(bytes, lnum) in LinesInclusive::new(&file.contents) {
+        stopped |= hl.over_budget();
+        let (start, end) = *chunk;
+        if lnum < start {
+            hl.skip_line(String::from_utf8_lossy(bytes).as_ref()); // Discard parsed result
+            continue;
+        }
+        if lnum <= collapsed_until {
+            hl.skip_line(String::from_utf8_lossy(bytes).as_ref());
+            last_drawn = lnum;
+            if let Some((_, ms)) = matched.split_first().filter(|(m, _)| m.line_number == lnum) {
+                matched = ms;
+            }
+        } else if lnum <= end {
+            if mem::take(&mut stopped) {
+                sink.highlight_stopped()?;
+            }
+            let (regions, patterns, replacement) = match matched.split_first() {
+                Some((m, ms)) if m.line_number == lnum => {
+                    matched = ms;
+                    // XXX: Cannot move out ranges in line match
+                    let (ranges, patterns) = if m.patterns.is_empty() {
+                        (m.ranges.clone(), vec![])
+                    } else {
+                        flatten_pattern_ranges(&m.ranges, &m.patterns)
+                    };
+                    (Some(ranges), patterns, m.replacement.as_deref())
+                }
+                _ => (None, vec![], None),
+            };
+            let in_context = regions.is_none();
+            let regions = regions.or_else(|| {
+                let regions = file.context_regions();
+                let idx = regions
+                    .binary_search_by_key(&lnum, |m| m.line_number)
+                    .ok()?;
+                Some(regions[idx].ranges.clone())
+            });
+            let mut decoded = decode_line(bytes, regions, invalid_utf8);
+            if let (Some(delimiter), Some(widths)) = (tabular, columns.get(chunk_idx)) {
+                decoded = align_fields(decoded, widths, delimiter);
+            }
+            let DecodedLine {
+                text: line,
+                mut regions,
+                escapes,
+            } = decoded;
+            // The highlighter requires a newline at the end of line
+            let replacement = replacement.map(|r| {
+                let mut r = String::from_utf8_lossy(r).into_owned();
+                if !r.ends_with('\n') {
+                    r.push('\n');
+                }
+                r
+            });
+            // Highlight the replacement before the line since it is highlighted in the context before the line
+            let new_tokens = replacement.as_deref().map(|r| {
+                let tokens = hl.highlight_detached(r);
+                match line_hook {
+                    Some(hook) => apply_line_hook(hook, &file.path, lnum, tokens),
+                    None => tokens,
+                }
+            });
+            // Collect to `Vec` rather than handing HighlightIterator as-is. HighlightIterator takes ownership of Highlighter
+            // while the iteration. When the highlighter is stored in `self`, it means the iterator takes ownership of `self`.
+            let mut tokens = dim_ranges(hl.highlight(line.as_ref()), &escapes);
+            sink.literal_tabs(hl.take_literal_tabs());
+            sink.literal_brackets(hl.take_literal_brackets());
+            sink.region_patterns(patterns);
+            if in_context {
+                if let Some(regions) = regions.take() {
+                    sink.context_regions(regions);
+                }
+            }
+            if let Some(hook) = line_hook {
+                tokens = apply_line_hook(hook, &file.path, lnum, tokens);
+            }
+            if let Some(((_, last), rs)) = repeats.split_first().filter(|(r, _)| r.0 == lnum) {
+                sink.repeated(*last);
+                collapsed_until = *last;
+                repeats = rs;
+            }
+            if let Some(new_tokens) = new_tokens {
+                sink.replaced_line(tokens, new_tokens, lnum, regions.unwrap_or_default())?;
+            } else {
+                sink.line(tokens, lnum, regions)?;
+            }
+            last_drawn = lnum;
+        }
+
+        if lnum == end {
+            if first_only {
+                done = true;
+                break;
+            }
+            if let Some(c) = chunks.next() {
+                sink.separator(c.0.saturating_sub(end + 1))?;
+                chunk = c;
+                chunk_idx += 1;
             } else {
-                let msg = format!("Unknown theme '{}'. See --list-themes output", name);
-                Err(PrintError::new(msg).into())
+                done = true;
+                break;
+            }
+        }
+    }
+
+    // The rest of the chunks point beyond the end of the file
+    if !done {
+        let num_lines = count_lines(&file.contents) as u64;
+        let rest = if first_only { None } else { Some(chunks) };
+        let missing: Vec<_> = iter::once(chunk)
+            .chain(rest.into_iter().flatten())
+            .map(|&(s, e)| (cmp::max(s, num_lines + 1), e))
+            .filter(|(s, e)| s <= e)
+            .collect();
+        sink.missing_lines(&missing)?;
+    }
+
+    // Lines after the last drawn line. Nothing is skipped when the chunk reaches the end of the file
+    if edge_separators {
+        let skipped = (count_lines(&file.contents) as u64).saturating_sub(last_drawn);
+        if skipped > 0 {
+            sink.separator(skipped)?;
+        }
+    }
+
+    sink.footer()
+}
+
+/// Options which are applied only to one file printed by [`SyntectPrinter::print_with`]. They override the options of
+/// the printer and the colors of the theme. For example, a tool searching multiple patterns can paint the matches of
+/// each pattern with its own color. Colors are RGB values and ignored on terminals which support only 16 colors.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct FileOptions {
+    /// Background color of matched regions. `None` means the color of the theme
+    pub region_bg: Option<(u8, u8, u8)>,
+    /// Background color of matched lines. `None` means the color of the theme
+    pub match_bg: Option<(u8, u8, u8)>,
+}
+
+/// Part of text in a line with its highlight style. `in_match` is true when the text is in a matched region.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StyledSpan {
+    pub text: String,
+    pub style: Style,
+    pub in_match: bool,
+}
+
+/// Line returned from [`SyntectPrinter::highlight_file`]. The line number, whether the line is matched, and the text of
+/// the line split into spans with their highlight styles.
+pub type HighlightedLine = (u64, bool, Vec<(Style, String)>);
+
+/// Structured event of rendering a file produced by [`SyntectPrinter::render`]. Applications such as TUIs can draw them
+/// with their own widgets instead of parsing the bytes printed to terminal.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RenderEvent {
+    /// Start of the file. `notice` is set when the file contents are not rendered (e.g. a binary file).
+    Header {
+        path: PathBuf,
+        notice: Option<String>,
+    },
+    /// Line in a chunk. `matched` is true when the line is a matched line. The spans don't contain a newline.
+    Line {
+        lnum: u64,
+        matched: bool,
+        spans: Vec<StyledSpan>,
+    },
+    /// Replacement of the matched line which was sent as the previous `Line` event. The spans don't contain a newline.
+    Replacement { lnum: u64, spans: Vec<StyledSpan> },
+    /// Separator between two chunks. `skipped` is the number of lines not included in the chunks. When
+    /// [`PrinterOptions::edge_separators`] is enabled, it is also sent before the first chunk and after the last chunk.
+    Separator { skipped: u64 },
+    /// End of the file.
+    Footer,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum LineKind {
+    Context,
+    Matched,
+    Removed, // Matched line which has a replacement
+    Added,   // Replacement of the matched line
+}
+
+// Row of the documents printed by the printers of other formats than terminal
+pub(crate) enum RenderedRow {
+    Line {
+        lnum: u64,
+        kind: LineKind,
+        spans: Vec<StyledSpan>,
+    },
+    Separator,
+    Notice(String),
+}
+
+struct EventSink<F: FnMut(RenderEvent)> {
+    on_event: F,
+}
+
+impl<F: FnMut(RenderEvent)> RenderSink for EventSink<F> {
+    fn header(&mut self, path: &Path) -> io::Result<()> {
+        (self.on_event)(RenderEvent::Header {
+            path: path.to_path_buf(),
+            notice: None,
+        });
+        Ok(())
+    }
+
+    fn line(
+        &mut self,
+        mut tokens: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Option<Vec<(usize, usize)>>,
+    ) -> io::Result<()> {
+        if let Some(tok) = tokens.last_mut() {
+            tok.chomp();
+        }
+        let matched = regions.is_some();
+        let regions = regions.unwrap_or_default();
+
+        // Split tokens at the boundaries of matched regions
+        let mut spans = vec![];
+        let mut offset = 0;
+        for Token { style, text } in tokens {
+            let mut rest = text;
+            while !rest.is_empty() {
+                let region = regions.iter().find(|(s, e)| *s <= offset && offset < *e);
+                let boundary = match region {
+                    Some((_, e)) => *e,
+                    None => regions
+                        .iter()
+                        .map(|(s, _)| *s)
+                        .filter(|s| *s > offset)
+                        .min()
+                        .unwrap_or(usize::MAX),
+                };
+                let mut len = cmp::min(rest.len(), boundary - offset);
+                while !rest.is_char_boundary(len) {
+                    len += 1;
+                }
+                spans.push(StyledSpan {
+                    text: rest[..len].to_string(),
+                    style,
+                    in_match: region.is_some(),
+                });
+                rest = &rest[len..];
+                offset += len;
+            }
+        }
+
+        (self.on_event)(RenderEvent::Line {
+            lnum,
+            matched,
+            spans,
+        });
+        Ok(())
+    }
+
+    fn replaced_line(
+        &mut self,
+        old: Vec<Token<'_>>,
+        mut new: Vec<Token<'_>>,
+        lnum: u64,
+        regions: Vec<(usize, usize)>,
+    ) -> io::Result<()> {
+        self.line(old, lnum, Some(regions))?;
+        if let Some(tok) = new.last_mut() {
+            tok.chomp();
+        }
+        let spans = new
+            .into_iter()
+            .filter(|t| !t.text.is_empty())
+            .map(|Token { style, text }| StyledSpan {
+                text: text.to_string(),
+                style,
+                in_match: false,
+            })
+            .collect();
+        (self.on_event)(RenderEvent::Replacement { lnum, spans });
+        Ok(())
+    }
+
+    fn separator(&mut self, skipped: u64) -> io::Result<()> {
+        (self.on_event)(RenderEvent::Separator { skipped });
+        Ok(())
+    }
+
+    fn footer(&mut self) -> io::Result<()> {
+        (self.on_event)(RenderEvent::Footer);
+        Ok(())
+    }
+}
+
+/// Printer which highlights and prints the lines of a stream one by one as they arrive (e.g. `tail -f log | ...`). It is
+/// created by [`SyntectPrinter::stream`]. The highlighting state is kept across lines so that constructs spanning
+/// multiple lines such as block comments are highlighted correctly. Since the total number of lines is unknown, line
+/// numbers are drawn in [`StreamPrinter::lnum_width`] columns and the gutter grows when a line number needs more columns.
+pub struct StreamPrinter<'a, W: Write> {
+    drawer: Drawer<'a, W>,
+    hl: LineHighlighter<'a>,
+    path: PathBuf,
+    lnum: u64,
+    skipped: u64,
+    started: bool,
+}
+
+impl<'a, W: Write> StreamPrinter<'a, W> {
+    /// Draw line numbers in at least `width` columns. The default width is 4. Setting this before pushing lines avoids
+    /// the gutter growing in the middle of the stream.
+    pub fn lnum_width(&mut self, width: u16) {
+        self.drawer.lnum_width = width;
+    }
+
+    /// Line number of the last line pushed or skipped. 0 means no line has been given yet.
+    pub fn line_number(&self) -> u64 {
+        self.lnum
+    }
+
+    /// Highlight the next line of the stream and print it. `ranges` are the byte ranges of the matched regions in the
+    /// line and `None` means the line is not matched. The output is flushed so that the line is visible immediately.
+    pub fn push_line(
+        &mut self,
+        line: &[u8],
+        ranges: Option<Vec<(usize, usize)>>,
+    ) -> io::Result<()> {
+        self.lnum += 1;
+        let lnum = self.lnum;
+        if self.started && self.skipped > 0 {
+            if !self.drawer.stacked {
+                self.drawer.lnum_width = cmp::max(self.drawer.lnum_width, 3); // Consider '...' in gutter
+            }
+            self.drawer.draw_separator_line(self.skipped)?;
+        }
+        if self.drawer.numbering == Numbering::ChunkLocal && (!self.started || self.skipped > 0) {
+            self.drawer.chunk_starts.push(lnum);
+        }
+        self.skipped = 0;
+        if !self.drawer.stacked {
+            let width = num_digits(self.drawer.numbered(lnum));
+            self.drawer.lnum_width = cmp::max(self.drawer.lnum_width, width);
+        }
+        if !self.started {
+            self.drawer.draw_header(&self.path)?;
+            self.started = true;
+        }
+
+        // The highlighter requires a newline at the end of line
+        let mut bytes = line.to_vec();
+        if !bytes.ends_with(b"\n") {
+            bytes.push(b'\n');
+        }
+        let DecodedLine {
+            text,
+            regions,
+            escapes,
+        } = decode_line(&bytes, ranges, self.drawer.invalid_utf8);
+        let mut tokens = dim_ranges(self.hl.highlight(text.as_ref()), &escapes);
+        self.drawer.literal_tabs = self.hl.take_literal_tabs();
+        self.drawer.literal_brackets = self.hl.take_literal_brackets();
+        if let Some(hook) = self.drawer.line_hook {
+            tokens = apply_line_hook(hook, &self.path, lnum, tokens);
+        }
+        self.drawer.draw_line(tokens, lnum, regions)?;
+        self.drawer.canvas.flush()
+    }
+
+    /// Skip the next line of the stream without printing it. The line is still parsed to keep the highlighting state
+    /// correct. A separator is drawn before the next printed line like the gap between chunks of a file.
+    pub fn skip_line(&mut self, line: &[u8]) {
+        self.lnum += 1;
+        self.skipped += 1;
+        let mut line = String::from_utf8_lossy(line).into_owned();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        self.hl.skip_line(&line);
+    }
+
+    /// Finish the stream by drawing the footer. Nothing is printed when no line was pushed.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.started {
+            self.drawer.draw_footer()?;
+        }
+        self.drawer.canvas.flush()
+    }
+}
+
+// A theme in `names` which is not available is an error unless some theme in `fallbacks` is available. When `bat_assets`
+// is true, themes which are not bundled are also looked for in bat's directories
+fn load_themes<'a>(
+    names: impl Iterator<Item = &'a str>,
+    fallbacks: &[&str],
+    bat_assets: bool,
+) -> Result<ThemeSet> {
+    load_themes_with(load_bat_themes()?, names, fallbacks, bat_assets)
+}
+
+// Like `load_themes`, but `themes` are the bundled themes loaded in advance
+fn load_themes_with<'a>(
+    mut themes: ThemeSet,
+    names: impl Iterator<Item = &'a str>,
+    fallbacks: &[&str],
+    bat_assets: bool,
+) -> Result<ThemeSet> {
+    let mut defaults = None;
+    let mut user_themes = None;
+    let mut unknown = None;
+    for (name, is_fallback) in names
+        .map(|n| (n, false))
+        .chain(fallbacks.iter().map(|n| (*n, true)))
+    {
+        if themes.themes.contains_key(name) {
+            continue;
+        }
+        let defaults = defaults.get_or_insert_with(ThemeSet::load_defaults);
+        let theme = defaults.themes.remove(name).or_else(|| {
+            let user_themes = user_themes.get_or_insert_with(|| {
+                if bat_assets {
+                    load_user_bat_themes(bat_cache_dir().as_deref(), bat_config_dir().as_deref())
+                } else {
+                    ThemeSet::new()
+                }
+            });
+            user_themes.themes.remove(name)
+        });
+        match theme {
+            Some(theme) => {
+                themes.themes.insert(name.to_string(), theme);
             }
+            None if is_fallback => {}
+            None => {
+                unknown.get_or_insert(name);
+            }
+        }
+    }
+    if let Some(name) = unknown {
+        if !fallbacks.iter().any(|n| themes.themes.contains_key(*n)) {
+            return Err(HgrepError::UnknownTheme(name.to_string()));
         }
     }
+    Ok(themes)
+}
+
+fn default_theme_name(color_support: TermColorSupport) -> &'static str {
+    if color_support == TermColorSupport::Ansi16 {
+        "ansi"
+    } else {
+        "Monokai Extended" // Our 25bit -> 8bit color conversion works really well with this colorscheme
+    }
 }
 
 pub struct SyntectAssets {
@@ -1004,7 +3480,7 @@ impl SyntectAssets {
     pub fn load(theme: Option<&str>) -> Result<Self> {
         Ok(Self {
             syntax_set: load_syntax_set()?,
-            theme_set: load_themes(theme)?,
+            theme_set: load_themes(theme.into_iter(), &[], false)?,
         })
     }
 }
@@ -1029,6 +3505,17 @@ where
     syntaxes: SyntaxSet,
     themes: ThemeSet,
     opts: PrinterOptions<'main>,
+    line_hook: Option<Box<LineHook>>,
+    gutter_annotator: Option<Box<dyn GutterAnnotator>>,
+    symbol_source: Option<Box<SymbolSource>>,
+    syntax_mappings: Vec<(GlobMatcher, &'main str)>,
+    // Whether some file was already printed. This is checked while the writer is locked to put a form feed between files
+    printed_file: AtomicBool,
+    // Whether the newline at the end of the last printed file was held back since `trailing_newline` is disabled. It is
+    // written before the next file so that only the end of the whole output has no newline
+    newline_pending: AtomicBool,
+    // Captured once on creation so that printing does not depend on the current directory. Tests overwrite this
+    cwd: Option<PathBuf>,
 }
 
 impl<'main> SyntectPrinter<'main, Stdout> {
@@ -1042,20 +3529,65 @@ where
     for<'a> W: LockableWrite<'a>,
 {
     pub fn new(writer: W, opts: PrinterOptions<'main>) -> Result<Self> {
+        let mut syntaxes = load_syntax_set()?;
+        if opts.bat_assets {
+            let (cache_dir, config_dir) = (bat_cache_dir(), bat_config_dir());
+            syntaxes =
+                with_user_bat_syntaxes(syntaxes, cache_dir.as_deref(), config_dir.as_deref());
+        }
+        // Syntect's default themes and user's themes are loaded only when they are requested. Bundled themes still need
+        // to be deserialized in order, but the ones which are never used by this printer are not kept
+        let builtin_theme = default_theme_name(opts.color_support);
+        let bundled = load_bat_themes_with(|name| {
+            name == builtin_theme
+                || opts.default_theme == Some(name)
+                || opts.theme_names().any(|n| n == name)
+                || opts.theme_fallbacks.contains(&name)
+        })?;
+        let mut themes = load_themes_with(
+            bundled,
+            opts.theme_names(),
+            &opts.theme_fallbacks,
+            opts.bat_assets,
+        )?;
+        if let Some(name) = opts.default_theme {
+            // Loading a theme only as a fallback never fails even if it is not found. See `unknown_default_theme`
+            themes = load_themes_with(themes, iter::empty(), &[name], opts.bat_assets)?;
+        }
+        check_language(opts.language, &syntaxes)?;
         Ok(Self {
             writer,
-            syntaxes: load_syntax_set()?,
-            themes: load_themes(opts.theme)?,
+            syntax_mappings: build_syntax_mappings(&opts.syntax_mappings, &syntaxes)?,
+            syntaxes,
+            themes,
             opts,
+            line_hook: None,
+            gutter_annotator: None,
+            symbol_source: None,
+            printed_file: AtomicBool::new(false),
+            newline_pending: AtomicBool::new(false),
+            cwd: env::current_dir().ok(),
         })
     }
 
+    /// Create a printer with preloaded assets. This panics when `opts.syntax_mappings` contains an invalid mapping or
+    /// `opts.language` is unknown. Use [`SyntectPrinter::new`] to handle the errors.
     pub fn with_assets(assets: SyntectAssets, writer: W, opts: PrinterOptions<'main>) -> Self {
+        check_language(opts.language, &assets.syntax_set).unwrap();
+        let syntax_mappings =
+            build_syntax_mappings(&opts.syntax_mappings, &assets.syntax_set).unwrap();
         Self {
             writer,
             syntaxes: assets.syntax_set,
             themes: assets.theme_set,
             opts,
+            line_hook: None,
+            gutter_annotator: None,
+            symbol_source: None,
+            syntax_mappings,
+            printed_file: AtomicBool::new(false),
+            newline_pending: AtomicBool::new(false),
+            cwd: env::current_dir().ok(),
         }
     }
 
@@ -1063,74 +3595,642 @@ where
         &mut self.writer
     }
 
-    fn theme(&self) -> &Theme {
-        let name = self.opts.theme.unwrap_or_else(|| {
-            if self.opts.color_support == TermColorSupport::Ansi16 {
-                "ansi"
-            } else {
-                "Monokai Extended" // Our 25bit -> 8bit color conversion works really well with this colorscheme
-            }
-        });
-        &self.themes.themes[name]
+    /// Return `opts.default_theme` when it was not found. The built-in default theme is used instead of it.
+    pub fn unknown_default_theme(&self) -> Option<&'main str> {
+        self.opts
+            .default_theme
+            .filter(|name| !self.themes.themes.contains_key(*name))
     }
 
-    fn find_syntax(&self, path: &Path) -> Result<&SyntaxReference> {
-        let name = match path.extension().and_then(OsStr::to_str) {
-            Some("fs") => Some("F#"),
-            Some("h") => Some("C++"),
-            Some("pac") => Some("JavaScript (Babel)"),
-            _ => None,
-        };
-        let name = name.or_else(|| match path.file_name().and_then(OsStr::to_str) {
-            Some(".clang-format") => Some("YAML"),
-            _ => None,
-        });
-        if let Some(syntax) = name.and_then(|n| self.syntaxes.find_syntax_by_name(n)) {
-            return Ok(syntax);
-        }
+    /// Set a hook called before drawing each line. The hook can modify styles and texts of the tokens in the line.
+    /// For example, it can dim TODO comments or make a search term bold. Since the hook is called for every drawn
+    /// line, heavy work in the hook directly slows down printing.
+    pub fn line_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&Path, u64, &mut Vec<(Style, &str)>) + Send + Sync + 'static,
+    {
+        self.line_hook = Some(Box::new(hook));
+    }
 
-        Ok(self
-            .syntaxes
-            .find_syntax_for_file(path)?
-            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text()))
+    /// Set an annotator which draws an extra column in the gutter of each file. See [`GutterAnnotator`].
+    pub fn gutter_annotator<A: GutterAnnotator + 'static>(&mut self, annotator: A) {
+        self.gutter_annotator = Some(Box::new(annotator));
     }
-}
 
-impl<'main, W> Printer for SyntectPrinter<'main, W>
-where
-    for<'a> W: LockableWrite<'a>,
-{
-    fn print(&self, file: File) -> Result<()> {
-        use crate::io::IgnoreBrokenPipe;
+    /// Set a source of the symbol enclosing each chunk. The symbol is drawn above the chunk. See [`SymbolSource`].
+    pub fn symbol_source<F>(&mut self, source: F)
+    where
+        F: Fn(&Path, u64) -> Option<String> + Send + Sync + 'static,
+    {
+        self.symbol_source = Some(Box::new(source));
+    }
 
-        if file.chunks.is_empty() || file.line_matches.is_empty() {
-            return Ok(());
+    fn theme_name(&self) -> &'main str {
+        let name = self
+            .opts
+            .theme_name()
+            .or_else(|| {
+                self.opts
+                    .default_theme
+                    .filter(|name| self.themes.themes.contains_key(*name))
+            })
+            .unwrap_or_else(|| default_theme_name(self.opts.color_support));
+        if self.themes.themes.contains_key(name) {
+            return name;
         }
+        self.opts
+            .theme_fallbacks
+            .iter()
+            .copied()
+            .find(|n| self.themes.themes.contains_key(*n))
+            .unwrap_or(name)
+    }
 
-        let mut buf = vec![];
-        let theme = self.theme();
-        let syntax = self.find_syntax(&file.path)?;
+    pub(crate) fn theme(&self) -> &Theme {
+        &self.themes.themes[self.theme_name()]
+    }
 
-        let hl = LineHighlighter::new(syntax, theme, &self.syntaxes);
-        Drawer::new(&mut buf, &self.opts, theme, &file.chunks).draw_file(&file, hl)?;
+    pub(crate) fn options(&self) -> &PrinterOptions<'main> {
+        &self.opts
+    }
 
-        // Take lock here to print files in serial from multiple threads
-        let mut output = self.writer.lock();
-        output.write_all(&buf).ignore_broken_pipe()?;
-        Ok(output.flush()?)
+    /// Return true when no syntax is found for the file and it is highlighted as plain text. `contents` is used to
+    /// detect the syntax from its first line such as shebang when the path is not enough. Pass an empty slice when
+    /// the contents are not available yet.
+    pub fn is_plain_text(&self, path: &Path, contents: &[u8]) -> bool {
+        let syntax = self.find_syntax(path, contents, None);
+        std::ptr::eq(syntax, self.syntaxes.find_syntax_plain_text())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::chunk::{File, LineMatch};
-    use lazy_static::lazy_static;
-    use std::cell::{RefCell, RefMut};
-    use std::fmt;
-    use std::fs;
-    use std::mem;
-    use std::path::PathBuf;
+    /// Explain which syntax and theme are used for printing the file and why the syntax was chosen. This is useful
+    /// for debugging wrong syntax highlighting.
+    pub fn explain(&self, path: &Path, contents: &[u8]) -> Explanation {
+        let (syntax, reason) = self.detect_syntax(path, contents, None);
+        Explanation {
+            path: path.to_path_buf(),
+            syntax: syntax.name.clone(),
+            reason,
+            theme: self.theme_name().to_string(),
+        }
+    }
+
+    // Slice the rows in the window. Each row on terminal ends with newline since text-wrapping inserts newlines and
+    // colors are set again at the start of each row
+    fn rows_in_window<'buf>(&self, buf: &'buf [u8]) -> &'buf [u8] {
+        let row_start = |row: usize| {
+            if row == 0 {
+                return 0;
+            }
+            memchr_iter(b'\n', buf)
+                .nth(row - 1)
+                .map(|i| i + 1)
+                .unwrap_or(buf.len())
+        };
+        let start = row_start(self.opts.scroll_offset);
+        let end = match self.opts.window_height {
+            Some(height) => row_start(self.opts.scroll_offset + height),
+            None => buf.len(),
+        };
+        &buf[start..end]
+    }
+
+    // `language` is the name or the extension of the language which overrides the detection
+    fn find_syntax(
+        &self,
+        path: &Path,
+        contents: &[u8],
+        language: Option<&str>,
+    ) -> &SyntaxReference {
+        self.detect_syntax(path, contents, language).0
+    }
+
+    fn detect_syntax(
+        &self,
+        path: &Path,
+        contents: &[u8],
+        language: Option<&str>,
+    ) -> (&SyntaxReference, SyntaxReason) {
+        let syntax = language
+            .into_iter()
+            .chain(self.opts.language)
+            .find_map(|l| find_language(&self.syntaxes, l));
+        if let Some(syntax) = syntax {
+            return (syntax, SyntaxReason::Language);
+        }
+
+        // Detect syntax from the member's path when the file is in an archive
+        let path = archive_member_path(path).unwrap_or(path);
+        // Detect syntax from the real path when the file is at some revision
+        let path = revision_path(path, self.cwd.as_deref()).unwrap_or(path);
+
+        let file_name = path.file_name().and_then(OsStr::to_str).unwrap_or("");
+        let mapped = self
+            .syntax_mappings
+            .iter()
+            .find(|(glob, _)| glob.is_match(file_name) || glob.is_match(path))
+            .and_then(|(glob, name)| Some((self.syntaxes.find_syntax_by_name(name)?, glob)));
+        if let Some((syntax, glob)) = mapped {
+            return (
+                syntax,
+                SyntaxReason::Mapping(glob.glob().glob().to_string()),
+            );
+        }
+
+        // C, C++ and Objective-C share the same extension for header files
+        if path.extension() == Some(OsStr::new("h")) {
+            if let Some(syntax) = self
+                .syntaxes
+                .find_syntax_by_name(header_syntax_name(contents))
+            {
+                return (syntax, SyntaxReason::HeaderContents);
+            }
+        }
+
+        // Note: `SyntaxSet::find_syntax_for_file` is not available since it opens the file to read its first line. The
+        // path may not exist on file system (e.g. a member of archive) and the contents are already in memory.
+        if let Some(syntax) = self.syntaxes.find_syntax_by_extension(file_name) {
+            return (syntax, SyntaxReason::FileName);
+        }
+        let extension = path.extension().and_then(OsStr::to_str).unwrap_or("");
+        if let Some(syntax) = self.syntaxes.find_syntax_by_extension(extension) {
+            return (syntax, SyntaxReason::Extension);
+        }
+        let end = memchr(b'\n', contents)
+            .map(|i| i + 1)
+            .unwrap_or(contents.len());
+        let first_line = String::from_utf8_lossy(&contents[..end]);
+        if let Some(syntax) = self.syntaxes.find_syntax_by_first_line(&first_line) {
+            return (syntax, SyntaxReason::FirstLine);
+        }
+        (
+            self.syntaxes.find_syntax_plain_text(),
+            SyntaxReason::PlainText,
+        )
+    }
+
+    // Returns the notice shown in the header when the file should not be rendered
+    fn skip_reason(&self, file: &File) -> Option<String> {
+        let contents = &file.contents;
+        if !self.opts.binary_as_text && is_binary(contents) {
+            return Some(match file.line_matches.first() {
+                Some(m) => format!("binary file, match on line {}", m.line_number),
+                None => "binary file".to_string(),
+            });
+        }
+        if self.opts.strict_utf8 {
+            if let Err(err) = std::str::from_utf8(contents) {
+                let valid = &contents[..err.valid_up_to()];
+                let lnum = memchr_iter(b'\n', valid).count() + 1;
+                return Some(format!("invalid UTF-8 at line {}, skipped", lnum));
+            }
+        }
+        None
+    }
+
+    /// Render the file as a sequence of [`RenderEvent`]s instead of printing bytes. Options for drawing such as the
+    /// terminal width are not applied, but the chunks to render are the same as [`Printer::print`].
+    pub fn render(&self, file: &File, on_event: impl FnMut(RenderEvent)) {
+        if file.chunks.is_empty() {
+            return;
+        }
+        let retained;
+        let file = if self.opts.first_only == FirstOnly::Match || self.opts.trim_chunk_blanks {
+            let mut f = file.clone();
+            if self.opts.first_only == FirstOnly::Match {
+                f.retain_first_match();
+            }
+            if self.opts.trim_chunk_blanks {
+                f.trim_blank_context();
+            }
+            retained = f;
+            &retained
+        } else {
+            file
+        };
+
+        let mut sink = EventSink { on_event };
+        if let Some(notice) = self.skip_reason(file) {
+            (sink.on_event)(RenderEvent::Header {
+                path: file.path.clone(),
+                notice: Some(notice),
+            });
+            (sink.on_event)(RenderEvent::Footer);
+            return;
+        }
+
+        let syntax = self.find_syntax(&file.path, &file.contents, file.language.as_deref());
+        let hl = self.line_highlighter(syntax);
+        let opts = RenderOptions {
+            first_only: self.opts.first_only != FirstOnly::None,
+            edge_separators: self.opts.edge_separators,
+            invalid_utf8: self.opts.invalid_utf8,
+            line_hook: self.line_hook.as_deref(),
+            repeats: &[],
+            tabular: tabular_delimiter(&self.opts, &file.path),
+        };
+        render_file(&mut sink, file, hl, opts).expect("EventSink never fails");
+    }
+
+    /// Highlight the lines in the chunks of the file and return the line number, whether the line is matched, and the
+    /// styled spans of each line. This is a simpler form of [`SyntectPrinter::render`] for applications which draw lines
+    /// with their own UI. Separators, notices and replacements are not included, and adjacent spans of the same style
+    /// are merged so that matched regions are not split out. The spans don't contain a newline.
+    ///
+    /// The returned strings are owned copies of the file contents so they can outlive both the printer and `file`.
+    pub fn highlight_file(&self, file: &File) -> Vec<HighlightedLine> {
+        let mut lines = vec![];
+        self.render(file, |event| {
+            let RenderEvent::Line {
+                lnum,
+                matched,
+                spans,
+            } = event
+            else {
+                return;
+            };
+            let mut merged: Vec<(Style, String)> = vec![];
+            for span in spans {
+                match merged.last_mut() {
+                    Some((style, text)) if *style == span.style => text.push_str(&span.text),
+                    _ => merged.push((span.style, span.text)),
+                }
+            }
+            lines.push((lnum, matched, merged));
+        });
+        lines
+    }
+
+    // Collect the rendered lines of the file for the printers of documents such as SVG and RTF. `None` means nothing is
+    // rendered for the file. Disabled font styles are removed from the spans
+    pub(crate) fn render_rows(&self, file: &File) -> Option<(PathBuf, Vec<RenderedRow>)> {
+        let font_styles = enabled_font_styles(&self.opts.disabled_font_styles);
+        let mask = |mut spans: Vec<StyledSpan>| {
+            for span in &mut spans {
+                span.style.font_style &= font_styles;
+            }
+            spans
+        };
+        let mut path = None;
+        let mut rows = vec![];
+        self.render(file, |event| match event {
+            RenderEvent::Header { path: p, notice } => {
+                path = Some(p);
+                rows.extend(notice.map(RenderedRow::Notice));
+            }
+            RenderEvent::Line {
+                lnum,
+                matched,
+                spans,
+            } => {
+                let kind = if matched {
+                    LineKind::Matched
+                } else {
+                    LineKind::Context
+                };
+                let spans = mask(spans);
+                rows.push(RenderedRow::Line { lnum, kind, spans });
+            }
+            RenderEvent::Replacement { lnum, spans } => {
+                // The matched line before the replacement is drawn as a removed line of diff
+                if let Some(RenderedRow::Line { kind, .. }) = rows.last_mut() {
+                    *kind = LineKind::Removed;
+                }
+                let kind = LineKind::Added;
+                let spans = mask(spans);
+                rows.push(RenderedRow::Line { lnum, kind, spans });
+            }
+            RenderEvent::Separator { .. } => rows.push(RenderedRow::Separator),
+            RenderEvent::Footer => {}
+        });
+        Some((path?, rows))
+    }
+
+    fn line_highlighter(&self, syntax: &SyntaxReference) -> LineHighlighter<'_> {
+        let mut hl = LineHighlighter::new(syntax, self.theme(), &self.syntaxes);
+        if !self.opts.highlight {
+            hl.disable_highlight();
+        } else {
+            if self.opts.literal_tabs_in_strings && self.opts.tab_width > 0 {
+                hl.detect_literal_tabs();
+            }
+            if self.opts.bracket_colors {
+                hl.detect_literal_brackets();
+            }
+        }
+        if let Some(budget) = self.opts.highlight_budget {
+            hl.set_budget(budget);
+        }
+        if let Some((r, g, b)) = self.opts.plain_text_fg {
+            let is_plain_text = std::ptr::eq(syntax, self.syntaxes.find_syntax_plain_text());
+            if is_plain_text && self.opts.color_support != TermColorSupport::Ansi16 {
+                hl.set_foreground(Color { r, g, b, a: 255 });
+            }
+        }
+        hl
+    }
+
+    /// Create a [`StreamPrinter`] which prints the lines of a stream to `out` as they arrive. `path` is shown in the
+    /// header and the syntax is detected from it since the contents are not available yet. Options which need the whole
+    /// file such as `first_only`, `match_sparkline` and `window_height` are not applied to streams.
+    pub fn stream<O: Write>(&self, path: &Path, out: O) -> StreamPrinter<'_, O> {
+        let theme = self.theme();
+        let syntax = self.find_syntax(path, b"", None);
+        let hl = self.line_highlighter(syntax);
+        let mut drawer = Drawer::new(out, &self.opts, theme, &[]);
+        drawer.lnum_width = 4;
+        drawer.line_hook = self.line_hook.as_deref();
+        drawer.annotator = self.gutter_annotator.as_deref();
+        drawer.path = path.to_path_buf();
+        if self.opts.hyperlinks {
+            drawer.file_url = file_url(path, self.cwd.as_deref());
+        }
+        StreamPrinter {
+            drawer,
+            hl,
+            path: path.to_path_buf(),
+            lnum: 0,
+            skipped: 0,
+            started: false,
+        }
+    }
+}
+
+/// Why the syntax was chosen for the file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SyntaxReason {
+    /// Specified by the language of the file (see [`File::from_bytes`]) or [`PrinterOptions::language`]
+    Language,
+    /// Matched to the glob pattern of syntax mappings
+    Mapping(String),
+    /// Guessed from the contents of the header file (*.h)
+    HeaderContents,
+    /// Matched to the whole file name such as 'Makefile'
+    FileName,
+    /// Matched to the file extension
+    Extension,
+    /// Detected from the first line of the file such as shebang
+    FirstLine,
+    /// No syntax was found
+    PlainText,
+}
+
+impl SyntaxReason {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Language => "language",
+            Self::Mapping(_) => "mapping",
+            Self::HeaderContents => "header-contents",
+            Self::FileName => "file-name",
+            Self::Extension => "extension",
+            Self::FirstLine => "first-line",
+            Self::PlainText => "plain-text",
+        }
+    }
+}
+
+impl fmt::Display for SyntaxReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Language => write!(f, "specified by the language of the file"),
+            Self::Mapping(glob) => write!(f, "matched to syntax mapping '{}'", glob),
+            Self::HeaderContents => write!(f, "guessed from contents of the header file"),
+            Self::FileName => write!(f, "matched to the file name"),
+            Self::Extension => write!(f, "matched to the file extension"),
+            Self::FirstLine => write!(f, "detected from the first line"),
+            Self::PlainText => write!(f, "no syntax was found"),
+        }
+    }
+}
+
+/// Result of [`SyntectPrinter::explain`]. `Display` formats it as plain text.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Explanation {
+    pub path: PathBuf,
+    pub syntax: String,
+    pub reason: SyntaxReason,
+    pub theme: String,
+}
+
+impl Explanation {
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            r#"{{"path":{},"syntax":{},"reason":{}"#,
+            json_string(&self.path.to_string_lossy()),
+            json_string(&self.syntax),
+            json_string(self.reason.kind()),
+        );
+        if let SyntaxReason::Mapping(glob) = &self.reason {
+            json.push_str(&format!(r#","glob":{}"#, json_string(glob)));
+        }
+        json.push_str(&format!(r#","theme":{}}}"#, json_string(&self.theme)));
+        json
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "path: {}", self.path.display())?;
+        writeln!(f, "syntax: {} ({})", self.syntax, self.reason)?;
+        write!(f, "theme: {}", self.theme)
+    }
+}
+
+// Mappings for the files which syntect cannot detect correctly from their file names
+const BUILTIN_SYNTAX_MAPPINGS: &[(&str, &str)] = &[
+    ("*.fs", "F#"),
+    ("*.pac", "JavaScript (Babel)"),
+    (".clang-format", "YAML"),
+    ("Dockerfile.*", "Dockerfile"),
+    ("Containerfile", "Dockerfile"),
+    (".babelrc", "JSON"),
+];
+
+// Find the syntax by its name such as 'Rust' or by its file extension such as 'rs'
+fn find_language<'a>(syntaxes: &'a SyntaxSet, language: &str) -> Option<&'a SyntaxReference> {
+    syntaxes
+        .find_syntax_by_name(language)
+        .or_else(|| syntaxes.find_syntax_by_token(language))
+}
+
+fn check_language(language: Option<&str>, syntaxes: &SyntaxSet) -> Result<()> {
+    match language {
+        Some(l) if find_language(syntaxes, l).is_none() => {
+            Err(HgrepError::UnknownLanguage(l.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn build_syntax_mappings<'a>(
+    mappings: &[(&'a str, &'a str)],
+    syntaxes: &SyntaxSet,
+) -> Result<Vec<(GlobMatcher, &'a str)>> {
+    // User's mappings are preferred over the builtin mappings
+    let mut built = Vec::with_capacity(mappings.len() + BUILTIN_SYNTAX_MAPPINGS.len());
+    for (pat, name) in mappings
+        .iter()
+        .copied()
+        .chain(BUILTIN_SYNTAX_MAPPINGS.iter().copied())
+    {
+        if syntaxes.find_syntax_by_name(name).is_none() {
+            let msg = format!("Unknown syntax '{}' for glob '{}'", name, pat);
+            return Err(HgrepError::InvalidSyntaxMapping(msg));
+        }
+        let glob = GlobBuilder::new(pat)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| HgrepError::InvalidSyntaxMapping(format!("{}", e)))?;
+        built.push((glob.compile_matcher(), name));
+    }
+    Ok(built)
+}
+
+// Guess the language of the header file (*.h) from tokens in it. Note that this is a heuristic and does not consider
+// comments or string literals
+fn header_syntax_name(contents: &[u8]) -> &'static str {
+    const MAX_SCAN_BYTES: usize = 64 * 1024; // Avoid scanning the entire large header file
+    let contents = &contents[..cmp::min(contents.len(), MAX_SCAN_BYTES)];
+    let words = contents
+        .split(|b| !(b.is_ascii_alphanumeric() || *b == b'_' || *b == b'@'))
+        .filter(|w| !w.is_empty());
+
+    let mut is_c = false;
+    for word in words {
+        match word {
+            b"@interface" | b"@protocol" | b"@implementation" | b"@class" => return "Objective-C",
+            b"class" | b"namespace" | b"template" | b"typename" | b"virtual" | b"constexpr"
+            | b"nullptr" => return "C++",
+            b"__cplusplus" | b"restrict" | b"_Bool" | b"_Generic" | b"_Static_assert" => {
+                is_c = true
+            }
+            _ => {}
+        }
+    }
+
+    if is_c {
+        "C"
+    } else {
+        "C++" // C++ syntax can highlight C headers well in most cases
+    }
+}
+
+impl<'main, W> SyntectPrinter<'main, W>
+where
+    for<'a> W: LockableWrite<'a>,
+{
+    // Print one line per file with its number of matched lines followed by an empty line
+    fn print_index(&self, files: &[File]) -> Result<()> {
+        use crate::io::IgnoreBrokenPipe;
+
+        if files.is_empty() {
+            return Ok(());
+        }
+        let mut buf = vec![];
+        let mut drawer = Drawer::new(&mut buf, &self.opts, self.theme(), &[]);
+        for file in files {
+            drawer.draw_index_line(&file.path, file.line_matches.len())?;
+        }
+        writeln!(buf)?;
+
+        let mut output = self.writer.lock();
+        output
+            .write_all(&buf)
+            .and_then(|_| output.flush())
+            .ignore_broken_pipe()?;
+        Ok(())
+    }
+
+    /// Print the file like [`Printer::print`] with the options only for the file.
+    pub fn print_with(&self, mut file: File, file_opts: &FileOptions) -> Result<()> {
+        use crate::io::IgnoreBrokenPipe;
+
+        if file.chunks.is_empty() {
+            return Ok(());
+        }
+        if self.opts.first_only == FirstOnly::Match {
+            file.retain_first_match();
+        }
+        if self.opts.trim_chunk_blanks {
+            file.trim_blank_context();
+        }
+
+        let mut buf = vec![];
+        let theme = self.theme();
+        let syntax = self.find_syntax(&file.path, &file.contents, file.language.as_deref());
+
+        let hl = self.line_highlighter(syntax);
+        let mut drawer = Drawer::new(&mut buf, &self.opts, theme, &file.chunks);
+        drawer.canvas.override_colors(file_opts);
+        drawer.line_hook = self.line_hook.as_deref();
+        drawer.annotator = self.gutter_annotator.as_deref();
+        drawer.symbol_source = self.symbol_source.as_deref();
+        drawer.tabular = tabular_delimiter(&self.opts, &file.path);
+        drawer.path = file.path.clone();
+        if self.opts.hyperlinks {
+            drawer.file_url = file_url(&file.path, self.cwd.as_deref());
+        }
+        if let Some(notice) = self.skip_reason(&file) {
+            drawer.draw_skipped_file(&file.path, &notice)?;
+        } else {
+            drawer.draw_file(&file, hl)?;
+        }
+        let mut buf = self.rows_in_window(&buf);
+        let mut held_newline = false;
+        if !self.opts.trailing_newline {
+            if let Some(b) = buf.strip_suffix(b"\n") {
+                buf = b;
+                held_newline = true;
+            }
+        }
+
+        // Take lock here to print files in serial from multiple threads
+        // Note: BrokenPipe is not an error. It happens when the reader side quits early (e.g. `| head`)
+        let mut output = self.writer.lock();
+        if self.newline_pending.swap(held_newline, Ordering::Relaxed) {
+            output.write_all(b"\n").ignore_broken_pipe()?;
+        }
+        if self.opts.form_feed && self.printed_file.swap(true, Ordering::Relaxed) {
+            output.write_all(b"\x0c").ignore_broken_pipe()?;
+        }
+        output
+            .write_all(buf)
+            .and_then(|_| output.flush())
+            .ignore_broken_pipe()?;
+        Ok(())
+    }
+}
+
+impl<'main, W> Printer for SyntectPrinter<'main, W>
+where
+    for<'a> W: LockableWrite<'a>,
+{
+    fn print(&self, file: File) -> anyhow::Result<()> {
+        Ok(self.print_with(file, &FileOptions::default())?)
+    }
+
+    fn print_all(&self, mut files: Vec<File>, sort: SortOrder) -> anyhow::Result<()> {
+        sort_files(&mut files, sort);
+        if self.opts.file_index {
+            self.print_index(&files)?;
+        }
+        for file in files {
+            self.print_with(file, &FileOptions::default())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{File, LineMatch};
+    use crate::printer::ThemeMode;
+    use lazy_static::lazy_static;
+    use std::cell::{RefCell, RefMut};
+    use std::fmt;
+    use std::fs;
+    use std::mem;
+    use std::path::PathBuf;
     use std::str;
 
     lazy_static! {
@@ -1156,6 +4256,21 @@ mod tests {
         }
     }
 
+    // Remove the SGR escape sequences to check the printed texts
+    fn strip_escapes(s: &str) -> String {
+        let mut stripped = String::new();
+        let mut in_escape = false;
+        for c in s.chars() {
+            match c {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if in_escape => {}
+                c => stripped.push(c),
+            }
+        }
+        stripped
+    }
+
     mod ui {
         use super::*;
         use pretty_assertions::assert_eq;
@@ -1288,6 +4403,60 @@ mod tests {
             run_uitest(file, outfile, f);
         }
 
+        #[test]
+        fn test_replace_preview() {
+            let dir = Path::new(".").join("testdata").join("syntect");
+            let mut file = read_chunks(dir.join("replace_preview.rs"));
+            let lines: Vec<_> = file.contents.split(|b| *b == b'\n').collect();
+            let line_matches = file
+                .line_matches
+                .iter()
+                .map(|m| {
+                    let line = lines[m.line_number as usize - 1];
+                    let (start, end) = m.ranges[0];
+                    let mut replaced = line[..start].to_vec();
+                    replaced.extend_from_slice(b"*replaced with much longer text*");
+                    replaced.extend_from_slice(&line[end..]);
+                    m.clone().with_replacement(replaced)
+                })
+                .collect();
+            file.line_matches = line_matches;
+            run_uitest(file, dir.join("replace_preview.out"), |_| {});
+        }
+
+        #[test]
+        fn test_multi_patterns() {
+            let dir = Path::new(".").join("testdata").join("syntect");
+            let mut file = read_chunks(dir.join("multi_patterns.rs"));
+            let line_matches = file
+                .line_matches
+                .iter()
+                .map(|m| match m.ranges.as_slice() {
+                    &[(s0, e0), (s1, e1)] => {
+                        // The range of the third pattern overlaps with the both ranges
+                        let ranges = vec![(s0, e0), (s1, e1), (e0 - 6, s1 + 6)];
+                        LineMatch::new(m.line_number, ranges).with_patterns(vec![0, 1, 2])
+                    }
+                    _ => m.clone().with_patterns(vec![1]),
+                })
+                .collect();
+            file.line_matches = line_matches;
+            run_uitest(file, dir.join("multi_patterns.out"), |o| {
+                o.pattern_colors = vec![(255, 0, 0), (0, 128, 255)];
+            });
+        }
+
+        #[test]
+        fn test_edge_separators() {
+            let dir = Path::new(".").join("testdata").join("syntect");
+            let mut file = read_chunks(dir.join("edge_separators.rs"));
+            // Same chunk as `hgrep -c 6 -C 6`. `read_chunks` cannot read chunks across 12 lines
+            file.chunks = vec![(4, 16)].into_boxed_slice();
+            run_uitest(file, dir.join("edge_separators.out"), |o| {
+                o.edge_separators = true;
+            });
+        }
+
         macro_rules! uitests {
             ($($input:ident($f:expr),)+) => {
                 $(
@@ -1316,6 +4485,24 @@ mod tests {
             test_hard_tab(|o| {
                 o.tab_width = 0;
             }),
+            test_hard_tab_wrap(|o| {
+                o.tab_width = 0;
+            }),
+            test_tab_align(|o| {
+                o.align_tabs = true;
+            }),
+            test_tab_no_align(|_| {}),
+            test_h_scroll(|o| {
+                o.h_scroll = 17;
+                o.text_wrap = TextWrapMode::Never;
+            }),
+            test_match_underline(|o| {
+                o.match_underline = Some((255, 0, 0));
+            }),
+            test_match_underline_ansi16(|o| {
+                o.match_underline = Some((255, 0, 0));
+                o.color_support = TermColorSupport::Ansi16;
+            }),
             test_ansi256_colors(|o| {
                 o.color_support = TermColorSupport::Ansi256;
             }),
@@ -1369,56 +4556,256 @@ mod tests {
             test_multi_chunks_bg(|o| {
                 o.background_color = true;
             }),
-            test_japanese_default(|_| {}),
-            test_japanese_background(|o| {
-                o.background_color = true;
+            test_header_style(|o| {
+                o.header_style = HeaderStyle {
+                    fg: Some((0, 0, 0)),
+                    bg: Some((255, 200, 0)),
+                    bold: false,
+                    underline: true,
+                };
             }),
-            test_wrap_japanese_after(|_| {}),
-            test_wrap_japanese_before(|_| {}),
-            test_wrap_break_wide_char(|_| {}),
-            test_wrap_break_wide_char_bg(|o| {
-                o.background_color = true;
+            test_separator_color(|o| {
+                o.separator_color = Some((255, 128, 0));
             }),
-            test_wrap_japanese_louise(|_| {}),
-            test_wrap_jp_louise_bg(|o| {
-                o.background_color = true;
+            test_separator_color_ansi16(|o| {
+                o.separator_color = Some((255, 128, 0));
+                o.color_support = TermColorSupport::Ansi16;
             }),
-            test_wrap_jp_louise_no_grid(|o| {
-                o.grid = false;
+            test_indent_guides(|o| {
+                o.indent_guides = true;
             }),
-            test_wrap_emoji(|_| {}),
-            test_wrap_emoji_zwj(|_| {}),
-            test_emoji(|_| {}),
-            test_emoji_bg(|o| {
+            test_indent_guides_hard_tab(|o| {
+                o.indent_guides = true;
+                o.tab_width = 0;
+            }),
+            test_match_sparkline(|o| {
+                o.match_sparkline = true;
+            }),
+            test_match_sparkline_ascii(|o| {
+                o.match_sparkline = true;
+                o.ascii_lines = true;
+            }),
+            test_overflow_tooltip(|o| {
+                o.text_wrap = TextWrapMode::Never;
+                o.overflow_tooltip = true;
+            }),
+            test_overflow_tooltip_bg(|o| {
+                o.text_wrap = TextWrapMode::Never;
+                o.overflow_tooltip = true;
                 o.background_color = true;
             }),
-            test_no_grid_background(|o| {
-                o.grid = false;
+            test_bracket_colors(|o| {
+                o.bracket_colors = true;
+            }),
+            test_bracket_colors_ansi16(|o| {
+                o.bracket_colors = true;
+                o.color_support = TermColorSupport::Ansi16;
+            }),
+            test_header_path_width(|o| {
+                o.header_path_width = Some(60);
+                o.match_sparkline = true;
+            }),
+            test_transparent(|o| {
+                o.transparent = true;
                 o.background_color = true;
             }),
-            test_wide_char_region(|_| {}),
-            test_wide_char_region_bg(|o| {
+            test_header_align_right(|o| {
+                o.header_align = HeaderAlign::Right;
                 o.background_color = true;
             }),
-            test_wrap_accross_regions(|_| {}),
-            test_wrap_match_at_second_line(|_| {}),
-            test_wrap_region_accross_line(|_| {}),
-            test_wrap_region_jp_accross_line(|_| {}),
-            test_wrap_match_at_second_line_bg(|o| {
+            test_auto_truncate_after(|o| {
+                o.auto_truncate_after = Some(2);
+            }),
+            test_auto_truncate_after_bg(|o| {
+                o.auto_truncate_after = Some(2);
                 o.background_color = true;
             }),
-            test_region_at_end_of_line(|_| {}),
-            test_region_at_end_of_line_bg(|o| {
+            test_continuation_line_numbers(|o| {
+                o.continuation_line_numbers = true;
+            }),
+            test_continuation_line_numbers_bg(|o| {
+                o.continuation_line_numbers = true;
                 o.background_color = true;
             }),
-            test_region_at_line_start(|_| {}),
-            test_wrap_region_line_start(|_| {}),
-            test_wrap_region_line_end(|_| {}),
-            test_wrap_3_lines_emoji(|_| {}),
-            test_first_only(|o| {
-                o.first_only = true;
+            test_match_indicator(|o| {
+                o.match_indicator = true;
             }),
-            test_ascii_lines_grid(|o| {
+            test_match_indicator_ascii_wrap(|o| {
+                o.match_indicator = true;
+                o.ascii_lines = true;
+                o.background_color = true;
+            }),
+            test_no_highlight(|o| {
+                o.highlight = false;
+            }),
+            test_no_highlight_bg(|o| {
+                o.highlight = false;
+                o.background_color = true;
+            }),
+            test_border_rounded(|o| {
+                o.border = BorderStyle::Rounded;
+            }),
+            test_border_rounded_wrap_bg(|o| {
+                o.border = BorderStyle::Rounded;
+                o.background_color = true;
+            }),
+            test_border_rounded_no_wrap(|o| {
+                o.border = BorderStyle::Rounded;
+                o.text_wrap = TextWrapMode::Never;
+            }),
+            test_border_ascii_no_grid(|o| {
+                o.border = BorderStyle::Rounded;
+                o.ascii_lines = true;
+                o.grid = false;
+            }),
+            test_border_rounded_transparent(|o| {
+                o.border = BorderStyle::Rounded;
+                o.transparent = true;
+                o.background_color = true;
+            }),
+            test_match_emphasis_chunk(|o| {
+                o.match_emphasis = MatchEmphasis::WholeChunk;
+            }),
+            test_match_emphasis_none(|o| {
+                o.match_emphasis = MatchEmphasis::None;
+            }),
+            test_match_emphasis_none_bg(|o| {
+                o.match_emphasis = MatchEmphasis::None;
+                o.background_color = true;
+            }),
+            test_match_first_line(|_| {}),
+            test_match_last_line(|_| {}),
+            test_edge_separators_file_edges(|o| {
+                o.edge_separators = true;
+            }),
+            test_highlight_budget_zero(|o| {
+                o.highlight_budget = Some(Duration::ZERO);
+            }),
+            test_show_line_count(|o| {
+                o.show_line_count = true;
+            }),
+            test_match_color(|o| {
+                o.match_color = Some((0x33, 0x66, 0x99));
+            }),
+            test_focus_match(|o| {
+                o.focus_match = true;
+            }),
+            test_focus_match_bg(|o| {
+                o.focus_match = true;
+                o.background_color = true;
+            }),
+            test_show_eol(|o| {
+                o.show_eol = Some('$');
+            }),
+            test_show_eol_wrap(|o| {
+                o.show_eol = Some('¶');
+                o.background_color = true;
+            }),
+            test_numbering_chunk_local(|o| {
+                o.numbering = Numbering::ChunkLocal;
+            }),
+            test_stacked_layout(|o| {
+                o.min_text_width = Some(76);
+            }),
+            test_stacked_layout_bg(|o| {
+                o.min_text_width = Some(76);
+                o.background_color = true;
+            }),
+            test_collapse_repeats(|o| {
+                o.collapse_repeats = true;
+            }),
+            test_header_lines_matches(|o| {
+                o.header_lines = HeaderLines::Matches;
+            }),
+            test_header_lines_chunks(|o| {
+                o.header_lines = HeaderLines::Chunks;
+            }),
+            test_gutter_only(|o| {
+                o.gutter_only = true;
+            }),
+            test_gutter_only_chunk_local(|o| {
+                o.gutter_only = true;
+                o.numbering = Numbering::ChunkLocal;
+            }),
+            test_hanging_indent(|o| {
+                o.hanging_indent = true;
+            }),
+            test_hanging_indent_bg(|o| {
+                o.hanging_indent = true;
+                o.background_color = true;
+            }),
+            test_min_lnum_width(|o| {
+                o.min_lnum_width = Some(5);
+            }),
+            test_no_highlight_matches(|o| {
+                o.highlight_matches = false;
+            }),
+            test_no_highlight_matches_bg(|o| {
+                o.highlight_matches = false;
+                o.background_color = true;
+            }),
+            test_plain_gutter(|o| {
+                o.plain_gutter = true;
+            }),
+            test_separator_gap(|o| {
+                o.separator_gap = true;
+            }),
+            test_japanese_default(|_| {}),
+            test_japanese_background(|o| {
+                o.background_color = true;
+            }),
+            test_wrap_japanese_after(|_| {}),
+            test_wrap_japanese_before(|_| {}),
+            test_wrap_break_wide_char(|_| {}),
+            test_wrap_break_wide_char_bg(|o| {
+                o.background_color = true;
+            }),
+            test_wrap_japanese_louise(|_| {}),
+            test_wrap_jp_louise_bg(|o| {
+                o.background_color = true;
+            }),
+            test_wrap_jp_louise_no_grid(|o| {
+                o.grid = false;
+            }),
+            test_wrap_emoji(|_| {}),
+            test_wrap_emoji_zwj(|_| {}),
+            test_emoji(|_| {}),
+            test_emoji_bg(|o| {
+                o.background_color = true;
+            }),
+            test_no_grid_background(|o| {
+                o.grid = false;
+                o.background_color = true;
+            }),
+            test_wide_char_region(|_| {}),
+            test_wide_char_region_bg(|o| {
+                o.background_color = true;
+            }),
+            test_wrap_accross_regions(|_| {}),
+            test_wrap_match_at_second_line(|_| {}),
+            test_wrap_region_accross_line(|_| {}),
+            test_wrap_region_jp_accross_line(|_| {}),
+            test_wrap_match_at_second_line_bg(|o| {
+                o.background_color = true;
+            }),
+            test_region_at_end_of_line(|_| {}),
+            test_region_at_end_of_line_bg(|o| {
+                o.background_color = true;
+            }),
+            test_region_at_line_start(|_| {}),
+            test_wrap_region_line_start(|_| {}),
+            test_wrap_region_line_end(|_| {}),
+            test_wrap_3_lines_emoji(|_| {}),
+            test_first_only(|o| {
+                o.first_only = FirstOnly::Chunk;
+            }),
+            test_first_match_only(|o| {
+                o.first_only = FirstOnly::Match;
+            }),
+            test_trim_chunk_blanks(|o| {
+                o.trim_chunk_blanks = true;
+            }),
+            test_ascii_lines_grid(|o| {
                 o.ascii_lines = true;
             }),
             test_ascii_lines_no_grid(|o| {
@@ -1431,6 +4818,17 @@ mod tests {
             }),
             test_wrap_between_regions(|_| {}),
             test_wrap_regions_japanese(|_| {}),
+            test_search_info(|o| {
+                o.search_info = Some(SearchInfo {
+                    case_insensitive: true,
+                });
+            }),
+            test_search_info_bg(|o| {
+                o.search_info = Some(SearchInfo {
+                    case_insensitive: true,
+                });
+                o.background_color = true;
+            }),
         );
     }
 
@@ -1517,6 +4915,24 @@ mod tests {
         }
     }
 
+    struct FlushErrorStdoutLock(io::ErrorKind);
+    impl Write for FlushErrorStdoutLock {
+        fn write(&mut self, b: &[u8]) -> io::Result<usize> {
+            Ok(b.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::new(self.0, DummyError))
+        }
+    }
+
+    struct FlushErrorStdout(io::ErrorKind);
+    impl<'a> LockableWrite<'a> for FlushErrorStdout {
+        type Locked = FlushErrorStdoutLock;
+        fn lock(&'a self) -> Self::Locked {
+            FlushErrorStdoutLock(self.0)
+        }
+    }
+
     fn sample_chunk(file: &str) -> File {
         let readme = PathBuf::from(file);
         let lmats = vec![LineMatch::lnum(3)];
@@ -1547,6 +4963,31 @@ mod tests {
         printer.print(file).unwrap();
     }
 
+    #[test]
+    fn test_flush_error() {
+        let file = sample_chunk("README.md");
+        let opts = PrinterOptions::default();
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            FlushErrorStdout(io::ErrorKind::Other),
+            opts,
+        );
+        let err = printer.print(file).unwrap_err();
+        assert_eq!(&format!("{}", err), "dummy error!", "message={}", err);
+    }
+
+    #[test]
+    fn test_no_error_at_broken_pipe_on_flush() {
+        let file = sample_chunk("README.md");
+        let opts = PrinterOptions::default();
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            FlushErrorStdout(io::ErrorKind::BrokenPipe),
+            opts,
+        );
+        printer.print(file).unwrap();
+    }
+
     #[test]
     fn test_unknown_theme() {
         let opts = PrinterOptions {
@@ -1562,66 +5003,2126 @@ mod tests {
     }
 
     #[test]
-    fn test_print_nothing() {
-        let file = File::new(PathBuf::from("x.txt"), vec![], vec![], vec![]);
-        let opts = PrinterOptions::default();
-        let stdout = DummyStdout(RefCell::new(vec![]));
-        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
-        printer.print(file).unwrap();
-        let printed = mem::take(printer.writer_mut()).0.into_inner();
-        assert!(
-            printed.is_empty(),
-            "pritned:\n{}",
-            String::from_utf8_lossy(&printed)
+    fn test_unknown_theme_in_pair() {
+        let opts = PrinterOptions {
+            theme_light: Some("GitHub"),
+            theme_dark: Some("this theme does not exist"),
+            theme_mode: Some(ThemeMode::Light),
+            ..Default::default()
+        };
+        let err = match SyntectPrinter::with_stdout(opts) {
+            Err(e) => e,
+            Ok(_) => panic!("error did not occur"),
+        };
+        let msg = format!("{}", err);
+        assert!(msg.contains("Unknown theme"), "message={:?}", msg);
+    }
+
+    #[test]
+    fn test_user_bat_assets() {
+        let config_dir = Path::new("testdata").join("syntect").join("bat_config");
+        // Broken theme files are ignored
+        let themes = load_user_bat_themes(None, Some(&config_dir));
+        assert_eq!(themes.themes.keys().collect::<Vec<_>>(), ["Custom"]);
+        let syntaxes = with_user_bat_syntaxes(load_syntax_set().unwrap(), None, Some(&config_dir));
+        assert!(syntaxes.find_syntax_by_extension("hgreptest").is_some());
+        assert!(syntaxes.find_syntax_by_name("Rust").is_some());
+
+        let cache_dir =
+            env::temp_dir().join(format!("hgrep-test-bat-cache-{}", std::process::id()));
+        fs::create_dir_all(&cache_dir).unwrap();
+        let compress = |serialized: Vec<u8>| {
+            let mut enc = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::best());
+            enc.write_all(&serialized).unwrap();
+            enc.finish().unwrap()
+        };
+        // Same format as `bat cache --build`. The theme set is not compressed but each theme in it is compressed. The
+        // syntax set is compressed
+        let mut cached_themes = BTreeMap::new();
+        let theme = bincode::serialize(&themes.themes["Custom"]).unwrap();
+        cached_themes.insert("Cached".to_string(), compress(theme));
+        cached_themes.insert("Broken".to_string(), b"broken".to_vec());
+        fs::write(
+            cache_dir.join("themes.bin"),
+            bincode::serialize(&cached_themes).unwrap(),
+        )
+        .unwrap();
+        let mut builder = syntect::parsing::SyntaxSetBuilder::new();
+        builder
+            .add_from_folder(config_dir.join("syntaxes"), true)
+            .unwrap();
+        let syntaxes = bincode::serialize(&builder.build()).unwrap();
+        fs::write(cache_dir.join("syntaxes.bin"), compress(syntaxes)).unwrap();
+
+        let themes = load_user_bat_themes(Some(&cache_dir), Some(&config_dir));
+        let mut names: Vec<_> = themes.themes.keys().collect();
+        names.sort();
+        assert_eq!(names, ["Cached", "Custom"]);
+        // Syntax set in cache is preferred over the bundled syntaxes
+        let syntaxes = with_user_bat_syntaxes(load_syntax_set().unwrap(), Some(&cache_dir), None);
+        assert!(syntaxes.find_syntax_by_extension("hgreptest").is_some());
+        assert!(syntaxes.find_syntax_by_name("Rust").is_none());
+
+        // Broken cache is ignored
+        fs::write(cache_dir.join("syntaxes.bin"), b"broken").unwrap();
+        let syntaxes = with_user_bat_syntaxes(
+            load_syntax_set().unwrap(),
+            Some(&cache_dir),
+            Some(&config_dir),
         );
+        assert!(syntaxes.find_syntax_by_extension("hgreptest").is_some());
+        fs::remove_dir_all(&cache_dir).unwrap();
     }
 
     #[test]
-    fn test_no_syntax_found() {
-        let file = sample_chunk("LICENSE.txt");
-        let opts = PrinterOptions::default();
-        let stdout = DummyStdout(RefCell::new(vec![]));
-        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
-        printer.print(file).unwrap();
-        let printed = mem::take(printer.writer_mut()).0.into_inner();
-        assert!(!printed.is_empty());
+    fn test_default_theme() {
+        let tests = [
+            (None, Some("Nord"), "Nord"),
+            (Some("GitHub"), Some("Nord"), "GitHub"),
+            (None, Some("this theme does not exist"), "Monokai Extended"),
+        ];
+        for (theme, default_theme, want) in tests {
+            let opts = PrinterOptions {
+                theme,
+                default_theme,
+                color_support: TermColorSupport::True,
+                ..Default::default()
+            };
+            let printer = SyntectPrinter::with_stdout(opts).unwrap();
+            let explanation = printer.explain(Path::new("test.rs"), b"");
+            assert_eq!(
+                explanation.theme, want,
+                "theme={:?} default={:?}",
+                theme, default_theme
+            );
+            let unknown = default_theme.filter(|n| n.contains("does not exist"));
+            assert_eq!(printer.unknown_default_theme(), unknown);
+        }
     }
 
     #[test]
-    fn test_adjacent_regions() {
-        let contents = b"this is test\n";
-        let ranges = (0..contents.len()).map(|i| (i, i + 1)).collect();
-        let lmats = vec![LineMatch {
-            line_number: 1,
-            ranges,
-        }];
-        let chunks = vec![(1, 1)];
-        let file = File::new(PathBuf::from("test.txt"), lmats, chunks, contents.to_vec());
+    fn test_theme_fallbacks() {
+        let opts = PrinterOptions {
+            theme: Some("this theme does not exist"),
+            theme_fallbacks: vec!["this theme does not exist either", "Nord", "GitHub"],
+            ..Default::default()
+        };
+        let printer = SyntectPrinter::with_stdout(opts).unwrap();
+        let explanation = printer.explain(Path::new("test.rs"), b"");
+        assert_eq!(explanation.theme, "Nord");
+
+        let opts = PrinterOptions {
+            theme: Some("this theme does not exist"),
+            theme_fallbacks: vec!["this theme does not exist either"],
+            ..Default::default()
+        };
+        let err = match SyntectPrinter::with_stdout(opts) {
+            Err(e) => e,
+            Ok(_) => panic!("error did not occur"),
+        };
+        let msg = format!("{}", err);
+        assert!(
+            msg.contains("Unknown theme 'this theme does not exist'"),
+            "message={:?}",
+            msg,
+        );
+
+        // Fallbacks are not used when the theme is available
+        let opts = PrinterOptions {
+            theme: Some("GitHub"),
+            theme_fallbacks: vec!["Nord"],
+            ..Default::default()
+        };
+        let printer = SyntectPrinter::with_stdout(opts).unwrap();
+        let explanation = printer.explain(Path::new("test.rs"), b"");
+        assert_eq!(explanation.theme, "GitHub");
+    }
 
+    #[test]
+    fn test_stream_printer() {
+        let contents =
+            "/*\n * comment\n */\nfn f() {}\nfn g() {}\nfn h() {}\nfn i() {}\nfn j() {}\n";
         let opts = PrinterOptions {
             color_support: TermColorSupport::True,
+            term_width: 80,
             ..Default::default()
         };
-        let stdout = DummyStdout(RefCell::new(vec![]));
-        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        let printer = SyntectPrinter::with_assets(ASSETS.clone(), DummyStdout::default(), opts);
+
+        // Streaming lines prints the same output as printing the whole file with the same chunks
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::new(3, vec![(1, 3)]), LineMatch::lnum(7)],
+            vec![(2, 4), (6, 7)],
+            contents.as_bytes().to_vec(),
+        );
         printer.print(file).unwrap();
+        let expected = printer.writer.0.borrow().clone();
+
+        let mut out = vec![];
+        let mut stream = printer.stream(Path::new("test.rs"), &mut out);
+        stream.lnum_width(3);
+        for (idx, line) in contents.lines().enumerate() {
+            match idx + 1 {
+                2 | 4 | 6 => stream.push_line(line.as_bytes(), None).unwrap(),
+                3 => stream
+                    .push_line(line.as_bytes(), Some(vec![(1, 3)]))
+                    .unwrap(),
+                7 => stream.push_line(line.as_bytes(), Some(vec![])).unwrap(),
+                _ => stream.skip_line(line.as_bytes()),
+            }
+        }
+        assert_eq!(stream.line_number(), 8);
+        stream.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(expected).unwrap()
+        );
 
-        let printed = mem::take(printer.writer_mut()).0.into_inner();
-        let mut lines = printed.split_inclusive(|b| *b == b'\n');
+        // The gutter grows when a line number needs more columns
+        let mut out = vec![];
+        let mut stream = printer.stream(Path::new("test.rs"), &mut out);
+        stream.lnum_width(1);
+        for _ in 0..10 {
+            stream.push_line(b"fn f() {}", None).unwrap();
+        }
+        stream.finish().unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(" 9 "), "{:?}", out);
+        assert!(out.contains("10 "), "{:?}", out);
+
+        // Nothing is printed when no line is pushed
+        let mut out = vec![];
+        printer
+            .stream(Path::new("test.rs"), &mut out)
+            .finish()
+            .unwrap();
+        assert!(out.is_empty());
+    }
 
-        // One region per one character, but color codes between adjacent regions are not inserted
-        let expected = b"\x1b[38;2;0;0;0m\x1b[48;2;255;231;146mthis is test";
-        let this_is_test_line = lines.nth(3).unwrap();
-        let found = this_is_test_line
-            .windows(expected.len())
-            .any(|s| s == expected);
+    #[test]
+    fn test_summarize_lines() {
+        let tests = [
+            (vec![], None),
+            (vec![(3, 3)], Some("line 3")),
+            (vec![(3, 5)], Some("lines 3-5")),
+            (
+                vec![(12, 12), (40, 40), (41, 41), (42, 42)],
+                Some("lines 12,40-42"),
+            ),
+            (
+                vec![(1, 4), (3, 8), (9, 10), (20, 30)],
+                Some("lines 1-10,20-30"),
+            ),
+            (
+                vec![(1, u64::MAX), (3, 4)],
+                Some("lines 1-18446744073709551615"),
+            ),
+        ];
+        for (ranges, want) in tests {
+            let summary = summarize_lines(ranges.iter().copied());
+            assert_eq!(summary.as_deref(), want, "ranges={:?}", ranges);
+        }
+    }
+
+    #[test]
+    fn test_repeated_runs() {
+        let contents = b"a\na\r\na\nb\nb\nb\nc\nc\nc\nc\n".to_vec();
+        let tests = [
+            // Matched and unmatched lines are in different runs
+            (
+                vec![LineMatch::lnum(3)],
+                vec![(1, 10)],
+                vec![(1, 2), (4, 6), (7, 10)],
+            ),
+            // Runs don't continue across chunks and lines out of chunks are ignored
+            (
+                vec![],
+                vec![(1, 2), (3, 5), (9, 10)],
+                vec![(1, 2), (4, 5), (9, 10)],
+            ),
+            // Lines with replacements are never collapsed
+            (
+                vec![LineMatch::lnum(5).with_replacement("x"), LineMatch::lnum(6)],
+                vec![(4, 8)],
+                vec![(7, 8)],
+            ),
+        ];
+        for (matches, chunks, want) in tests {
+            let file = File::new(PathBuf::from("test.txt"), matches, chunks, contents.clone());
+            assert_eq!(repeated_runs(&file), want, "chunks={:?}", file.chunks);
+        }
+    }
+
+    #[test]
+    fn test_flatten_pattern_ranges() {
+        let tests = [
+            // No overlap
+            (
+                vec![(0, 2), (4, 6)],
+                vec![0, 1],
+                vec![(0, 2), (4, 6)],
+                vec![0, 1],
+            ),
+            // Later range wins on overlapped part
+            (
+                vec![(0, 4), (2, 6)],
+                vec![0, 1],
+                vec![(0, 2), (2, 6)],
+                vec![0, 1],
+            ),
+            (
+                vec![(2, 6), (0, 4)],
+                vec![1, 0],
+                vec![(0, 4), (4, 6)],
+                vec![0, 1],
+            ),
+            // Range inside another range splits it
+            (
+                vec![(0, 9), (3, 6)],
+                vec![0, 1],
+                vec![(0, 3), (3, 6), (6, 9)],
+                vec![0, 1, 0],
+            ),
+            // Adjacent ranges of the same pattern are joined
+            (
+                vec![(0, 3), (3, 6), (6, 9)],
+                vec![0, 0, 1],
+                vec![(0, 6), (6, 9)],
+                vec![0, 1],
+            ),
+            // Missing pattern indices are the first pattern
+            (
+                vec![(0, 2), (1, 3)],
+                vec![1],
+                vec![(0, 1), (1, 3)],
+                vec![1, 0],
+            ),
+        ];
+        for (ranges, patterns, want_ranges, want_patterns) in tests {
+            let (r, p) = flatten_pattern_ranges(&ranges, &patterns);
+            assert_eq!(
+                r, want_ranges,
+                "ranges={:?} patterns={:?}",
+                ranges, patterns
+            );
+            assert_eq!(
+                p, want_patterns,
+                "ranges={:?} patterns={:?}",
+                ranges, patterns
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_index() {
+        let file = |path: &str, lnums: &[u64]| {
+            let line_matches = lnums.iter().map(|&l| LineMatch::lnum(l)).collect();
+            File::new(
+                PathBuf::from(path),
+                line_matches,
+                vec![(1, 3)],
+                b"fn a() {}\nfn b() {}\nfn c() {}\n".to_vec(),
+            )
+        };
+        let opts = PrinterOptions {
+            color_support: TermColorSupport::Ansi16,
+            file_index: true,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        let files = vec![file("b.rs", &[1, 3]), file("a.rs", &[2])];
+        Printer::print_all(&printer, files, SortOrder::Path).unwrap();
+        let printed = String::from_utf8(printer.writer.0.into_inner()).unwrap();
+
+        let lines: Vec<_> = printed.lines().collect();
         assert!(
-            found,
-            "line={:?}",
-            str::from_utf8(this_is_test_line).unwrap()
+            lines[0].contains("a.rs") && lines[0].contains(" (1 match)"),
+            "{:?}",
+            lines
+        );
+        assert!(
+            lines[1].contains("b.rs") && lines[1].contains(" (2 matches)"),
+            "{:?}",
+            lines
+        );
+        assert_eq!(lines[2], "");
+        // Details of the files follow the index
+        let a = printed.rfind(" a.rs").unwrap();
+        let b = printed.rfind(" b.rs").unwrap();
+        assert!(
+            lines[3..].iter().all(|l| !l.contains("match")),
+            "{:?}",
+            lines
+        );
+        assert!(a < b, "{:?}", printed);
+    }
+
+    #[test]
+    fn test_reserve_separator_gutter() {
+        let theme = Theme::default();
+        let single = [(1, 5)];
+        let multi = [(1, 5), (8, 9)];
+        let gutter_width = |opts: &PrinterOptions<'_>, chunks: &[(u64, u64)]| {
+            Drawer::new(vec![], opts, &theme, chunks).gutter_width()
+        };
+
+        // Gutter is tight for a single chunk since no separator is drawn
+        let mut opts = PrinterOptions::default();
+        assert_eq!(gutter_width(&opts, &single), 5);
+        assert_eq!(gutter_width(&opts, &multi), 7);
+
+        opts.reserve_separator_gutter = true;
+        assert_eq!(gutter_width(&opts, &single), 7);
+        assert_eq!(gutter_width(&opts, &multi), 7);
+
+        // Wider line numbers don't need the reserved width
+        assert_eq!(gutter_width(&opts, &[(998, 1000)]), 8);
+    }
+
+    #[test]
+    fn test_load_bat_themes_with_filter() {
+        let all: ThemeSet = bincode::deserialize_from(ZlibDecoder::new(THEME_SET_BIN)).unwrap();
+        let loaded = load_bat_themes().unwrap();
+        assert!(loaded.themes.keys().eq(all.themes.keys()));
+
+        let loaded = load_bat_themes_with(|name| name == "Nord" || name == "ansi").unwrap();
+        let names: Vec<_> = loaded.themes.keys().map(String::as_str).collect();
+        assert_eq!(names, ["Nord", "ansi"]);
+        assert_eq!(
+            loaded.themes["Nord"].settings.background,
+            all.themes["Nord"].settings.background,
         );
     }
 
+    #[test]
+    fn test_load_only_used_themes() {
+        let opts = PrinterOptions {
+            theme: Some("Nord"),
+            theme_fallbacks: vec!["InspiredGitHub"],
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let printer = SyntectPrinter::with_stdout(opts).unwrap();
+        let mut names: Vec<_> = printer.themes.themes.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["InspiredGitHub", "Monokai Extended", "Nord"]);
+
+        let opts = PrinterOptions {
+            color_support: TermColorSupport::Ansi16,
+            ..Default::default()
+        };
+        let printer = SyntectPrinter::with_stdout(opts).unwrap();
+        let names: Vec<_> = printer.themes.themes.keys().map(String::as_str).collect();
+        assert_eq!(names, ["ansi"]);
+    }
+
+    #[test]
+    fn test_theme_pair_from_different_theme_sets() {
+        let opts = PrinterOptions {
+            theme_light: Some("InspiredGitHub"), // Only in syntect's default themes
+            theme_dark: Some("Nord"),            // Only in bat's themes
+            theme_mode: Some(ThemeMode::Light),
+            ..Default::default()
+        };
+        let printer = SyntectPrinter::with_stdout(opts).unwrap();
+        assert_eq!(printer.theme().name.as_deref(), Some("GitHub"));
+    }
+
+    #[test]
+    fn test_no_trailing_newline() {
+        let print = |trailing_newline| {
+            let opts = PrinterOptions {
+                trailing_newline,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(sample_chunk("README.md")).unwrap();
+            String::from_utf8(mem::take(printer.writer_mut()).0.into_inner()).unwrap()
+        };
+        let with_newline = print(true);
+        let without_newline = print(false);
+        assert!(with_newline.ends_with('\n'), "{:?}", with_newline);
+        assert!(!without_newline.ends_with('\n'), "{:?}", without_newline);
+        assert_eq!(without_newline, with_newline.strip_suffix('\n').unwrap(),);
+    }
+
+    #[test]
+    fn test_no_trailing_newline_multiple_files() {
+        let print = |trailing_newline| {
+            let opts = PrinterOptions {
+                trailing_newline,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(sample_chunk("README.md")).unwrap();
+            printer.print(sample_chunk("Cargo.toml")).unwrap();
+            String::from_utf8(mem::take(printer.writer_mut()).0.into_inner()).unwrap()
+        };
+        let with_newline = print(true);
+        let without_newline = print(false);
+        // Only the newline at the end of the whole output is omitted. The last row of the first file is not joined with
+        // the header of the second file
+        assert_eq!(without_newline, with_newline.strip_suffix('\n').unwrap());
+    }
+
+    #[test]
+    fn test_print_nothing() {
+        let file = File::new(PathBuf::from("x.txt"), vec![], vec![], vec![]);
+        let opts = PrinterOptions::default();
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        assert!(
+            printed.is_empty(),
+            "pritned:\n{}",
+            String::from_utf8_lossy(&printed)
+        );
+    }
+
+    #[test]
+    fn test_print_chunk_without_matched_lines() {
+        let contents = b"fn main() {\n    println!(\"hello\");\n}\n";
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![],
+            vec![(1, 3)],
+            contents.to_vec(),
+        );
+        let opts = PrinterOptions {
+            color_support: TermColorSupport::True,
+            term_width: 80,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+
+        // Header (3 lines) + body (3 lines) + footer (1 line)
+        assert_eq!(printed.lines().count(), 7, "printed:\n{}", printed);
+        assert!(printed.contains("hello"), "printed:\n{}", printed);
+
+        // No line is painted with the background color for matched lines
+        let palette = Palette::new(&ASSETS.theme_set.themes["Monokai Extended"]);
+        let Color { r, g, b, .. } = palette.match_bg;
+        let match_bg = format!("\x1b[48;2;{};{};{}m", r, g, b);
+        assert!(!printed.contains(&match_bg), "printed:\n{}", printed);
+    }
+
+    #[test]
+    fn test_print_with_file_options() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::new(1, vec![(3, 7)])],
+            vec![(1, 1)],
+            b"fn main() {}\n".to_vec(),
+        );
+        let file_opts = FileOptions {
+            region_bg: Some((1, 2, 3)),
+            match_bg: Some((4, 5, 6)),
+        };
+        let print = |color_support, file_opts: &FileOptions| {
+            let opts = PrinterOptions {
+                color_support,
+                term_width: 80,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print_with(file.clone(), file_opts).unwrap();
+            String::from_utf8(printer.writer.0.into_inner()).unwrap()
+        };
+
+        let printed = print(TermColorSupport::True, &file_opts);
+        assert!(printed.contains("\x1b[48;2;1;2;3m"), "{:?}", printed);
+        assert!(printed.contains("\x1b[48;2;4;5;6m"), "{:?}", printed);
+
+        // Colors are not overridden on 16 colors terminals
+        let printed = print(TermColorSupport::Ansi16, &file_opts);
+        let want = print(TermColorSupport::Ansi16, &FileOptions::default());
+        assert_eq!(printed, want);
+
+        // Other files are printed with the colors of the theme
+        let printed = print(TermColorSupport::True, &FileOptions::default());
+        assert!(!printed.contains("\x1b[48;2;1;2;3m"), "{:?}", printed);
+    }
+
+    #[test]
+    fn test_theme_without_foreground() {
+        for bg in [0x10, 0xf0] {
+            let mut theme = Theme::default();
+            theme.settings.background = Some(Color {
+                r: bg,
+                g: bg,
+                b: bg,
+                a: 0xff,
+            });
+            let palette = Palette::new(&theme);
+            for c in [palette.foreground, palette.match_lnum_fg, palette.gutter_fg] {
+                assert_eq!(c.a, 0xff, "bg={:x} color={:?}", bg, c);
+                assert!(
+                    diff_u8(color_average(c), bg) >= 0x20,
+                    "bg={:x} color={:?}",
+                    bg,
+                    c
+                );
+            }
+
+            let mut assets = ASSETS.clone();
+            assets
+                .theme_set
+                .themes
+                .insert("No Foreground".into(), theme);
+            let opts = PrinterOptions {
+                theme: Some("No Foreground"),
+                color_support: TermColorSupport::True,
+                background_color: true,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(assets, stdout, opts);
+            printer.print(sample_chunk("README.md")).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let printed = String::from_utf8(printed).unwrap();
+            // Colors are never reset in the middle of lines
+            for line in printed.lines() {
+                let body = line.strip_suffix("\x1b[0m").unwrap_or(line);
+                assert!(!body.contains("\x1b[0m"), "line={:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_theme_without_background() {
+        for fg in [0x20, 0xe0] {
+            let mut theme = Theme::default();
+            theme.settings.foreground = Some(Color {
+                r: fg,
+                g: fg,
+                b: fg,
+                a: 0xff,
+            });
+            let palette = Palette::new(&theme);
+            let base = Palette::default_foreground(palette.foreground);
+            for (bg, fg) in [
+                (palette.match_bg, palette.foreground),
+                (palette.region_bg, palette.region_fg),
+                (base, palette.gutter_fg),
+            ] {
+                assert_eq!(bg.a, 0xff, "bg={:?}", bg);
+                assert_eq!(fg.a, 0xff, "fg={:?}", fg);
+                assert!(
+                    diff_u8(color_average(fg), color_average(bg)) >= 0x20,
+                    "bg={:?} fg={:?}",
+                    bg,
+                    fg,
+                );
+            }
+
+            let mut assets = ASSETS.clone();
+            assets
+                .theme_set
+                .themes
+                .insert("No Background".into(), theme);
+            let opts = PrinterOptions {
+                theme: Some("No Background"),
+                color_support: TermColorSupport::True,
+                background_color: true,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(assets, stdout, opts);
+            printer.print(sample_chunk("README.md")).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let printed = String::from_utf8(printed).unwrap();
+            // Matched lines are painted and colors are never reset in the middle of lines
+            let Color { r, g, b, .. } = palette.match_bg;
+            let match_bg = format!("\x1b[48;2;{};{};{}m", r, g, b);
+            assert!(printed.contains(&match_bg), "{:?}", printed);
+            for line in printed.lines() {
+                let body = line.strip_suffix("\x1b[0m").unwrap_or(line);
+                assert!(!body.contains("\x1b[0m"), "line={:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_syntax_found() {
+        let file = sample_chunk("LICENSE.txt");
+        let opts = PrinterOptions::default();
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        assert!(!printed.is_empty());
+    }
+
+    #[test]
+    fn test_plain_text_foreground() {
+        let print = |path: &str, fg, color_support| {
+            let opts = PrinterOptions {
+                plain_text_fg: fg,
+                color_support,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(sample_chunk(path)).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            String::from_utf8(printed).unwrap()
+        };
+
+        // Text in the file without syntax is drawn with the color
+        let fg = Some((1, 2, 3));
+        let printed = print("LICENSE.txt", fg, TermColorSupport::True);
+        assert!(
+            printed.contains("\x1b[38;2;1;2;3mthe MIT License"),
+            "{:?}",
+            printed
+        );
+
+        // Highlighted files are not affected
+        let printed = print("README.md", fg, TermColorSupport::True);
+        assert!(!printed.contains("\x1b[38;2;1;2;3m"), "{:?}", printed);
+
+        // The color is ignored on 16 colors terminals
+        let printed = print("LICENSE.txt", fg, TermColorSupport::Ansi16);
+        let want = print("LICENSE.txt", None, TermColorSupport::Ansi16);
+        assert_eq!(printed, want);
+    }
+
+    #[test]
+    fn test_adjacent_regions() {
+        let contents = b"this is test\n";
+        let ranges = (0..contents.len()).map(|i| (i, i + 1)).collect();
+        let lmats = vec![LineMatch::new(1, ranges)];
+        let chunks = vec![(1, 1)];
+        let file = File::new(PathBuf::from("test.txt"), lmats, chunks, contents.to_vec());
+
+        let opts = PrinterOptions {
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file).unwrap();
+
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let mut lines = printed.split_inclusive(|b| *b == b'\n');
+
+        // One region per one character, but color codes between adjacent regions are not inserted
+        let expected = b"\x1b[38;2;0;0;0m\x1b[48;2;255;231;146mthis is test";
+        let this_is_test_line = lines.nth(3).unwrap();
+        let found = this_is_test_line
+            .windows(expected.len())
+            .any(|s| s == expected);
+        assert!(
+            found,
+            "line={:?}",
+            str::from_utf8(this_is_test_line).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_context_regions() {
+        let contents = b"this is test\nthat is test\n";
+        let lmats = vec![LineMatch::new(2, vec![(0, 4)])];
+        let mut file = File::new(
+            PathBuf::from("test.txt"),
+            lmats,
+            vec![(1, 2)],
+            contents.to_vec(),
+        );
+
+        let region = b"\x1b[38;2;0;0;0m\x1b[48;2;255;231;146mthis";
+        for context_regions in [vec![], vec![LineMatch::new(1, vec![(0, 4)])]] {
+            let want = !context_regions.is_empty();
+            file = file.with_context_regions(context_regions);
+
+            let opts = PrinterOptions {
+                color_support: TermColorSupport::True,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file.clone()).unwrap();
+
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let context_line = printed.split_inclusive(|b| *b == b'\n').nth(3).unwrap();
+            let line = str::from_utf8(context_line).unwrap();
+            assert!(line.contains(" 1 "), "line={:?}", line);
+            let found = context_line.windows(region.len()).any(|s| s == region);
+            assert_eq!(found, want, "line={:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_window_with_wrapped_lines() {
+        let contents = format!("fn main() {{\n    let s = \"{}\";\n}}\n", "x".repeat(100));
+        let print = |scroll_offset, window_height| {
+            let file = File::new(
+                PathBuf::from("test.rs"),
+                vec![LineMatch::lnum(2)],
+                vec![(1, 3)],
+                contents.clone().into_bytes(),
+            );
+            let opts = PrinterOptions {
+                term_width: 40,
+                scroll_offset,
+                window_height,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            String::from_utf8(mem::take(printer.writer_mut()).0.into_inner()).unwrap()
+        };
+
+        let all = print(0, None);
+        let rows: Vec<_> = all.split_inclusive('\n').collect();
+        // 3 header rows, 1 + 4 + 1 body rows (line 2 is wrapped into 4 rows), and 1 footer row
+        assert_eq!(rows.len(), 10, "{:?}", rows);
+
+        for (offset, height) in [
+            (0, Some(3)),
+            (4, Some(2)),
+            (5, Some(100)),
+            (8, None),
+            (100, Some(1)),
+        ] {
+            let printed = print(offset, height);
+            let end = height
+                .map(|h| cmp::min(offset + h, rows.len()))
+                .unwrap_or(rows.len());
+            let want = rows.get(offset..end).unwrap_or(&[]).concat();
+            assert_eq!(printed, want, "offset={} height={:?}", offset, height);
+        }
+
+        // Window starts at a row wrapped from the line 2
+        let printed = print(5, Some(1));
+        assert!(!printed.contains(" 2 "), "{:?}", printed);
+        assert!(printed.contains("xxxx"), "{:?}", printed);
+    }
+
+    #[test]
+    fn test_gutter_annotator() {
+        // Example annotator which shows test coverage of each line
+        struct Coverage {
+            hits: Vec<u64>,
+            misses: Vec<u64>,
+        }
+        impl GutterAnnotator for Coverage {
+            fn width(&self) -> usize {
+                2
+            }
+            fn annotate(&self, path: &Path, lnum: u64) -> Option<StyledCell> {
+                assert_eq!(path, Path::new("test.rs"));
+                let (text, r) = if self.hits.contains(&lnum) {
+                    ("ok", 0)
+                } else if self.misses.contains(&lnum) {
+                    ("miss", 255) // Truncated to the width
+                } else {
+                    return None;
+                };
+                let fg = Color {
+                    r,
+                    g: 1,
+                    b: 2,
+                    a: 255,
+                };
+                Some(StyledCell {
+                    text: text.to_string(),
+                    fg: Some(fg),
+                    bg: None,
+                })
+            }
+        }
+
+        let contents = b"fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n\nfn f() {}\n";
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(2)],
+            vec![(1, 3), (6, 6)],
+            contents.to_vec(),
+        );
+        let opts = PrinterOptions {
+            term_width: 24,
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.gutter_annotator(Coverage {
+            hits: vec![1, 2],
+            misses: vec![3],
+        });
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+        assert!(printed.contains("\x1b[38;2;255;1;2mmi"), "{:?}", printed);
+
+        let stripped = strip_escapes(&printed);
+        let want = "\
+────────────────────────
+ test.rs
+────────┬───────────────
+ok    1 │ fn main() {
+ok    2 │     let x = 1;
+mi    3 │     println!(\"
+        │ {}\", x);
+    ... ├╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶
+      6 │ fn f() {}
+────────┴───────────────
+";
+        assert_eq!(stripped, want);
+    }
+
+    #[test]
+    fn test_symbol_source() {
+        let contents =
+            b"fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n\nfn f() {}\n\nfn g() {}\n";
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(2), LineMatch::lnum(8)],
+            vec![(1, 3), (6, 6), (8, 8)],
+            contents.to_vec(),
+        );
+        let opts = PrinterOptions {
+            term_width: 24,
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        // Dummy source of symbols which knows only `main` and `g`. The line of the first match in each chunk is queried
+        printer.symbol_source(|path, lnum| {
+            assert_eq!(path, Path::new("test.rs"));
+            match lnum {
+                2 => Some("fn main".to_string()),
+                8 => Some("fn g".to_string()),
+                6 => None,
+                _ => panic!("unexpected line {}", lnum),
+            }
+        });
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+
+        let stripped = strip_escapes(&printed);
+        let want = "\
+────────────────────────
+ test.rs
+─────┬──────────────────
+     │ in fn main
+   1 │ fn main() {
+   2 │     let x = 1;   
+   3 │     println!(\"{}\"
+     │ , x);
+ ... ├╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶
+   6 │ fn f() {}
+ ... ├╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶
+     │ in fn g
+   8 │ fn g() {}        
+─────┴──────────────────
+";
+        assert_eq!(stripped, want);
+    }
+
+    #[test]
+    fn test_symbol_source_wider_than_terminal() {
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 1)],
+            b"fn f() {}\n".to_vec(),
+        );
+        let opts = PrinterOptions {
+            term_width: 21,
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.symbol_source(|_, _| Some("関数名前関数名前".to_string()));
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+
+        // The body is 16 cells wide. The 7th wide character does not fit in it
+        let stripped = strip_escapes(&printed);
+        let want = "\
+─────────────────────
+ test.rs
+───┬─────────────────
+   │ in 関数名前関数
+ 1 │ fn f() {}       
+───┴─────────────────
+";
+        assert_eq!(stripped, want);
+    }
+
+    #[test]
+    fn test_disabled_font_styles() {
+        // Monokai Extended underlines the name of the base class
+        let file = File::new(
+            PathBuf::from("test.py"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 2)],
+            b"class Foo(Bar):\n    pass\n".to_vec(),
+        );
+        let print = |disabled_font_styles| {
+            let opts = PrinterOptions {
+                color_support: TermColorSupport::True,
+                disabled_font_styles,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file.clone()).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            String::from_utf8(printed).unwrap()
+        };
+
+        let printed = print(vec![]);
+        assert!(printed.contains("\x1b[4mBar"), "{:?}", printed);
+        assert!(printed.contains("\x1b[24m"), "{:?}", printed);
+
+        let printed = print(vec![FontStyleKind::Underline]);
+        assert!(!printed.contains("\x1b[4m"), "{:?}", printed);
+        assert!(!printed.contains("\x1b[24m"), "{:?}", printed);
+        assert!(printed.contains("Bar"), "{:?}", printed);
+    }
+
+    #[test]
+    fn test_form_feed_between_files() {
+        let file = |name: &str| {
+            File::new(
+                PathBuf::from(name),
+                vec![LineMatch::lnum(1)],
+                vec![(1, 1)],
+                b"fn main() {}\n".to_vec(),
+            )
+        };
+        let opts = PrinterOptions {
+            form_feed: true,
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file("a.rs")).unwrap();
+        printer
+            .print(File::new(PathBuf::from("empty.rs"), vec![], vec![], vec![]))
+            .unwrap();
+        printer.print(file("b.rs")).unwrap();
+        printer.print(file("c.rs")).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+
+        // No form feed before the first file, after the last file, or for files with nothing to print
+        let pages: Vec<_> = printed.split('\x0c').collect();
+        assert_eq!(pages.len(), 3, "{:?}", printed);
+        for (page, name) in pages.iter().zip(["a.rs", "b.rs", "c.rs"]) {
+            assert!(page.contains(name), "{:?}", page);
+            assert!(page.ends_with('\n'), "{:?}", page);
+        }
+    }
+
+    #[test]
+    fn test_long_token_is_not_drawn_past_right_edge() {
+        let contents = format!("let s = \"{}\";\n", "QmxvYg".repeat(100_000));
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 1)],
+            contents.into_bytes(),
+        );
+        let clipped = PrinterOptions {
+            text_wrap: TextWrapMode::Never,
+            border: BorderStyle::Rounded,
+            ..Default::default()
+        };
+        let truncated = PrinterOptions {
+            auto_truncate_after: Some(1),
+            ..Default::default()
+        };
+        for opts in [clipped, truncated] {
+            let opts = PrinterOptions {
+                term_width: 80,
+                color_support: TermColorSupport::True,
+                ..opts
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file.clone()).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            // Only the rows visible in the terminal are written
+            assert!(printed.len() < 4096, "{} bytes", printed.len());
+        }
+    }
+
+    #[test]
+    fn test_no_wrap_writes_text_past_right_edge() {
+        let long = format!(
+            "let s = \"{}\ttab {}\";",
+            "QmxvYg".repeat(30),
+            "QmxvYg".repeat(30)
+        );
+        let contents = format!("fn main() {{\n    {}\n}}\n", long);
+        let start = 4 + long.find("tab").unwrap();
+        // The region in the context line is past the right edge of the narrow terminal
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 3)],
+            contents.into_bytes(),
+        )
+        .with_context_regions(vec![LineMatch::new(2, vec![(start, start + 3)])]);
+        let print = |term_width| {
+            let opts = PrinterOptions {
+                term_width,
+                text_wrap: TextWrapMode::Never,
+                color_support: TermColorSupport::True,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file.clone()).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let printed = String::from_utf8(printed).unwrap();
+            printed.lines().nth(4).unwrap().to_string()
+        };
+        // The line overflowing the terminal is written in the same way as the line fitting in a wide terminal
+        let (narrow, wide) = (print(80), print(500));
+        assert_eq!(narrow, wide);
+        let stripped = strip_escapes(&narrow);
+        assert!(stripped.contains("QmxvYg    tab QmxvYg"), "{:?}", stripped);
+        assert!(stripped.ends_with("QmxvYg\";"), "{:?}", stripped);
+    }
+
+    #[test]
+    fn test_output_width() {
+        let contents = format!("fn main() {{\n    {}\n}}\n", "x".repeat(200));
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(2)],
+            vec![(1, 3)],
+            contents.into_bytes(),
+        );
+        // The width of the terminal is ignored
+        let opts = PrinterOptions {
+            term_width: 40,
+            output_width: Some(120),
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+
+        let stripped = strip_escapes(&printed);
+        let rows: Vec<_> = stripped.lines().collect();
+        assert_eq!(rows.len(), 8, "{}", stripped);
+        for row in [rows[0], rows[2], rows[7]] {
+            assert_eq!(row.chars().count(), 120, "{:?}", row);
+            assert!(row.chars().all(|c| "─┬┴".contains(c)), "{:?}", row);
+        }
+        // The matched line is wrapped at the same width as the decorations
+        assert_eq!(rows[4], format!(" 2 │     {}", "x".repeat(111)));
+        assert_eq!(
+            rows[5],
+            format!("   │ {}{}", "x".repeat(89), " ".repeat(26))
+        );
+    }
+
+    #[test]
+    fn test_chunks_beyond_end_of_file() {
+        fn print(file: File, term_width: u16) -> String {
+            let opts = PrinterOptions {
+                term_width,
+                color_support: TermColorSupport::True,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            strip_escapes(&String::from_utf8(printed).unwrap())
+        }
+
+        let contents = b"foo\nbar\nbaz\n";
+        let file = File::new(
+            PathBuf::from("test.txt"),
+            vec![LineMatch::lnum(2), LineMatch::lnum(6)],
+            vec![(1, 4), (6, 8)],
+            contents.to_vec(),
+        );
+        let want = "\
+────────────────────────────────────────
+ test.txt
+─────┬──────────────────────────────────
+   1 │ foo
+   2 │ bar                              
+   3 │ baz
+     │ (lines 4,6-8 no longer present)
+─────┴──────────────────────────────────
+";
+        assert_eq!(print(file, 40), want);
+
+        let file = File::new(
+            PathBuf::from("test.txt"),
+            vec![LineMatch::lnum(2), LineMatch::lnum(6)],
+            vec![(1, 2), (6, 6)],
+            contents.to_vec(),
+        );
+        let want = "\
+────────────────────────────────
+ test.txt
+─────┬──────────────────────────
+   1 │ foo
+   2 │ bar                      
+ ... ├╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶╶
+     │ (line 6 no longer present
+─────┴──────────────────────────
+";
+        assert_eq!(print(file, 32), want);
+    }
+
+    #[test]
+    fn test_h_scroll() {
+        let tests = [
+            ("abcdefgh", 3, 4, "defgh"),
+            ("abc", 5, 4, ""),
+            // Wide character lying across the left edge is rendered as space
+            ("あいう", 1, 4, " いう"),
+            ("あいう", 2, 4, "いう"),
+            // Combining character following skipped character is also skipped
+            ("e\u{301}xyz", 1, 4, "xyz"),
+            // Tab lying across the left edge is rendered as spaces
+            ("a\tb", 3, 4, " b"),
+            // Hard tab at column 4 (after 3 cells gutter) advances 4 cells
+            ("a\tb", 3, 0, "  b"),
+        ];
+
+        for (line, h_scroll, tab_width, want) in tests {
+            let contents = format!("{}\n", line).into_bytes();
+            let file = File::new(PathBuf::from("test.txt"), vec![], vec![(1, 1)], contents);
+            let opts = PrinterOptions {
+                h_scroll,
+                tab_width,
+                align_tabs: true,
+                grid: false,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let printed = strip_escapes(&String::from_utf8(printed).unwrap());
+            let body = printed.lines().last().unwrap();
+            let body = body.strip_prefix(" 1 ").unwrap();
+            assert_eq!(
+                body.trim_end_matches(' '),
+                want,
+                "line={:?} h_scroll={}",
+                line,
+                h_scroll
+            );
+        }
+    }
+
+    #[test]
+    fn test_line_hook() {
+        let file = sample_chunk("README.md");
+        let opts = PrinterOptions {
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.line_hook(|path, lnum, tokens| {
+            assert_eq!(path, Path::new("README.md"));
+            if lnum == 2 {
+                for (style, _) in tokens.iter_mut() {
+                    style.foreground = Color {
+                        r: 1,
+                        g: 2,
+                        b: 3,
+                        a: 255,
+                    };
+                }
+            }
+        });
+        printer.print(file).unwrap();
+
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let expected = b"\x1b[38;2;1;2;3m";
+        let lines: Vec<_> = printed.split_inclusive(|b| *b == b'\n').collect();
+        let has_color = |line: &[u8]| line.windows(expected.len()).any(|s| s == expected);
+        assert!(has_color(lines[4]), "line={:?}", str::from_utf8(lines[4]));
+        assert!(!has_color(lines[3]), "line={:?}", str::from_utf8(lines[3]));
+    }
+
+    #[test]
+    fn test_find_syntax_for_archive_member() {
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let tests = [
+            ("foo.tar!src/lib.rs", "", "Rust"),
+            ("foo.zip!Makefile", "", "Makefile"),
+            (
+                "foo.tar.gz!bin/script",
+                "#!/bin/bash\necho hi\n",
+                "Bourne Again Shell (bash)",
+            ),
+            ("foo.tgz!unknown", "hello\n", "Plain Text"),
+        ];
+        for (path, contents, want) in tests {
+            let syntax = printer.find_syntax(Path::new(path), contents.as_bytes(), None);
+            assert_eq!(syntax.name, want, "path={:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_print_file_at_revision() {
+        let path = PathBuf::from("src/lib.rs@HEAD~1");
+        let contents = b"fn main() {\n    println!(\"hello\");\n}\n".to_vec();
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        assert_eq!(printer.find_syntax(&path, &contents, None).name, "Rust");
+        assert_eq!(
+            printer
+                .find_syntax(Path::new("foo.tar!lib.rs@main"), b"", None)
+                .name,
+            "Rust",
+        );
+
+        let file = File::new(path, vec![LineMatch::lnum(2)], vec![(1, 3)], contents);
+        let mut printer = printer;
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+        assert!(printed.contains(" src/lib.rs@HEAD~1"), "{:?}", printed);
+    }
+
+    #[test]
+    fn test_print_bytes_with_language() {
+        let print = |file: File| {
+            let opts = PrinterOptions {
+                color_support: TermColorSupport::True,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            String::from_utf8(printed).unwrap()
+        };
+        let contents = "fn main() {\n    println!(\"hello\");\n}\n";
+        let file = |name: &str, lang| {
+            File::from_bytes(name, lang, contents, vec![LineMatch::lnum(2)], vec![(1, 3)])
+        };
+
+        // The language is specified by its name or its extension regardless of the name hint
+        let want = print(file("fetched.rs", None)).replace("fetched.rs", "<fetched>");
+        for lang in ["Rust", "rust", "rs"] {
+            let printed = print(file("<fetched>", Some(lang)));
+            assert_eq!(printed, want, "lang={:?}", lang);
+        }
+
+        // Unknown language falls back to the detection from the name hint
+        let printed = print(file("fetched.rs", Some("unknown-language")));
+        assert_eq!(printed.replace("fetched.rs", "<fetched>"), want);
+        let printed = print(file("<fetched>", None));
+        assert_ne!(printed, want);
+
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let (syntax, reason) = printer.detect_syntax(Path::new("foo.c"), b"", Some("rs"));
+        assert_eq!(syntax.name, "Rust");
+        assert_eq!(reason, SyntaxReason::Language);
+    }
+
+    #[test]
+    fn test_find_syntax_by_file_name() {
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let tests = [
+            ("Dockerfile", "Dockerfile"),
+            ("path/to/Dockerfile", "Dockerfile"),
+            ("Dockerfile.dev", "Dockerfile"),
+            ("Containerfile", "Dockerfile"),
+            ("Makefile", "Makefile"),
+            ("GNUmakefile", "Makefile"),
+            ("CMakeLists.txt", "CMake"),
+            (".bashrc", "Bourne Again Shell (bash)"),
+            (".clang-format", "YAML"),
+            (".babelrc", "JSON"),
+            ("foo.fs", "F#"),
+        ];
+        for (path, want) in tests {
+            let syntax = printer.find_syntax(Path::new(path), b"", None);
+            assert_eq!(syntax.name, want, "path={:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_find_syntax_for_header_file() {
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let c_header = "#ifdef __cplusplus\nextern \"C\" {\n#endif\ntypedef struct { int x; } foo_t;\nint foo(foo_t *f);\n#ifdef __cplusplus\n}\n#endif\n";
+        let cpp_header =
+            "#pragma once\nnamespace foo {\nclass Bar {\n  public:\n    int x;\n};\n}\n";
+        let objc_header = "#import <Foundation/Foundation.h>\n@interface Foo : NSObject\n@end\n";
+        let tests = [
+            ("foo.h", c_header, "C"),
+            ("foo.h", cpp_header, "C++"),
+            ("foo.h", objc_header, "Objective-C"),
+            ("foo.h", "int foo(void);\n", "C++"),
+            ("foo.c", cpp_header, "C"),
+        ];
+        for (path, contents, want) in tests {
+            let syntax = printer.find_syntax(Path::new(path), contents.as_bytes(), None);
+            assert_eq!(syntax.name, want, "contents={:?}", contents);
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let opts = PrinterOptions {
+            theme: Some("Nord"),
+            syntax_mappings: vec![("*.build", "Python")],
+            ..Default::default()
+        };
+        let printer =
+            SyntectPrinter::with_assets(ASSETS.clone(), DummyStdout(RefCell::new(vec![])), opts);
+        let tests = [
+            (
+                "meson.build",
+                "",
+                "Python",
+                SyntaxReason::Mapping("*.build".into()),
+            ),
+            (
+                "foo.h",
+                "class Foo {};\n",
+                "C++",
+                SyntaxReason::HeaderContents,
+            ),
+            ("Makefile", "", "Makefile", SyntaxReason::FileName),
+            ("foo.rs", "", "Rust", SyntaxReason::Extension),
+            (
+                "script",
+                "#!/bin/bash\n",
+                "Bourne Again Shell (bash)",
+                SyntaxReason::FirstLine,
+            ),
+            ("foo.unknown", "", "Plain Text", SyntaxReason::PlainText),
+        ];
+        for (path, contents, syntax, reason) in tests {
+            let explained = printer.explain(Path::new(path), contents.as_bytes());
+            let want = Explanation {
+                path: PathBuf::from(path),
+                syntax: syntax.to_string(),
+                reason,
+                theme: "Nord".to_string(),
+            };
+            assert_eq!(explained, want);
+        }
+    }
+
+    #[test]
+    fn test_explanation_output() {
+        let explained = Explanation {
+            path: PathBuf::from("path/to/\"foo\".build"),
+            syntax: "Python".to_string(),
+            reason: SyntaxReason::Mapping("*.build".to_string()),
+            theme: "Nord".to_string(),
+        };
+        assert_eq!(
+            explained.to_string(),
+            "path: path/to/\"foo\".build\nsyntax: Python (matched to syntax mapping '*.build')\ntheme: Nord",
+        );
+        assert_eq!(
+            explained.to_json(),
+            r#"{"path":"path/to/\"foo\".build","syntax":"Python","reason":"mapping","glob":"*.build","theme":"Nord"}"#,
+        );
+
+        let explained = Explanation {
+            reason: SyntaxReason::Extension,
+            ..explained
+        };
+        assert_eq!(
+            explained.to_json(),
+            r#"{"path":"path/to/\"foo\".build","syntax":"Python","reason":"extension","theme":"Nord"}"#,
+        );
+    }
+
+    #[test]
+    fn test_custom_syntax_mappings() {
+        let opts = PrinterOptions {
+            syntax_mappings: vec![
+                ("*.build", "Python"),
+                ("**/.config/*.conf", "INI"),
+                ("*.h", "C"),
+            ],
+            ..Default::default()
+        };
+        let printer =
+            SyntectPrinter::with_assets(ASSETS.clone(), DummyStdout(RefCell::new(vec![])), opts);
+        let tests = [
+            ("meson.build", "Python"),
+            ("/home/foo/.config/app.conf", "INI"),
+            ("nginx.conf", "nginx"),
+            ("foo.h", "C"),
+        ];
+        for (path, want) in tests {
+            let syntax = printer.find_syntax(Path::new(path), b"", None);
+            assert_eq!(syntax.name, want, "path={:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_invalid_syntax_mappings() {
+        for (mapping, want) in [
+            (
+                ("*.foo", "Unknown Syntax"),
+                "Unknown syntax 'Unknown Syntax'",
+            ),
+            (("[a-", "Rust"), "Invalid syntax mapping"),
+        ] {
+            let opts = PrinterOptions {
+                syntax_mappings: vec![mapping],
+                ..Default::default()
+            };
+            let err = match SyntectPrinter::new(DummyStdout(RefCell::new(vec![])), opts) {
+                Err(e) => e,
+                Ok(_) => panic!("error did not occur"),
+            };
+            assert!(
+                matches!(err, HgrepError::InvalidSyntaxMapping(_)),
+                "{:?}",
+                err
+            );
+            let msg = format!("{}", err);
+            assert!(msg.contains(want), "message={:?}", msg);
+        }
+    }
+
+    #[test]
+    fn test_language_option() {
+        for lang in ["Rust", "rust", "rs"] {
+            let opts = PrinterOptions {
+                language: Some(lang),
+                ..Default::default()
+            };
+            let printer = SyntectPrinter::with_assets(
+                ASSETS.clone(),
+                DummyStdout(RefCell::new(vec![])),
+                opts,
+            );
+            let (syntax, reason) = printer.detect_syntax(Path::new("foo.txt"), b"", None);
+            assert_eq!(syntax.name, "Rust", "lang={:?}", lang);
+            assert_eq!(reason, SyntaxReason::Language);
+
+            // The language of the file is preferred
+            let syntax = printer.find_syntax(Path::new("foo.txt"), b"", Some("py"));
+            assert_eq!(syntax.name, "Python", "lang={:?}", lang);
+        }
+
+        let opts = PrinterOptions {
+            language: Some("Unknown Language"),
+            ..Default::default()
+        };
+        let err = match SyntectPrinter::new(DummyStdout(RefCell::new(vec![])), opts) {
+            Err(e) => e,
+            Ok(_) => panic!("error did not occur"),
+        };
+        assert!(matches!(err, HgrepError::UnknownLanguage(_)), "{:?}", err);
+        assert_eq!(format!("{}", err), "Unknown language 'Unknown Language'");
+    }
+
+    #[test]
+    fn test_is_plain_text() {
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let tests = [
+            ("foo.rs", "", false),
+            ("foo.unknown", "", true),
+            ("LICENSE", "MIT License\n", true),
+            ("script", "#!/usr/bin/env python\nprint(1)\n", false),
+            ("foo.tar!src/lib.rs", "", false),
+        ];
+        for (path, contents, want) in tests {
+            let have = printer.is_plain_text(Path::new(path), contents.as_bytes());
+            assert_eq!(have, want, "path={:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_print_archive_member() {
+        let path = PathBuf::from("foo.tar!src/lib.rs");
+        let contents = b"fn main() {\n    println!(\"hello\");\n}\n".to_vec();
+        let file = File::new(path, vec![LineMatch::lnum(2)], vec![(1, 3)], contents);
+        let opts = PrinterOptions::default();
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+        assert!(printed.contains(" foo.tar!src/lib.rs"), "{:?}", printed);
+    }
+
+    #[test]
+    fn test_strict_utf8() {
+        let contents = b"fn main() {\n    println!(\"\xff\");\n}\n".to_vec();
+        for (strict, skipped) in [(false, false), (true, true)] {
+            let file = File::new(
+                PathBuf::from("test.rs"),
+                vec![LineMatch::lnum(2)],
+                vec![(1, 3)],
+                contents.clone(),
+            );
+            let opts = PrinterOptions {
+                strict_utf8: strict,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let printed = String::from_utf8(printed).unwrap();
+            assert_eq!(
+                printed.contains("invalid UTF-8 at line 2, skipped"),
+                skipped,
+                "{:?}",
+                printed,
+            );
+            assert_eq!(printed.contains("main"), !skipped, "{:?}", printed);
+        }
+    }
+
+    #[test]
+    fn test_decode_line() {
+        let bytes = b"a\xff\xfeb foo\xe3\x81 bar\n";
+        let regions = Some(vec![(4, 7), (11, 14)]);
+
+        let decoded = decode_line(bytes, regions.clone(), InvalidUtf8::default());
+        assert_eq!(decoded.text, "a\u{fffd}\u{fffd}b foo\u{fffd} bar\n");
+        assert_eq!(decoded.regions, Some(vec![(8, 11), (16, 19)]));
+        assert!(decoded.escapes.is_empty());
+
+        let decoded = decode_line(bytes, regions.clone(), InvalidUtf8::Replace('?'));
+        assert_eq!(decoded.text, "a??b foo? bar\n");
+        assert_eq!(decoded.regions, Some(vec![(4, 7), (10, 13)]));
+
+        let decoded = decode_line(bytes, regions, InvalidUtf8::Escape);
+        assert_eq!(decoded.text, "a\\xff\\xfeb foo\\xe3\\x81 bar\n");
+        assert_eq!(decoded.regions, Some(vec![(10, 13), (23, 26)]));
+        assert_eq!(decoded.escapes, vec![(1, 5), (5, 9), (14, 22)]);
+
+        let decoded = decode_line(b"foo\n", None, InvalidUtf8::Escape);
+        assert!(matches!(decoded.text, Cow::Borrowed("foo\n")));
+    }
+
+    #[test]
+    fn test_align_fields() {
+        let contents = b"a,bb,c\nxxx,\"y,z\",w\n\nskipped,line\n";
+        let columns = column_widths(contents, &[(1, 2)], ',');
+        assert_eq!(columns, vec![vec![3, 5]]);
+
+        let line = DecodedLine {
+            text: Cow::Borrowed("a,bb,c\n"),
+            regions: Some(vec![(0, 1), (1, 2), (2, 4)]),
+            escapes: vec![],
+        };
+        let aligned = align_fields(line, &columns[0], ',');
+        assert_eq!(aligned.text, "a  ,bb   ,c\n");
+        assert_eq!(aligned.regions, Some(vec![(0, 1), (3, 4), (4, 6)]));
+
+        // Delimiters in quotes are not aligned
+        let line = DecodedLine {
+            text: Cow::Borrowed("xxx,\"y,z\",w\n"),
+            regions: None,
+            escapes: vec![],
+        };
+        let aligned = align_fields(line, &columns[0], ',');
+        assert_eq!(aligned.text, "xxx,\"y,z\",w\n");
+
+        // Tab delimiters are replaced with spaces
+        let line = DecodedLine {
+            text: Cow::Borrowed("1\t200\n"),
+            regions: Some(vec![(2, 5)]),
+            escapes: vec![(0, 1)],
+        };
+        let aligned = align_fields(line, &[3], '\t');
+        assert_eq!(aligned.text, "1   200\n");
+        assert_eq!(aligned.regions, Some(vec![(4, 7)]));
+        assert_eq!(aligned.escapes, vec![(0, 1)]);
+
+        // Line without delimiter is not modified
+        let line = DecodedLine {
+            text: Cow::Borrowed("foo\n"),
+            regions: None,
+            escapes: vec![],
+        };
+        let aligned = align_fields(line, &[3], ',');
+        assert!(matches!(aligned.text, Cow::Borrowed("foo\n")));
+    }
+
+    #[test]
+    fn test_tabular_delimiter() {
+        let mut opts = PrinterOptions::default();
+        assert_eq!(tabular_delimiter(&opts, Path::new("a.csv")), None);
+        opts.tabular = true;
+        assert_eq!(tabular_delimiter(&opts, Path::new("a.csv")), Some(','));
+        assert_eq!(tabular_delimiter(&opts, Path::new("a.CSV")), Some(','));
+        assert_eq!(tabular_delimiter(&opts, Path::new("a.tsv")), Some('\t'));
+        assert_eq!(tabular_delimiter(&opts, Path::new("a")), Some('\t'));
+        opts.delimiter = Some('|');
+        assert_eq!(tabular_delimiter(&opts, Path::new("a.csv")), Some('|'));
+    }
+
+    #[test]
+    fn test_print_invalid_utf8_escape() {
+        let contents = b"fn main() {\n    println!(\"\xff\");\n}\n".to_vec();
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![LineMatch::lnum(2)],
+            vec![(1, 3)],
+            contents,
+        );
+        let opts = PrinterOptions {
+            invalid_utf8: InvalidUtf8::Escape,
+            color_support: TermColorSupport::True,
+            ..Default::default()
+        };
+        let stdout = DummyStdout(RefCell::new(vec![]));
+        let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+        printer.print(file).unwrap();
+        let printed = mem::take(printer.writer_mut()).0.into_inner();
+        let printed = String::from_utf8(printed).unwrap();
+        assert!(!printed.contains('\u{fffd}'), "{:?}", printed);
+
+        // The escape is drawn in a different color from the string literal around it
+        let line = printed.lines().find(|l| l.contains("println")).unwrap();
+        let (before, after) = line.split_once("\\xff").unwrap();
+        let fg_before = &before[before.rfind("\x1b[38;").unwrap()..];
+        assert!(!fg_before.contains('"'), "{:?}", line);
+        assert!(after.starts_with("\x1b[38;"), "{:?}", line);
+    }
+
+    #[test]
+    fn test_print_literal_tabs_in_strings() {
+        let contents = b"fn main() {\n\tprintln!(\"a\\tb\ta\"); // c\td\n}\n".to_vec();
+        let print = |literal_tabs_in_strings| {
+            let file = File::new(
+                PathBuf::from("test.rs"),
+                vec![LineMatch::lnum(2)],
+                vec![(1, 3)],
+                contents.clone(),
+            );
+            let opts = PrinterOptions {
+                tab_width: 4,
+                literal_tabs_in_strings,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let printed = String::from_utf8(printed).unwrap();
+            printed
+                .lines()
+                .find(|l| l.contains("println"))
+                .unwrap()
+                .to_string()
+        };
+
+        let line = print(false);
+        assert!(!line.contains('\t'), "{:?}", line);
+
+        // Only the tabs in the string literal and the comment are kept. The indentation is expanded
+        let line = print(true);
+        assert_eq!(line.matches('\t').count(), 2, "{:?}", line);
+        assert!(line.contains("    "), "{:?}", line);
+        assert!(!line.contains("\tprintln"), "{:?}", line);
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(b"fn main() {}\n"));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\0\0"));
+        let mut late_nul = vec![b'a'; BINARY_DETECTION_BYTES];
+        late_nul.push(b'\0');
+        assert!(!is_binary(&late_nul));
+    }
+
+    #[test]
+    fn test_print_binary_file() {
+        let contents = b"foo\0bar\nmatch\nbaz\n".to_vec();
+        for (as_text, skipped) in [(false, true), (true, false)] {
+            let file = File::new(
+                PathBuf::from("test.bin"),
+                vec![LineMatch::lnum(2)],
+                vec![(1, 3)],
+                contents.clone(),
+            );
+            let opts = PrinterOptions {
+                binary_as_text: as_text,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            let printed = String::from_utf8(printed).unwrap();
+            assert_eq!(
+                printed.contains("binary file, match on line 2"),
+                skipped,
+                "{:?}",
+                printed,
+            );
+            assert_eq!(printed.contains("baz"), !skipped, "{:?}", printed);
+        }
+    }
+
+    #[test]
+    fn test_hyperlink_line_numbers() {
+        let print = |hyperlinks| {
+            let contents = b"fn main() {\n    println!(\"hello\");\n}\n".to_vec();
+            let path = std::env::current_dir().unwrap().join("test.rs");
+            let file = File::new(path, vec![LineMatch::lnum(2)], vec![(1, 3)], contents);
+            let opts = PrinterOptions {
+                hyperlinks,
+                ..Default::default()
+            };
+            let stdout = DummyStdout(RefCell::new(vec![]));
+            let mut printer = SyntectPrinter::with_assets(ASSETS.clone(), stdout, opts);
+            printer.print(file).unwrap();
+            let printed = mem::take(printer.writer_mut()).0.into_inner();
+            String::from_utf8(printed).unwrap()
+        };
+
+        let plain = print(false);
+        let linked = print(true);
+        let url = file_url(&std::env::current_dir().unwrap().join("test.rs"), None).unwrap();
+        for lnum in 1..=3 {
+            let anchor = format!(" \x1b]8;;{}#L{}\x1b\\{}\x1b]8;;\x1b\\", url, lnum, lnum);
+            assert!(linked.contains(&anchor), "{:?}", linked);
+        }
+
+        // Removing the OSC 8 sequences must restore the output without hyperlinks
+        let mut stripped = String::new();
+        let mut rest = linked.as_str();
+        while let Some(i) = rest.find("\x1b]8;") {
+            stripped.push_str(&rest[..i]);
+            let end = rest[i..].find("\x1b\\").unwrap();
+            rest = &rest[i + end + 2..];
+        }
+        stripped.push_str(rest);
+        assert_eq!(stripped, plain);
+    }
+
+    #[test]
+    fn test_render_events() {
+        let contents = "fn main() {\n    println!(\"hello\");\n}\n\n\nfn foo() {}\n";
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![
+                LineMatch::new(2, vec![(13, 20)]),
+                LineMatch::new(6, vec![(3, 6)]).with_replacement("fn bar() {}"),
+            ],
+            vec![(1, 3), (6, 6)],
+            contents.as_bytes().to_vec(),
+        );
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let mut events = vec![];
+        printer.render(&file, |e| events.push(e));
+
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| match e {
+                RenderEvent::Header { path, notice } => format!("header {:?} {:?}", path, notice),
+                RenderEvent::Line {
+                    lnum,
+                    matched,
+                    spans,
+                } => {
+                    let text: String = spans.iter().map(|s| s.text.as_str()).collect();
+                    let region: String = spans
+                        .iter()
+                        .filter(|s| s.in_match)
+                        .map(|s| s.text.as_str())
+                        .collect();
+                    format!("line {} {} {:?} {:?}", lnum, matched, text, region)
+                }
+                RenderEvent::Replacement { lnum, spans } => {
+                    let text: String = spans.iter().map(|s| s.text.as_str()).collect();
+                    format!("replacement {} {:?}", lnum, text)
+                }
+                RenderEvent::Separator { skipped } => format!("separator {}", skipped),
+                RenderEvent::Footer => "footer".to_string(),
+            })
+            .collect();
+        let want = [
+            r#"header "test.rs" None"#,
+            r#"line 1 false "fn main() {" """#,
+            r#"line 2 true "    println!(\"hello\");" "\"hello\"""#,
+            r#"line 3 false "}" """#,
+            "separator 2",
+            r#"line 6 true "fn foo() {}" "foo""#,
+            r#"replacement 6 "fn bar() {}""#,
+            "footer",
+        ];
+        assert_eq!(summary, want);
+
+        // Spans in a region keep styles of the highlighted tokens
+        let RenderEvent::Line { spans, .. } = &events[2] else {
+            panic!("unexpected event: {:?}", events[2]);
+        };
+        assert!(
+            spans.iter().filter(|s| s.in_match).count() > 1,
+            "{:?}",
+            spans
+        );
+    }
+
+    #[test]
+    fn test_highlight_file() {
+        let contents = "fn main() {\n    println!(\"hello\");\n}\n\n\nfn foo() {}\n";
+        let file = File::new(
+            PathBuf::from("test.rs"),
+            vec![
+                LineMatch::new(2, vec![(13, 20)]),
+                LineMatch::new(6, vec![(3, 6)]).with_replacement("fn bar() {}"),
+            ],
+            vec![(1, 3), (6, 6)],
+            contents.as_bytes().to_vec(),
+        );
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let lines = printer.highlight_file(&file);
+
+        let summary: Vec<_> = lines
+            .iter()
+            .map(|(lnum, matched, spans)| {
+                let text: String = spans.iter().map(|(_, s)| s.as_str()).collect();
+                format!("{} {} {:?}", lnum, matched, text)
+            })
+            .collect();
+        let want = [
+            r#"1 false "fn main() {""#,
+            r#"2 true "    println!(\"hello\");""#,
+            r#"3 false "}""#,
+            r#"6 true "fn foo() {}""#,
+        ];
+        assert_eq!(summary, want);
+
+        // The string literal is one span even though the matched region is a part of it
+        let (_, _, spans) = &lines[1];
+        for (i, (style, _)) in spans.iter().enumerate().skip(1) {
+            assert_ne!(*style, spans[i - 1].0, "{:?}", spans);
+        }
+        assert!(
+            spans.iter().any(|(_, s)| s.contains("\"hello\"")),
+            "{:?}",
+            spans
+        );
+    }
+
+    #[test]
+    fn test_render_overlapping_chunks() {
+        let contents = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let file = File::new(
+            PathBuf::from("test.txt"),
+            vec![LineMatch::lnum(2), LineMatch::lnum(4), LineMatch::lnum(9)],
+            vec![(1, 4), (4, 6), (5, 5), (9, 10)],
+            contents.as_bytes().to_vec(),
+        );
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let mut events = vec![];
+        printer.render(&file, |e| events.push(e));
+
+        let summary: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                RenderEvent::Line { lnum, matched, .. } => Some(format!("{} {}", lnum, matched)),
+                RenderEvent::Separator { skipped } => Some(format!("separator {}", skipped)),
+                _ => None,
+            })
+            .collect();
+        let want = [
+            "1 false",
+            "2 true",
+            "3 false",
+            "4 true",
+            "5 false",
+            "6 false",
+            "separator 2",
+            "9 true",
+            "10 false",
+        ];
+        assert_eq!(summary, want);
+    }
+
+    #[test]
+    fn test_render_events_for_skipped_file() {
+        let file = File::new(
+            PathBuf::from("test.bin"),
+            vec![LineMatch::lnum(1)],
+            vec![(1, 1)],
+            b"foo\0\n".to_vec(),
+        );
+        let printer = SyntectPrinter::with_assets(
+            ASSETS.clone(),
+            DummyStdout(RefCell::new(vec![])),
+            PrinterOptions::default(),
+        );
+        let mut events = vec![];
+        printer.render(&file, |e| events.push(e));
+        let want = vec![
+            RenderEvent::Header {
+                path: PathBuf::from("test.bin"),
+                notice: Some("binary file, match on line 1".to_string()),
+            },
+            RenderEvent::Footer,
+        ];
+        assert_eq!(events, want);
+    }
+
+    #[test]
+    fn test_count_lines() {
+        let tests: &[(&[u8], usize)] = &[
+            (b"", 0),
+            (b"\n", 1),
+            (b"foo", 1),
+            (b"foo\n", 1),
+            (b"foo\nbar", 2),
+            (b"foo\r\nbar\r\n", 2),
+            (b"\n\n\n", 3),
+        ];
+        for (contents, want) in tests {
+            assert_eq!(count_lines(contents), *want, "contents={:?}", contents);
+        }
+    }
+
+    #[test]
+    fn test_sparkline_level() {
+        let tests = [
+            (0, 1),
+            (1, 1),
+            (2, 2),
+            (3, 2),
+            (4, 3),
+            (7, 3),
+            (8, 4),
+            (127, 7),
+            (128, 8),
+            (100000, 8),
+        ];
+        for (count, want) in tests {
+            assert_eq!(sparkline_level(count), want, "count={}", count);
+        }
+    }
+
+    #[test]
+    fn test_num_digits() {
+        let tests = [
+            (0, 1),
+            (1, 1),
+            (9, 1),
+            (10, 2),
+            (99, 2),
+            (100, 3),
+            (999, 3),
+            (1000, 4),
+            (10000, 5),
+            (999_999_999_999_999_999, 18),
+            (1_000_000_000_000_000_000, 19),
+            (u64::MAX, 20),
+        ];
+        for (n, want) in tests {
+            assert_eq!(num_digits(n), want, "n={}", n);
+        }
+    }
+
+    #[test]
+    fn test_highlight_fallback_to_plain_text() {
+        let syntaxes = &ASSETS.syntax_set;
+        let syntax = syntaxes.find_syntax_by_extension("rs").unwrap();
+        let theme = ASSETS.theme_set.themes.values().next().unwrap();
+        let mut hl = LineHighlighter::new(syntax, theme, syntaxes);
+
+        // Unterminated raw string, unbalanced brackets and a stray lifetime which confuse the Rust syntax
+        for line in [
+            "let s = r##\"foo\"#; fn 'a<<<[[{(\n",
+            "}}}])) \"\\\n",
+            "\u{feff}\0\x7f'\n",
+        ] {
+            let tokens = hl.highlight(line);
+            let text: String = tokens.iter().map(|t| t.text).collect();
+            assert_eq!(text, line);
+            let tokens = hl.highlight_detached(line);
+            let text: String = tokens.iter().map(|t| t.text).collect();
+            assert_eq!(text, line);
+        }
+
+        // The raw text is rendered with the default style when no token was produced
+        let tokens = hl.or_plain_tokens("foo\n", vec![]);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].text, "foo\n");
+        assert_eq!(tokens[0].style.background, hl.hl.get_default().background);
+        assert!(hl.or_plain_tokens("", vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_hard_tab_width() {
+        assert_eq!(hard_tab_width(0), 8);
+        assert_eq!(hard_tab_width(5), 3);
+        assert_eq!(hard_tab_width(7), 1);
+        assert_eq!(hard_tab_width(8), 8);
+        assert_eq!(hard_tab_width(13), 3);
+    }
+
     #[test]
     fn test_wrote_error_on_list_themes() {
         let opts = PrinterOptions::default();
@@ -0,0 +1,6 @@
+fn main() {
+    let x = 1;
+    //                                                                     (width considering line number) 80 cols -> |
+    println!("*match to this line* {}", &["aaaaaaaaaaaaaaaaaaaaaaaaaa", "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbb", "cccccccccccccccc"]);
+    println!("{}", x);
+}
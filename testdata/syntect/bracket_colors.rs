@@ -0,0 +1,24 @@
+fn main() {
+    let v = vec![(1, [2, 3]), (4, [5, 6])];
+    for (a, b) in v.iter() {
+        if let Some(x) = b.get(0) {
+            // Brackets in comments (like this) are not colorized
+            println!("{}", f(a, g([x, "*match to (this) line*"])));
+        }
+    }
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    foo();
+    let y = h([1, 2]); // *match to this line*
+}
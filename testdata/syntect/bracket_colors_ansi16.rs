@@ -0,0 +1,8 @@
+fn main() {
+    let v = vec![(1, [2, 3]), (4, [5, 6])];
+    for (a, b) in v.iter() {
+        if let Some(x) = b.get(0) {
+            println!("{}", f(a, g([x, "*match to (this) line*"])));
+        }
+    }
+}
@@ -0,0 +1,11 @@
+fn main() {
+    log("retry");
+    log("retry");
+    log("retry");
+    println!("*match to this line*");
+    println!("*match to this line*");
+    println!("*match to this line*");
+    println!("*match to this line*");
+    log("done");
+    log("done");
+}
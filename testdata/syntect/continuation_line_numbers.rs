@@ -0,0 +1,4 @@
+fn main() {
+    // This line is long enough to be wrapped but short enough not to be truncated since it has only two wrapped rows of text ......................
+    let s = "*match to fooooooooooooooooooo fo foooooooooooooooooooo fofoooooooooooooooooooooooooooooo fooooooooooooooooooofofo  foooooooooooooooooooo fooofoo fooooooooooooooooooo fo foooooooooooooooooooo fofoooooooooooooooooooooooooooooo fooooooooooooooooooofofo  foooooooooooooooooooo fooofoo line*";
+}
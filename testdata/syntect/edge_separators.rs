@@ -0,0 +1,24 @@
+fn main() {
+    foo();
+    bar();
+    baz();
+    qux();
+    foo();
+    bar();
+    baz();
+    qux();
+    println!("*match to this line*");
+    foo();
+    bar();
+    baz();
+    qux();
+    foo();
+    bar();
+    baz();
+    qux();
+    foo();
+    bar();
+    baz();
+    qux();
+    foo();
+}
@@ -0,0 +1,7 @@
+println!("*match to this line*");
+fn main() {
+    foo();
+    bar();
+    baz();
+}
+println!("*match to this line*");
\ No newline at end of file
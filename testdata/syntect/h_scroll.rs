@@ -0,0 +1,7 @@
+fn main() {
+    let wide = "日本語の文字列";
+	let tab = 1;
+    println!("short");
+    let s = "text before *match to this line* and text after it which is very long to pan across";
+    let t = "x";
+}
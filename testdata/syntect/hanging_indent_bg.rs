@@ -0,0 +1,7 @@
+fn main() {
+    if true {
+        println!("this is a very long line which is wrapped at the right edge of the terminal {}", 42);
+        println!("*match to this line* which is also very long and wrapped at the right edge of the terminal");
+	let tab = "this line is indented with a hard tab and wrapped at the right edge of the terminal";
+    }
+}
@@ -0,0 +1,4 @@
+fn main() {
+	let s = "long line with tabs:	a	bb	ccc	dddd	eeeee	ffffff	ggggggg	hhhhhhhh	iiiiiiiii	jjjjjjjjjj	end";
+	println!("*match to this line*	{}	next	to	it	and	more	and	more	and	more", s);
+}
@@ -0,0 +1,11 @@
+mod foo {
+	impl Foo {
+		fn foo(&self) {
+			for i in 0..10 {
+				if i % 2 == 0 {
+					println!("*match to this line*");
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,10 @@
+println!("*match to this line*");
+fn main() {
+    foo();
+    bar();
+
+    baz();
+    qux();
+}
+
+fn foo() {}
@@ -0,0 +1,10 @@
+fn main() {
+    foo();
+    bar();
+
+    baz();
+    qux();
+}
+
+fn foo() {}
+println!("*match to this line*");
\ No newline at end of file
@@ -0,0 +1,5 @@
+fn main() {
+    let v = vec![1, 2, 3];
+    // *match to this line* and *match to this line* again
+    println!("*match to this line* {:?}", v);
+}
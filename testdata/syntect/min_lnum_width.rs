@@ -0,0 +1,5 @@
+fn main() {
+    let x = 1;
+    println!("*match to this line*");
+    let y = 2;
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let s = "*match to this line*";
+    println!("{}{}", "*match to a line*", "*match to b line*");
+}
@@ -0,0 +1,6 @@
+fn main() {
+    let x = 1;
+    println!("*match to this line*");
+    let y = 2;
+    println!("{}", x + y); // *match to this line* too
+}
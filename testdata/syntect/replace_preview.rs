@@ -0,0 +1,10 @@
+fn main() {
+    let greeting = "hello";
+    println!("{}: *match to this line*", greeting);
+    let items = vec![1, 2, 3];
+    for item in items {
+        println!("{}", item);
+    }
+    let message = format!("{} {}", greeting, "*match to the long line*, which is long enough to be wrapped");
+    println!("{}", message);
+}
@@ -0,0 +1,5 @@
+fn main() {   
+    // trailing tab	
+    println!("*match to this line*");  
+
+}
@@ -0,0 +1,4 @@
+fn main() {
+    println!("*match to this line*xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx");
+// yyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyz
+}
@@ -0,0 +1,8 @@
+fn main() {
+	let table = [
+		("a",	1,	"x"),
+		("abcdef",	12,	"xy"),
+		("abcdefgh",	123,	"xyz"),
+	];
+	println!("*match to this line*	{:?}", table);
+}
@@ -0,0 +1,21 @@
+fn main() {
+    foo();
+    bar();
+
+    println!("*match to this line*");
+
+    foo();
+    bar();
+
+
+
+
+
+
+
+    foo();
+    bar();
+
+    println!("*match to this line*");
+    foo();
+}
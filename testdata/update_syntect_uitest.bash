@@ -20,10 +20,13 @@ COLORTERM='' "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --background              ./testdata/syntect/background.rs                   > ./testdata/syntect/background.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/default.rs                      > ./testdata/syntect/default.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --tab 0                   ./testdata/syntect/hard_tab.rs                     > ./testdata/syntect/hard_tab.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --tab 0                   ./testdata/syntect/hard_tab_wrap.rs                > ./testdata/syntect/hard_tab_wrap.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/long_line.rs                    > ./testdata/syntect/long_line.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --background              ./testdata/syntect/long_line_bg.rs                 > ./testdata/syntect/long_line_bg.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --no-grid                 ./testdata/syntect/no_grid.rs                      > ./testdata/syntect/no_grid.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --tab 2                   ./testdata/syntect/tab_width_2.rs                  > ./testdata/syntect/tab_width_2.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --align-tabs              ./testdata/syntect/tab_align.rs                    > ./testdata/syntect/tab_align.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/tab_no_align.rs                 > ./testdata/syntect/tab_no_align.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --theme Nord              ./testdata/syntect/theme.rs                        > ./testdata/syntect/theme.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/empty_lines.rs                  > ./testdata/syntect/empty_lines.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --background              ./testdata/syntect/empty_lines_bg.rs               > ./testdata/syntect/empty_lines_bg.out
@@ -44,6 +47,59 @@ COLORTERM='' "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/multi_chunks_default.rs         > ./testdata/syntect/multi_chunks_default.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --no-grid                 ./testdata/syntect/multi_chunks_no_grid.rs         > ./testdata/syntect/multi_chunks_no_grid.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --background              ./testdata/syntect/multi_chunks_bg.rs              > ./testdata/syntect/multi_chunks_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --separator-gap           ./testdata/syntect/separator_gap.rs                > ./testdata/syntect/separator_gap.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --header-path-width 60 --match-sparkline ./testdata/syntect/header_path_width.rs > ./testdata/syntect/header_path_width.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --transparent --background ./testdata/syntect/transparent.rs           > ./testdata/syntect/transparent.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --header-align right --background ./testdata/syntect/header_align_right.rs > ./testdata/syntect/header_align_right.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --auto-truncate-after 2 ./testdata/syntect/auto_truncate_after.rs > ./testdata/syntect/auto_truncate_after.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --auto-truncate-after 2 --background ./testdata/syntect/auto_truncate_after_bg.rs > ./testdata/syntect/auto_truncate_after_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --continuation-line-numbers ./testdata/syntect/continuation_line_numbers.rs > ./testdata/syntect/continuation_line_numbers.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --continuation-line-numbers --background ./testdata/syntect/continuation_line_numbers_bg.rs > ./testdata/syntect/continuation_line_numbers_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-indicator ./testdata/syntect/match_indicator.rs > ./testdata/syntect/match_indicator.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-indicator --ascii-lines --background ./testdata/syntect/match_indicator_ascii_wrap.rs > ./testdata/syntect/match_indicator_ascii_wrap.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --no-highlight ./testdata/syntect/no_highlight.rs > ./testdata/syntect/no_highlight.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --no-highlight --background ./testdata/syntect/no_highlight_bg.rs > ./testdata/syntect/no_highlight_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --border rounded ./testdata/syntect/border_rounded.rs > ./testdata/syntect/border_rounded.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --border rounded --background ./testdata/syntect/border_rounded_wrap_bg.rs > ./testdata/syntect/border_rounded_wrap_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --border rounded --wrap never ./testdata/syntect/border_rounded_no_wrap.rs > ./testdata/syntect/border_rounded_no_wrap.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --border rounded --ascii-lines --no-grid ./testdata/syntect/border_ascii_no_grid.rs > ./testdata/syntect/border_ascii_no_grid.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --border rounded --transparent --background ./testdata/syntect/border_rounded_transparent.rs > ./testdata/syntect/border_rounded_transparent.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-emphasis chunk ./testdata/syntect/match_emphasis_chunk.rs > ./testdata/syntect/match_emphasis_chunk.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-emphasis none ./testdata/syntect/match_emphasis_none.rs > ./testdata/syntect/match_emphasis_none.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-emphasis none --background ./testdata/syntect/match_emphasis_none_bg.rs > ./testdata/syntect/match_emphasis_none_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 ./testdata/syntect/match_first_line.rs > ./testdata/syntect/match_first_line.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 ./testdata/syntect/match_last_line.rs > ./testdata/syntect/match_last_line.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --edge-separators ./testdata/syntect/edge_separators.rs > ./testdata/syntect/edge_separators.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --edge-separators ./testdata/syntect/edge_separators_file_edges.rs > ./testdata/syntect/edge_separators_file_edges.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --highlight-budget 0 ./testdata/syntect/highlight_budget_zero.rs > ./testdata/syntect/highlight_budget_zero.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --line-count ./testdata/syntect/show_line_count.rs > ./testdata/syntect/show_line_count.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-color '#369' ./testdata/syntect/match_color.rs > ./testdata/syntect/match_color.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --focus-match ./testdata/syntect/focus_match.rs > ./testdata/syntect/focus_match.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --focus-match --background ./testdata/syntect/focus_match_bg.rs > ./testdata/syntect/focus_match_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --show-eol '$' ./testdata/syntect/show_eol.rs > ./testdata/syntect/show_eol.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --show-eol '¶' --background ./testdata/syntect/show_eol_wrap.rs > ./testdata/syntect/show_eol_wrap.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --numbering chunk ./testdata/syntect/numbering_chunk_local.rs > ./testdata/syntect/numbering_chunk_local.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --min-text-width 76 ./testdata/syntect/stacked_layout.rs > ./testdata/syntect/stacked_layout.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --min-text-width 76 --background ./testdata/syntect/stacked_layout_bg.rs > ./testdata/syntect/stacked_layout_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --collapse-repeats ./testdata/syntect/collapse_repeats.rs > ./testdata/syntect/collapse_repeats.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --header-lines matches ./testdata/syntect/header_lines_matches.rs > ./testdata/syntect/header_lines_matches.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --header-lines chunks ./testdata/syntect/header_lines_chunks.rs > ./testdata/syntect/header_lines_chunks.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --gutter-only ./testdata/syntect/gutter_only.rs > ./testdata/syntect/gutter_only.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --gutter-only --numbering chunk ./testdata/syntect/gutter_only_chunk_local.rs > ./testdata/syntect/gutter_only_chunk_local.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --hanging-indent ./testdata/syntect/hanging_indent.rs > ./testdata/syntect/hanging_indent.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --hanging-indent --background ./testdata/syntect/hanging_indent_bg.rs > ./testdata/syntect/hanging_indent_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --min-lnum-width 5 ./testdata/syntect/min_lnum_width.rs > ./testdata/syntect/min_lnum_width.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --no-highlight-matches ./testdata/syntect/no_highlight_matches.rs > ./testdata/syntect/no_highlight_matches.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --no-highlight-matches --background ./testdata/syntect/no_highlight_matches_bg.rs > ./testdata/syntect/no_highlight_matches_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --plain-gutter ./testdata/syntect/plain_gutter.rs > ./testdata/syntect/plain_gutter.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --bracket-colors          ./testdata/syntect/bracket_colors.rs               > ./testdata/syntect/bracket_colors.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --bracket-colors --color-level 16 ./testdata/syntect/bracket_colors_ansi16.rs > ./testdata/syntect/bracket_colors_ansi16.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --wrap never --overflow-tooltip ./testdata/syntect/overflow_tooltip.rs        > ./testdata/syntect/overflow_tooltip.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --wrap never --overflow-tooltip --background ./testdata/syntect/overflow_tooltip_bg.rs > ./testdata/syntect/overflow_tooltip_bg.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-sparkline         ./testdata/syntect/match_sparkline.rs              > ./testdata/syntect/match_sparkline.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --match-sparkline --ascii-lines ./testdata/syntect/match_sparkline_ascii.rs > ./testdata/syntect/match_sparkline_ascii.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --indent-guides           ./testdata/syntect/indent_guides.rs                > ./testdata/syntect/indent_guides.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --indent-guides --tab 0   ./testdata/syntect/indent_guides_hard_tab.rs       > ./testdata/syntect/indent_guides_hard_tab.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/japanese_default.rs             > ./testdata/syntect/japanese_default.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --background              ./testdata/syntect/japanese_background.rs          > ./testdata/syntect/japanese_background.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/wrap_japanese_after.rs          > ./testdata/syntect/wrap_japanese_after.out
@@ -73,6 +129,8 @@ COLORTERM='' "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/wrap_whole_3_lines.rs           > ./testdata/syntect/wrap_whole_3_lines.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/wrap_3_lines_emoji.rs           > ./testdata/syntect/wrap_3_lines_emoji.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --first-only              ./testdata/syntect/first_only.rs                   > ./testdata/syntect/first_only.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --first-only=match        ./testdata/syntect/first_match_only.rs             > ./testdata/syntect/first_match_only.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --trim-chunk-blanks       ./testdata/syntect/trim_chunk_blanks.rs            > ./testdata/syntect/trim_chunk_blanks.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --ascii-lines             ./testdata/syntect/ascii_lines_grid.rs             > ./testdata/syntect/ascii_lines_grid.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 --ascii-lines --no-grid   ./testdata/syntect/ascii_lines_no_grid.rs          > ./testdata/syntect/ascii_lines_no_grid.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/multi_regions.rs                > ./testdata/syntect/multi_regions.out
@@ -80,6 +138,8 @@ COLORTERM='' "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/wrap_between_regions.rs         > ./testdata/syntect/wrap_between_regions.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/wrap_accross_regions.rs         > ./testdata/syntect/wrap_accross_regions.out
 "$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80                           ./testdata/syntect/wrap_regions_japanese.rs        > ./testdata/syntect/wrap_regions_japanese.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 -i --search-info          ./testdata/syntect/search_info.rs                  > ./testdata/syntect/search_info.out
+"$HGREP" '\*match to .+? line\*' -c 6 -C 6 -p syntect --term-width 80 -i --search-info --background ./testdata/syntect/search_info_bg.rs         > ./testdata/syntect/search_info_bg.out
 
 # Test for --list-themes
 "$HGREP" --list-themes -p syntect --term-width 80              > ./testdata/syntect/list_themes_default.out
@@ -92,9 +152,12 @@ cat ./testdata/syntect/ansi256_colors.out
 cat ./testdata/syntect/background.out
 cat ./testdata/syntect/default.out
 cat ./testdata/syntect/hard_tab.out
+cat ./testdata/syntect/hard_tab_wrap.out
 cat ./testdata/syntect/long_line.out
 cat ./testdata/syntect/no_grid.out
 cat ./testdata/syntect/tab_width_2.out
+cat ./testdata/syntect/tab_align.out
+cat ./testdata/syntect/tab_no_align.out
 cat ./testdata/syntect/theme.out
 cat ./testdata/syntect/empty_lines.out
 cat ./testdata/syntect/empty_lines_bg.out
@@ -115,6 +178,59 @@ cat ./testdata/syntect/multi_line_numbers.out
 cat ./testdata/syntect/multi_chunks_default.out
 cat ./testdata/syntect/multi_chunks_no_grid.out
 cat ./testdata/syntect/multi_chunks_bg.out
+cat ./testdata/syntect/separator_gap.out
+cat ./testdata/syntect/header_path_width.out
+cat ./testdata/syntect/transparent.out
+cat ./testdata/syntect/header_align_right.out
+cat ./testdata/syntect/auto_truncate_after.out
+cat ./testdata/syntect/auto_truncate_after_bg.out
+cat ./testdata/syntect/continuation_line_numbers.out
+cat ./testdata/syntect/continuation_line_numbers_bg.out
+cat ./testdata/syntect/match_indicator.out
+cat ./testdata/syntect/match_indicator_ascii_wrap.out
+cat ./testdata/syntect/no_highlight.out
+cat ./testdata/syntect/no_highlight_bg.out
+cat ./testdata/syntect/border_rounded.out
+cat ./testdata/syntect/border_rounded_wrap_bg.out
+cat ./testdata/syntect/border_rounded_no_wrap.out
+cat ./testdata/syntect/border_ascii_no_grid.out
+cat ./testdata/syntect/border_rounded_transparent.out
+cat ./testdata/syntect/match_emphasis_chunk.out
+cat ./testdata/syntect/match_emphasis_none.out
+cat ./testdata/syntect/match_emphasis_none_bg.out
+cat ./testdata/syntect/match_first_line.out
+cat ./testdata/syntect/match_last_line.out
+cat ./testdata/syntect/edge_separators.out
+cat ./testdata/syntect/edge_separators_file_edges.out
+cat ./testdata/syntect/highlight_budget_zero.out
+cat ./testdata/syntect/show_line_count.out
+cat ./testdata/syntect/match_color.out
+cat ./testdata/syntect/focus_match.out
+cat ./testdata/syntect/focus_match_bg.out
+cat ./testdata/syntect/show_eol.out
+cat ./testdata/syntect/show_eol_wrap.out
+cat ./testdata/syntect/numbering_chunk_local.out
+cat ./testdata/syntect/stacked_layout.out
+cat ./testdata/syntect/stacked_layout_bg.out
+cat ./testdata/syntect/collapse_repeats.out
+cat ./testdata/syntect/header_lines_matches.out
+cat ./testdata/syntect/header_lines_chunks.out
+cat ./testdata/syntect/gutter_only.out
+cat ./testdata/syntect/gutter_only_chunk_local.out
+cat ./testdata/syntect/hanging_indent.out
+cat ./testdata/syntect/hanging_indent_bg.out
+cat ./testdata/syntect/min_lnum_width.out
+cat ./testdata/syntect/no_highlight_matches.out
+cat ./testdata/syntect/no_highlight_matches_bg.out
+cat ./testdata/syntect/plain_gutter.out
+cat ./testdata/syntect/bracket_colors.out
+cat ./testdata/syntect/bracket_colors_ansi16.out
+cat ./testdata/syntect/overflow_tooltip.out
+cat ./testdata/syntect/overflow_tooltip_bg.out
+cat ./testdata/syntect/match_sparkline.out
+cat ./testdata/syntect/match_sparkline_ascii.out
+cat ./testdata/syntect/indent_guides.out
+cat ./testdata/syntect/indent_guides_hard_tab.out
 cat ./testdata/syntect/japanese_default.out
 cat ./testdata/syntect/japanese_background.out
 cat ./testdata/syntect/wrap_japanese_after.out
@@ -144,6 +260,8 @@ cat ./testdata/syntect/whole_line.out
 cat ./testdata/syntect/wrap_whole_3_lines.out
 cat ./testdata/syntect/wrap_3_lines_emoji.out
 cat ./testdata/syntect/first_only.out
+cat ./testdata/syntect/first_match_only.out
+cat ./testdata/syntect/trim_chunk_blanks.out
 cat ./testdata/syntect/ascii_lines_grid.out
 cat ./testdata/syntect/ascii_lines_no_grid.out
 cat ./testdata/syntect/multi_regions.out
@@ -151,6 +269,8 @@ cat ./testdata/syntect/multi_regions_bg.out
 cat ./testdata/syntect/wrap_between_regions.out
 cat ./testdata/syntect/wrap_accross_regions.out
 cat ./testdata/syntect/wrap_regions_japanese.out
+cat ./testdata/syntect/search_info.out
+cat ./testdata/syntect/search_info_bg.out
 
 cat ./testdata/syntect/list_themes_default.out
 cat ./testdata/syntect/list_themes_no_grid.out